@@ -0,0 +1,47 @@
+//! Criterion benchmark for the liveness hot path.
+//!
+//! `/health` is hit by every orchestrator probe in the fleet, so its
+//! handler is held to a different standard than the rest of the table:
+//! no heap allocation, no state, no serialization. This benchmark
+//! measures the handler future alone and the handler plus response
+//! assembly, so a regression in either shows up as a step change. The
+//! companion `health_alloc` test binary pins the zero-allocation
+//! property as a hard assertion.
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use axum::response::IntoResponse;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Drive a future that must complete without yielding. The liveness
+/// handler has no await points, so a single poll with a no-op waker
+/// resolves it — no executor in the loop means the measurement covers
+/// the handler and nothing else.
+fn poll_ready<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    let mut context = Context::from_waker(&waker);
+    match pin!(future).poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("the liveness handler never awaits"),
+    }
+}
+
+fn bench_health(c: &mut Criterion) {
+    c.bench_function("health_check", |b| {
+        b.iter(|| poll_ready(rust_basic_api::routes::health_check()))
+    });
+    c.bench_function("health_check_into_response", |b| {
+        b.iter(|| poll_ready(rust_basic_api::routes::health_check()).into_response())
+    });
+}
+
+criterion_group!(benches, bench_health);
+criterion_main!(benches);
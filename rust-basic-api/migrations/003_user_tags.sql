@@ -0,0 +1,15 @@
+-- Labels for users ("beta", "vip", ...) with a join table so a tag can be
+-- attached to any number of users. Hard-deleting a user or a tag drops the
+-- associations with it.
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS user_tags (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_tags_tag_id ON user_tags(tag_id);
@@ -0,0 +1,5 @@
+-- Row-level attribution: which API principal created and last modified
+-- each user. Nullable because rows predating this migration have no
+-- recorded principal.
+ALTER TABLE users ADD COLUMN created_by TEXT;
+ALTER TABLE users ADD COLUMN updated_by TEXT;
@@ -0,0 +1,12 @@
+-- Content-addressable avatar storage: blobs are keyed by their SHA-256 so
+-- identical uploads share one row, and users point at a blob through
+-- avatar_hash. No cascade from users: a blob may still be referenced by
+-- another user when one of them is deleted.
+CREATE TABLE IF NOT EXISTS user_avatars (
+    hash TEXT PRIMARY KEY,
+    content_type TEXT NOT NULL,
+    bytes BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE users ADD COLUMN avatar_hash TEXT REFERENCES user_avatars(hash);
@@ -0,0 +1,33 @@
+-- Row-level change log for compliance: every insert/update/delete on
+-- `users` is captured with full before/after images by a trigger, in the
+-- same transaction as the change. Merge soft-deletes its duplicate (an
+-- update setting `deleted_at`); `DELETE /users/:id` is a hard DELETE, and
+-- the trigger captures its final row image like any other.
+CREATE TABLE user_audit (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    old_row JSONB,
+    new_row JSONB
+);
+
+CREATE INDEX idx_user_audit_user_id ON user_audit(user_id);
+
+CREATE OR REPLACE FUNCTION record_user_audit() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO user_audit (user_id, action, old_row, new_row)
+    VALUES (
+        COALESCE(NEW.id, OLD.id),
+        LOWER(TG_OP),
+        CASE WHEN TG_OP IN ('UPDATE', 'DELETE') THEN to_jsonb(OLD) END,
+        CASE WHEN TG_OP IN ('INSERT', 'UPDATE') THEN to_jsonb(NEW) END
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS users_record_audit ON users;
+CREATE TRIGGER users_record_audit
+    AFTER INSERT OR UPDATE OR DELETE ON users
+    FOR EACH ROW EXECUTE FUNCTION record_user_audit();
@@ -0,0 +1,12 @@
+-- Shared key-value state for cross-replica bookkeeping (rate-limit
+-- counters, replay markers) when `STATE_BACKEND=postgres`. Every entry
+-- expires; rows past `expires_at` are treated as absent by the reader
+-- and reaped opportunistically by writers, so the table stays small
+-- without a dedicated sweeper.
+CREATE TABLE app_kv (
+    key TEXT PRIMARY KEY,
+    value JSONB NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_app_kv_expires_at ON app_kv(expires_at);
@@ -0,0 +1,16 @@
+-- Two-phase email changes: the requested address parks in pending_email
+-- and only lands on email once the owner presents the confirmation token,
+-- so a typo cannot lock anyone out of their account. Tokens are stored as
+-- SHA-256 hashes — a leaked table cannot be replayed as confirmations —
+-- and are deleted on use, making them single-use.
+ALTER TABLE users ADD COLUMN pending_email VARCHAR(255);
+
+CREATE TABLE IF NOT EXISTS email_change_tokens (
+    token_hash TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    new_email VARCHAR(255) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_change_tokens_user_id ON email_change_tokens(user_id);
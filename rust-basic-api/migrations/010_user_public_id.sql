@@ -0,0 +1,9 @@
+-- Non-sequential public identifier: clients can reference users without
+-- exposing the enumerable SERIAL id, and a leaked identifier is rotated
+-- rather than lived with. pgcrypto supplies gen_random_uuid() on Postgres
+-- versions where it is not yet built in.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+ALTER TABLE users ADD COLUMN public_id UUID NOT NULL DEFAULT gen_random_uuid();
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_public_id ON users(public_id);
@@ -0,0 +1,10 @@
+-- `audit_log.user_id` was declared with no ON DELETE action, so a hard
+-- DELETE of any user holding audit rows failed the foreign key and
+-- surfaced as a masked 500. Every merged-into primary holds such rows —
+-- merge re-points the duplicate's entries onto it — so merge-then-delete
+-- was guaranteed to break. The entries describe the user; they go with
+-- it. (`user_audit` is unaffected: it carries no foreign key by design,
+-- preserving history past deletion.)
+ALTER TABLE audit_log DROP CONSTRAINT audit_log_user_id_fkey;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
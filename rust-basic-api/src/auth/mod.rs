@@ -0,0 +1,435 @@
+//! Bearer-token authorization.
+//!
+//! Authorization is driven by scopes carried in a JWT (`Authorization:
+//! Bearer ...`, HS256, secret from `AUTH_JWT_SECRET`). When no secret is
+//! configured authorization is disabled and every request passes, which
+//! keeps local development and the default deployment working unchanged.
+
+use std::marker::PhantomData;
+
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::AppState;
+
+/// Scope names live here so route groups and tests cannot drift apart.
+pub mod scopes {
+    /// Read access to user endpoints.
+    pub const USERS_READ: &str = "users:read";
+    /// Mutating access to user endpoints.
+    pub const USERS_WRITE: &str = "users:write";
+    /// Administrative endpoints; implies every other scope.
+    pub const ADMIN: &str = "admin";
+}
+
+/// Claims we care about; everything else in the token is ignored.
+#[derive(Debug, Deserialize)]
+pub struct Claims {
+    #[serde(default)]
+    pub scope: Option<ScopeClaim>,
+    #[serde(default)]
+    pub sub: Option<String>,
+}
+
+/// The `scope` claim as issued by different identity providers: either a
+/// space-delimited string or an array of strings.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ScopeClaim {
+    List(Vec<String>),
+    Spaced(String),
+}
+
+impl Claims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scope {
+            Some(ScopeClaim::List(list)) => list.iter().any(|s| s == scope),
+            Some(ScopeClaim::Spaced(spaced)) => spaced.split_whitespace().any(|s| s == scope),
+            None => false,
+        }
+    }
+}
+
+/// Marker types naming the scope a route group requires.
+pub trait RequiredScope: Send + Sync {
+    const NAME: &'static str;
+}
+
+pub struct UsersRead;
+impl RequiredScope for UsersRead {
+    const NAME: &'static str = scopes::USERS_READ;
+}
+
+pub struct UsersWrite;
+impl RequiredScope for UsersWrite {
+    const NAME: &'static str = scopes::USERS_WRITE;
+}
+
+pub struct Admin;
+impl RequiredScope for Admin {
+    const NAME: &'static str = scopes::ADMIN;
+}
+
+/// Extractor enforcing that the caller's token carries the given scope (or
+/// `admin`, which passes everything). Rejects with 401 for missing/invalid
+/// tokens and 403 naming the missing scope.
+pub struct RequireScope<S>(PhantomData<S>);
+
+#[axum::async_trait]
+impl<S: RequiredScope> FromRequestParts<AppState> for RequireScope<S> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(secret) = &state.config.auth_jwt_secret else {
+            // Authorization disabled.
+            return Ok(Self(PhantomData));
+        };
+        // In strict-skew mode (`CLOCK_SKEW_MAX_AUTH_SECS`) a clock this
+        // far from the database's cannot judge tokens; refuse rather than
+        // accept what may be an expired credential.
+        if !state.clock.auth_trusted() {
+            return Err(AppError::http(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "token validation suspended: host clock skew exceeds the configured limit",
+            ));
+        }
+
+        let claims = decode_claims(parts, secret)?;
+        if claims.has_scope(S::NAME) || claims.has_scope(scopes::ADMIN) {
+            Ok(Self(PhantomData))
+        } else {
+            Err(AppError::http(
+                StatusCode::FORBIDDEN,
+                format!("missing required scope `{}`", S::NAME),
+            ))
+        }
+    }
+}
+
+/// The caller's principal and privileges, for row-level attribution and
+/// scope-based response shaping. Never rejects: an unauthenticated or
+/// invalid caller is `anonymous`, and when authorization is disabled every
+/// caller counts as admin, consistent with [`RequireScope`] passing
+/// everything in that mode.
+pub struct Caller {
+    /// JWT `sub` claim, `authenticated` for a valid token without one, or
+    /// `anonymous`.
+    pub principal: String,
+    /// Whether the caller holds the admin scope.
+    pub is_admin: bool,
+}
+
+impl Caller {
+    /// Whether the caller presented a valid bearer token. With
+    /// authorization disabled no caller can, so everyone counts as
+    /// unauthenticated here even though [`RequireScope`] admits them.
+    pub fn is_authenticated(&self) -> bool {
+        self.principal != crate::middleware::usage::ANONYMOUS
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(secret) = &state.config.auth_jwt_secret else {
+            return Ok(Self {
+                principal: crate::middleware::usage::ANONYMOUS.to_string(),
+                is_admin: true,
+            });
+        };
+        Ok(match decode_claims(parts, secret) {
+            Ok(claims) => Self {
+                is_admin: claims.has_scope(scopes::ADMIN),
+                principal: claims.sub.unwrap_or_else(|| "authenticated".to_string()),
+            },
+            Err(_) => Self {
+                principal: crate::middleware::usage::ANONYMOUS.to_string(),
+                is_admin: false,
+            },
+        })
+    }
+}
+
+/// The caller identity used for per-caller usage accounting: the JWT `sub`
+/// claim when a valid token is presented, `authenticated` for a valid
+/// token without one, and [`crate::middleware::usage::ANONYMOUS`] for
+/// everything else (including when authorization is disabled).
+pub fn caller_identity(headers: &axum::http::HeaderMap, secret: Option<&str>) -> String {
+    let Some(secret) = secret else {
+        return crate::middleware::usage::ANONYMOUS.to_string();
+    };
+    match decode_claims_from_headers(headers, secret) {
+        Ok(claims) => claims.sub.unwrap_or_else(|| "authenticated".to_string()),
+        Err(_) => crate::middleware::usage::ANONYMOUS.to_string(),
+    }
+}
+
+/// Require a valid bearer token, without any particular scope. Used by
+/// endpoints whose exposure is toggled by configuration (docs, metrics)
+/// rather than tied to a scope. A deployment without a configured secret
+/// has authorization disabled and admits everyone, consistent with
+/// [`RequireScope`].
+pub fn require_authenticated(
+    headers: &axum::http::HeaderMap,
+    secret: Option<&str>,
+) -> Result<(), AppError> {
+    match secret {
+        None => Ok(()),
+        Some(secret) => decode_claims_from_headers(headers, secret).map(|_| ()),
+    }
+}
+
+fn decode_claims(parts: &Parts, secret: &str) -> Result<Claims, AppError> {
+    decode_claims_from_headers(&parts.headers, secret)
+}
+
+fn decode_claims_from_headers(
+    headers: &axum::http::HeaderMap,
+    secret: &str,
+) -> Result<Claims, AppError> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| {
+            AppError::http(StatusCode::UNAUTHORIZED, "missing bearer token".to_string())
+        })?;
+
+    // Tokens without an `exp` are admitted (internal minting predates
+    // expiry), but an `exp` that is present is honored: expired tokens
+    // must die at their expiry, or minted tokens could never be revoked.
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.required_spec_claims.clear();
+
+    jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .map(|data| data.claims)
+    .map_err(|e| AppError::http(StatusCode::UNAUTHORIZED, format!("invalid token: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use jsonwebtoken::{EncodingKey, Header};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    const SECRET: &str = "test-secret";
+
+    fn mint(scope: serde_json::Value) -> String {
+        jsonwebtoken::encode(
+            &Header::default(),
+            &serde_json::json!({ "scope": scope }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn authed_state() -> crate::AppState {
+        let mut state = test_state();
+        state.config.auth_jwt_secret = Some(SECRET.to_string());
+        state
+    }
+
+    async fn request(
+        app: &axum::Router,
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+    ) -> StatusCode {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {token}"));
+        }
+        let body = if method == "POST" {
+            builder = builder.header("content-type", "application/json");
+            Body::from(r#"{"name":"Scope Test","email":"scope@example.com"}"#)
+        } else {
+            Body::empty()
+        };
+        app.clone()
+            .oneshot(builder.body(body).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn read_scope_allows_reads_but_not_writes() {
+        let app = test_app(authed_state());
+        let token = mint(serde_json::json!("users:read"));
+
+        assert_eq!(
+            request(&app, "GET", "/users", Some(&token)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            request(&app, "POST", "/users", Some(&token)).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn write_scope_as_array_claim_allows_writes() {
+        let app = test_app(authed_state());
+        let token = mint(serde_json::json!(["users:write"]));
+
+        assert_eq!(
+            request(&app, "POST", "/users", Some(&token)).await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            request(&app, "GET", "/users", Some(&token)).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_scope_passes_every_route_group() {
+        let app = test_app(authed_state());
+        let token = mint(serde_json::json!("admin"));
+
+        assert_eq!(
+            request(&app, "GET", "/users", Some(&token)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            request(&app, "POST", "/users", Some(&token)).await,
+            StatusCode::CREATED
+        );
+        // Admin endpoint responds (500 here: test state has no pool), but
+        // authorization passed.
+        assert_eq!(
+            request(&app, "POST", "/admin/pool/recycle", Some(&token)).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_unauthorized() {
+        let app = test_app(authed_state());
+        assert_eq!(
+            request(&app, "GET", "/users", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    /// An `exp` in the past must be fatal even though exp-less tokens
+    /// are admitted: expiry is the only revocation these tokens have.
+    #[tokio::test]
+    async fn an_expired_token_is_unauthorized() {
+        let app = test_app(authed_state());
+
+        let mint_with_exp = |exp: i64| {
+            jsonwebtoken::encode(
+                &Header::default(),
+                &serde_json::json!({ "scope": "admin", "exp": exp }),
+                &EncodingKey::from_secret(SECRET.as_bytes()),
+            )
+            .unwrap()
+        };
+
+        let expired = mint_with_exp(chrono::Utc::now().timestamp() - 3600);
+        assert_eq!(
+            request(&app, "GET", "/users", Some(&expired)).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // A live `exp` still passes, so honoring expiry does not reject
+        // well-formed tokens early.
+        let live = mint_with_exp(chrono::Utc::now().timestamp() + 3600);
+        assert_eq!(
+            request(&app, "GET", "/users", Some(&live)).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn no_secret_disables_authorization() {
+        let app = test_app(test_state());
+        assert_eq!(request(&app, "GET", "/users", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn docs_are_public_by_default() {
+        let app = test_app(test_state());
+        assert_eq!(request(&app, "GET", "/docs", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn docs_behind_auth_admit_any_valid_token() {
+        let mut state = authed_state();
+        state.config.docs_require_auth = true;
+        let app = test_app(state);
+
+        assert_eq!(
+            request(&app, "GET", "/docs", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let token = mint(serde_json::json!("users:read"));
+        assert_eq!(
+            request(&app, "GET", "/docs", Some(&token)).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_docs_are_indistinguishable_from_unknown_routes() {
+        use http_body_util::BodyExt;
+
+        let mut state = test_state();
+        state.config.docs_enabled = false;
+        let app = test_app(state);
+
+        let fetch = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
+        let docs = fetch("/docs").await;
+        let unknown = fetch("/no-such-route").await;
+
+        assert_eq!(docs.status(), StatusCode::NOT_FOUND);
+        assert_eq!(docs.status(), unknown.status());
+        let docs_body = docs.into_body().collect().await.unwrap().to_bytes();
+        let unknown_body = unknown.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(docs_body, unknown_body);
+    }
+
+    #[tokio::test]
+    async fn metrics_can_be_put_behind_auth() {
+        let mut state = authed_state();
+        state.config.metrics_require_auth = true;
+        let app = test_app(state);
+
+        assert_eq!(
+            request(&app, "GET", "/metrics", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let token = mint(serde_json::json!("users:read"));
+        assert_eq!(
+            request(&app, "GET", "/metrics", Some(&token)).await,
+            StatusCode::OK
+        );
+    }
+}
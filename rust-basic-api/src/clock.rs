@@ -0,0 +1,254 @@
+//! Host clock sanity check against the database's `now()`.
+//!
+//! Idempotency TTLs, token validation, and audit timestamps all assume
+//! the host clock is sane, and a container with broken NTP drifts
+//! silently. The database clock is one we already trust — every
+//! `created_at` comes from it — so a background monitor compares the two
+//! at startup and every [`SAMPLE_INTERVAL`], exporting the signed
+//! difference as the `clock_skew_seconds` gauge and logging a warning
+//! when the absolute skew exceeds `CLOCK_SKEW_WARN_SECS`.
+//!
+//! Optionally (`CLOCK_SKEW_MAX_AUTH_SECS`), bearer-token authorization
+//! refuses to run while the skew exceeds a second, larger threshold:
+//! with the clock that far out, any time-based token judgement would be
+//! wrong anyway, and a 503 is more honest than quietly accepting what
+//! may be an expired credential.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use prometheus::{register_gauge, Gauge};
+
+/// How often the monitor re-compares the clocks after the startup check.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Local time minus database time, in seconds, at the last comparison.
+static CLOCK_SKEW_SECONDS: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "clock_skew_seconds",
+        "Host clock minus database clock, seconds"
+    )
+    .expect("metric registration")
+});
+
+/// The most recent clock comparison, shared through [`crate::AppState`]
+/// so the auth path can consult it without a database round trip.
+#[derive(Default)]
+pub struct ClockStatus {
+    /// Signed skew in milliseconds at the last comparison.
+    skew_millis: AtomicI64,
+    auth_blocked: AtomicBool,
+}
+
+impl ClockStatus {
+    /// Record one local-vs-database comparison: update the gauge, warn
+    /// when the absolute skew exceeds `warn_threshold`, and set or clear
+    /// the auth block against `max_auth_skew` (`None` disables blocking).
+    pub fn observe(
+        &self,
+        local: DateTime<Utc>,
+        database: DateTime<Utc>,
+        warn_threshold: Duration,
+        max_auth_skew: Option<Duration>,
+    ) {
+        let skew_millis = local.timestamp_millis() - database.timestamp_millis();
+        self.skew_millis.store(skew_millis, Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss)]
+        let skew_seconds = skew_millis as f64 / 1000.0;
+        CLOCK_SKEW_SECONDS.set(skew_seconds);
+
+        let abs = Duration::from_millis(skew_millis.unsigned_abs());
+        if abs > warn_threshold {
+            tracing::warn!(
+                skew_seconds,
+                threshold_secs = warn_threshold.as_secs(),
+                "host clock disagrees with the database; check NTP on this host"
+            );
+        }
+        self.auth_blocked.store(
+            max_auth_skew.is_some_and(|max| abs > max),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Whether the clock is trustworthy enough to judge tokens by. Always
+    /// true before the first comparison and when blocking is disabled.
+    pub fn auth_trusted(&self) -> bool {
+        !self.auth_blocked.load(Ordering::Relaxed)
+    }
+
+    /// The signed skew at the last comparison, in seconds.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn skew_seconds(&self) -> f64 {
+        self.skew_millis.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+}
+
+/// Run the clock monitor until `shutdown` resolves: compare immediately
+/// (the startup check), then every [`SAMPLE_INTERVAL`]. A failed query
+/// leaves the previous verdict standing — a database outage is its own
+/// alert, not evidence about the clock.
+pub async fn monitor(
+    pool: sqlx::PgPool,
+    status: Arc<ClockStatus>,
+    warn_threshold: Duration,
+    max_auth_skew: Option<Duration>,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    let mut tick = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                match sqlx::query_as::<_, (DateTime<Utc>,)>("SELECT now()")
+                    .fetch_one(&pool)
+                    .await
+                {
+                    Ok((database,)) => {
+                        status.observe(Utc::now(), database, warn_threshold, max_auth_skew);
+                    }
+                    Err(error) => {
+                        tracing::debug!(%error, "clock comparison query failed; keeping last verdict");
+                    }
+                }
+            }
+            () = &mut shutdown => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use chrono::{Duration as ChronoDuration, Utc};
+    use tower::ServiceExt;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Capture {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = CaptureWriter;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            CaptureWriter(self.0.clone())
+        }
+    }
+
+    struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CaptureWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The `clock_skew_seconds` value in the current metrics rendering.
+    fn gauge_value() -> f64 {
+        crate::metrics::render()
+            .lines()
+            .find(|line| line.starts_with("clock_skew_seconds "))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|value| value.parse().ok())
+            .expect("clock_skew_seconds exported")
+    }
+
+    #[test]
+    fn skew_beyond_the_threshold_warns_and_sets_the_gauge() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(capture.clone())
+            .with_ansi(false)
+            .finish();
+        let status = ClockStatus::default();
+        let database = Utc::now();
+
+        tracing::subscriber::with_default(subscriber, || {
+            // Within the threshold: silent.
+            status.observe(
+                database + ChronoDuration::seconds(2),
+                database,
+                Duration::from_secs(5),
+                None,
+            );
+            assert!(!capture.contents().contains("disagrees with the database"));
+
+            // Nine seconds ahead of the database: warned and exported.
+            status.observe(
+                database + ChronoDuration::seconds(9),
+                database,
+                Duration::from_secs(5),
+                None,
+            );
+        });
+
+        assert!(capture.contents().contains("disagrees with the database"));
+        assert!((status.skew_seconds() - 9.0).abs() < 0.5);
+        assert!((gauge_value() - 9.0).abs() < 0.5);
+    }
+
+    #[tokio::test]
+    async fn excessive_skew_blocks_token_validation_in_strict_mode() {
+        use jsonwebtoken::{EncodingKey, Header};
+
+        let status = Arc::new(ClockStatus::default());
+        let mut state = crate::test_helpers::test_state();
+        state.config.auth_jwt_secret = Some("test-secret".to_string());
+        state.clock = status.clone();
+        let app = crate::test_helpers::test_app(state);
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &serde_json::json!({ "scope": "users:read" }),
+            &EncodingKey::from_secret(b"test-secret"),
+        )
+        .unwrap();
+        let list = || {
+            Request::builder()
+                .uri("/users")
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // A minute of skew with a 30-second limit: tokens are not judged.
+        let database = Utc::now();
+        status.observe(
+            database + ChronoDuration::seconds(60),
+            database,
+            Duration::from_secs(5),
+            Some(Duration::from_secs(30)),
+        );
+        let response = app.clone().oneshot(list()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The clock recovering clears the block on the next comparison.
+        status.observe(
+            database,
+            database,
+            Duration::from_secs(5),
+            Some(Duration::from_secs(30)),
+        );
+        let response = app.oneshot(list()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
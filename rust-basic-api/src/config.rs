@@ -0,0 +1,685 @@
+use std::env;
+
+/// Application configuration loaded from environment variables.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    pub server_port: u16,
+    /// Deployment environment (`ENVIRONMENT`, default `development`).
+    /// Gates the `.env` auto-load in [`load_dotenv`]: only development
+    /// reads the file, so a stray `.env` baked into a production image
+    /// cannot shadow real configuration.
+    pub environment: Environment,
+    /// Bind the server socket with `SO_REUSEPORT` (Unix only), allowing a
+    /// replacement process to take over the port without a bind gap.
+    pub so_reuseport: bool,
+    /// Response headers removed from every response before it is sent.
+    pub strip_response_headers: Vec<String>,
+    /// Attach an `X-Route` header naming the matched route template
+    /// (`/users/:id`, not `/users/42`) to every response
+    /// (`EXPOSE_ROUTE_HEADER`), for client-side metrics keyed by route
+    /// without high-cardinality paths.
+    pub expose_route_header: bool,
+    /// Origins granted CORS access (`CORS_ALLOWED_ORIGINS`, comma-
+    /// separated); empty disables CORS entirely. Hot-reloadable through
+    /// `POST /admin/cors/reload`.
+    pub cors_allowed_origins: Vec<String>,
+    /// Responses larger than this (bytes) log a warning and increment
+    /// `http_response_oversized_total`.
+    pub response_size_soft_limit: Option<u64>,
+    /// Responses larger than this (bytes) have their body stream aborted.
+    pub response_size_hard_limit: Option<u64>,
+    /// Milliseconds allowed for establishing a brand-new database
+    /// connection (TCP/TLS plus the Postgres handshake), distinct from the
+    /// pool's `acquire_timeout` which covers waiting for an existing
+    /// pooled connection. During a failover a half-dead host can accept
+    /// the TCP connect and then hang; this bound turns that into a prompt
+    /// failure.
+    pub database_connect_timeout_ms: u64,
+    /// Seconds to keep serving after readiness flips false on shutdown, so
+    /// load balancers can drain us. Set via `PRESHUTDOWN_DELAY_SECS`
+    /// (`DRAIN_DELAY_SECS` is accepted as a legacy alias).
+    pub drain_delay_secs: u64,
+    /// Prefix the whole API is served under, for reverse proxies that do
+    /// not strip their routing path. Empty means no prefix.
+    pub base_path: String,
+    /// Relocate the detailed health endpoints (`/health/ready`,
+    /// `/health/migrations`) under this prefix (`HEALTH_PATH`, e.g.
+    /// `/internal/healthz-<token>`), leaving their default paths 404ing.
+    /// They expose internal state — degraded modes, migration versions —
+    /// so an unguessable path limits disclosure without full auth. The
+    /// plain `/health` liveness stays where probes expect it. Unset
+    /// keeps everything under `/health`.
+    pub health_path: Option<String>,
+    /// Largest accepted avatar upload, in bytes.
+    pub avatar_max_bytes: usize,
+    /// Largest request body accepted by ordinary endpoints, in bytes.
+    /// Routes marked `bulk_body_limit` in the route table get
+    /// [`Config::bulk_max_body_bytes`] instead.
+    pub max_body_bytes: usize,
+    /// Largest request body accepted by bulk endpoints (the import route),
+    /// in bytes. Bulk payloads legitimately outgrow single-resource
+    /// bodies, and raising the global cap for their sake would weaken
+    /// protection everywhere else.
+    pub bulk_max_body_bytes: usize,
+    /// Most emails accepted by one `POST /users/lookup` batch.
+    pub lookup_max_emails: usize,
+    /// Resist email enumeration (`ENUMERATION_PROTECTION`): existence
+    /// lookups require a valid bearer token, and unauthenticated creations
+    /// answer an indistinguishable empty 202 for new and duplicate emails,
+    /// padded to a constant minimum response time. Callers presenting a
+    /// valid token keep precise responses.
+    pub enumeration_protection: bool,
+    /// Minimum response time (milliseconds) for the enumeration-protected
+    /// create path, so a duplicate cannot be told from a fresh creation by
+    /// timing either.
+    pub enumeration_min_response_ms: u64,
+    /// Seconds an email-change confirmation token stays valid after
+    /// `POST /users/:id/email-change` issues it.
+    pub email_change_token_ttl_secs: u64,
+    /// Largest `offset` accepted by `GET /users`; larger values get a 400
+    /// instead of forcing Postgres to scan and discard that many rows.
+    pub max_offset: i64,
+    /// Base maximum lifetime of a pooled connection (seconds) before it is
+    /// recycled.
+    pub db_max_lifetime_secs: u64,
+    /// Random fraction applied to the max lifetime (e.g. `0.1` for ±10%)
+    /// so warm-up-era connections do not all expire in the same instant.
+    pub db_max_lifetime_jitter: f64,
+    /// `idle_in_transaction_session_timeout` applied to every pooled
+    /// connection (milliseconds), so a buggy client holding a transaction
+    /// open gets its session terminated instead of blocking vacuum. `0`
+    /// disables the timeout.
+    pub db_idle_in_tx_timeout_ms: u64,
+    /// Pool acquisitions slower than this (milliseconds) log a starvation
+    /// warning.
+    pub db_acquire_warn_threshold_ms: u64,
+    /// Reject request bodies containing unknown fields instead of silently
+    /// ignoring them.
+    pub strict_json_fields: bool,
+    /// HS256 secret for bearer-token authorization. Unset disables
+    /// authorization entirely.
+    pub auth_jwt_secret: Option<String>,
+    /// Run pending migrations during startup. Disable when migrations are
+    /// applied out-of-band by the deploy pipeline.
+    pub run_migrations_on_startup: bool,
+    /// Guarantee this process performs no writes, for disaster-recovery
+    /// drills against a restored snapshot: mutating endpoints answer 405
+    /// with the `READ_ONLY` code, writing background workers stay parked,
+    /// and every pooled connection sets `default_transaction_read_only=on`
+    /// so anything that slips past the middleware still fails in Postgres.
+    pub read_only_mode: bool,
+    /// Refuse to start when the applied schema is older than
+    /// [`crate::repository::MIN_SCHEMA_VERSION`]. When `false`, start
+    /// anyway but report not-ready so the load balancer keeps traffic away.
+    pub schema_check_fatal: bool,
+    /// Tenant ids served by this deployment. Non-empty enables multi-tenant
+    /// mode: each tenant gets its own `tenant_<id>` schema and requests
+    /// must carry an `X-Tenant-Id` header. Empty means single-tenant.
+    pub tenants: Vec<String>,
+    /// Cache `get_user` reads in process, kept coherent across replicas by
+    /// the `user_changed` notification listener. Off by default; only the
+    /// default (non-tenant) repository is cached.
+    pub user_cache: bool,
+    /// Requests per minute allowed for `public_read` routes (health,
+    /// metrics, user reads) before the limiter answers 429.
+    pub rate_limit_public_read: u32,
+    /// Requests per minute allowed for `public_write` routes (user
+    /// mutations).
+    pub rate_limit_public_write: u32,
+    /// Requests per minute allowed for `expensive` routes (admin and
+    /// scan-heavy operations).
+    pub rate_limit_expensive: u32,
+    /// Serve the route documentation endpoint (`GET /docs`). When
+    /// disabled the path 404s exactly like an unknown route, so probes
+    /// cannot tell the endpoint exists.
+    pub docs_enabled: bool,
+    /// Require a valid bearer token for `GET /docs`.
+    pub docs_require_auth: bool,
+    /// Require a valid bearer token for `GET /metrics`.
+    pub metrics_require_auth: bool,
+    /// Skip the startup warmup phase (in-process request, serializer and
+    /// pool priming) for fast local iteration.
+    pub skip_warmup: bool,
+    /// Startup (from `run_application` entry to the listening log) slower
+    /// than this many seconds logs a warning with a per-phase breakdown, so
+    /// a deploy that suddenly boots slowly names the phase responsible.
+    pub startup_warn_secs: u64,
+    /// Seconds a streaming response gets to wrap up after the shutdown
+    /// signal fires. Kept shorter than `drain_delay_secs` so streams end
+    /// before the listener itself closes.
+    pub streaming_drain_budget_secs: u64,
+    /// Absolute skew between the host clock and the database's `now()`
+    /// beyond which the clock monitor logs a warning
+    /// (`CLOCK_SKEW_WARN_SECS`). The measured skew is always exported as
+    /// the `clock_skew_seconds` gauge.
+    pub clock_skew_warn_secs: u64,
+    /// Absolute skew beyond which bearer-token authorization answers 503
+    /// instead of judging tokens with a clock it cannot trust
+    /// (`CLOCK_SKEW_MAX_AUTH_SECS`). `0` (the default) disables the
+    /// blocking and leaves the skew as a warning only.
+    pub clock_skew_max_auth_secs: u64,
+    /// Log every SQL statement (and slow-statement warnings) the pool
+    /// executes. Off by default and intended for development: statement
+    /// text can reveal query shapes and, with inlined values, PII, so
+    /// production keeps sqlx statement logging fully disabled.
+    pub log_sql: bool,
+    /// Maximum distinct caller labels tracked for per-caller usage metrics
+    /// before further callers fold into the `other` bucket.
+    pub usage_max_callers: usize,
+    /// Connections reserved for background work (sampler, future jobs) so
+    /// it cannot starve web requests. `0` means background tasks share the
+    /// main pool.
+    pub background_pool_size: u32,
+    /// Attach a `Server-Timing` header (total/db/serialize phases) to
+    /// every response. Defaults to on unless `ENVIRONMENT=production`;
+    /// `SERVER_TIMING` overrides in either direction.
+    pub server_timing: bool,
+    /// Lowercase and trim emails on the create/update/upsert paths before
+    /// they are validated and persisted (`NORMALIZE_EMAILS`, default on).
+    /// Disable to store emails as received.
+    pub normalize_emails: bool,
+    /// Endpoint webhook events are POSTed to. Unset disables delivery
+    /// entirely (events are not even queued).
+    pub webhook_url: Option<String>,
+    /// Seconds after an event's creation during which delivery may still be
+    /// attempted (`WEBHOOK_MAX_AGE_SECS`); older events are dead-lettered
+    /// instead of retried.
+    pub webhook_max_age_secs: u64,
+    /// Path to the CA certificate (PEM) client certificates on `/admin/*`
+    /// routes must chain to (`MTLS_CA_CERT`). Unset disables mTLS and the
+    /// server speaks plain HTTP.
+    pub mtls_ca_cert: Option<String>,
+    /// Path to the server certificate chain (PEM) used when mTLS is
+    /// enabled (`MTLS_SERVER_CERT`).
+    pub mtls_server_cert: Option<String>,
+    /// Path to the server private key (PEM) used when mTLS is enabled
+    /// (`MTLS_SERVER_KEY`).
+    pub mtls_server_key: Option<String>,
+    /// Minimum TLS protocol version offered when TLS termination is
+    /// enabled (`TLS_MIN_VERSION`, `1.2` or `1.3`, default `1.2`).
+    /// Versions 1.0 and 1.1 are below the security baseline and rejected
+    /// at startup.
+    pub tls_min_version: TlsMinVersion,
+    /// Accept HTTP/2 requests (`ENABLE_HTTP2`, default off). Over plain
+    /// HTTP this permits h2c; when mTLS is configured it additionally
+    /// advertises `h2` via ALPN so TLS clients can negotiate it. Off,
+    /// the server is HTTP/1.1-only and HTTP/2 requests get a 505.
+    pub enable_http2: bool,
+    /// Availability target, in percent, that error budgets are computed
+    /// against (`SLO_AVAILABILITY`, default 99.9).
+    pub slo_availability: f64,
+    /// Maximum connections in the main pool (`DATABASE_MAX_CONNECTIONS`,
+    /// default 10). The special value `auto` sizes from available
+    /// parallelism (`cpus * 2 + 1`, capped) so heterogeneous deploy
+    /// targets right-size themselves.
+    pub database_max_connections: u32,
+    /// Where cross-request state (rate-limit counters, replay markers)
+    /// lives (`STATE_BACKEND`, default `memory`): process-local, or
+    /// shared across replicas through the `app_kv` table.
+    pub state_backend: StateBackend,
+    /// Defensive ceiling on rows a single repository query may return
+    /// (`MAX_ROWS_PER_QUERY`, default 10,000). Tripping it is treated as
+    /// a missing-LIMIT bug: the request fails with a masked 500 and an
+    /// alert is logged where the guard fired.
+    pub max_rows_per_query: usize,
+    /// Fraction of successful requests the access log keeps
+    /// (`ACCESS_LOG_SAMPLE_RATE`, 0.0–1.0, default 1.0). Error responses
+    /// are always logged regardless of the rate.
+    pub access_log_sample_rate: f64,
+    /// Paths whose requests skip the access log and the per-request
+    /// tracing span entirely (`ACCESS_LOG_QUIET_PATHS`, comma-separated,
+    /// default `/health`): liveness probes arrive at fleet scale and
+    /// would otherwise pay an allocation and a span each. Set the
+    /// variable to an empty value to quiet nothing.
+    pub access_log_quiet_paths: Vec<String>,
+    /// Maximum in-flight requests across the DB-heavy routes
+    /// (`ROUTE_CONCURRENCY_LIMIT`, default 16; `0` disables the ceiling).
+    /// Overflow is shed with a 503 so scans cannot monopolize the
+    /// connection pool.
+    pub route_concurrency_limit: usize,
+}
+
+/// Backend behind [`crate::kv::KvStore`], selected by `STATE_BACKEND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateBackend {
+    Memory,
+    Postgres,
+}
+
+/// Deployment environment, selected by `ENVIRONMENT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Staging,
+    Production,
+}
+
+/// Parse an `ENVIRONMENT` value. Like `STATE_BACKEND`, a bad value is
+/// fatal rather than a fallback: a typo silently landing on `development`
+/// would re-enable the `.env` auto-load this knob exists to disable.
+fn environment(raw: Option<&str>) -> anyhow::Result<Environment> {
+    match raw {
+        None | Some("") | Some("development") => Ok(Environment::Development),
+        Some("staging") => Ok(Environment::Staging),
+        Some("production") => Ok(Environment::Production),
+        Some(other) => {
+            anyhow::bail!("ENVIRONMENT must be development, staging, or production, got {other:?}")
+        }
+    }
+}
+
+/// Outcome of [`load_dotenv`], carried back to `main` for logging: the
+/// load runs before the tracing subscriber is installed, so the loader
+/// itself cannot log.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DotenvStatus {
+    /// `.env` was read; `contributed` counts the variables it set that
+    /// the process environment did not already define.
+    Loaded {
+        path: std::path::PathBuf,
+        contributed: usize,
+    },
+    /// No `.env` file exists.
+    NotFound,
+    /// `.env` exists but `ENVIRONMENT` is not development, so the file
+    /// was left untouched.
+    Ignored { path: std::path::PathBuf },
+}
+
+/// Load `.env` from the working directory — in development only.
+///
+/// `ENVIRONMENT` is read from the process environment alone; the file it
+/// gates gets no say, and an unparsable value counts as non-development
+/// so a typo cannot re-enable the load (`Config::from_env` reports it as
+/// the error). Variables already present in the process environment
+/// always win over the file.
+pub fn load_dotenv() -> DotenvStatus {
+    let development = matches!(
+        environment(env::var("ENVIRONMENT").ok().as_deref()),
+        Ok(Environment::Development)
+    );
+    load_dotenv_from(std::path::Path::new(".env"), development)
+}
+
+fn load_dotenv_from(path: &std::path::Path, development: bool) -> DotenvStatus {
+    if !path.exists() {
+        return DotenvStatus::NotFound;
+    }
+    if !development {
+        return DotenvStatus::Ignored {
+            path: path.to_path_buf(),
+        };
+    }
+    let Ok(entries) = dotenvy::from_path_iter(path) else {
+        // Unreadable file: the old `dotenv().ok()` shrugged too.
+        return DotenvStatus::NotFound;
+    };
+    let mut contributed = 0;
+    for entry in entries {
+        let Ok((key, value)) = entry else { continue };
+        if env::var_os(&key).is_none() {
+            env::set_var(&key, value);
+            contributed += 1;
+        }
+    }
+    DotenvStatus::Loaded {
+        path: path.to_path_buf(),
+        contributed,
+    }
+}
+
+/// Floor on the TLS protocol versions the server offers, selected by
+/// `TLS_MIN_VERSION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMinVersion {
+    V1_2,
+    V1_3,
+}
+
+/// Parse a `TLS_MIN_VERSION` value. Like `STATE_BACKEND`, a bad value is
+/// fatal rather than a fallback: a typo silently landing on 1.2 would
+/// undercut the compliance floor the knob exists to enforce.
+fn tls_min_version(raw: Option<&str>) -> anyhow::Result<TlsMinVersion> {
+    match raw {
+        None | Some("") | Some("1.2") => Ok(TlsMinVersion::V1_2),
+        Some("1.3") => Ok(TlsMinVersion::V1_3),
+        Some(other) => anyhow::bail!("TLS_MIN_VERSION must be 1.2 or 1.3, got {other:?}"),
+    }
+}
+
+/// Upper bound on `DATABASE_MAX_CONNECTIONS=auto`: beyond this, more
+/// connections mostly buy contention on the database side.
+const AUTO_MAX_CONNECTIONS_CAP: u32 = 32;
+
+/// Resolve `DATABASE_MAX_CONNECTIONS`: `auto` computes `cpus * 2 + 1`
+/// capped at [`AUTO_MAX_CONNECTIONS_CAP`], a number is taken as-is, and
+/// anything else (including `0`) falls back to the default of 10.
+fn max_connections(raw: Option<&str>, cpus: usize) -> u32 {
+    match raw {
+        Some("auto") => (u32::try_from(cpus).unwrap_or(u32::MAX))
+            .saturating_mul(2)
+            .saturating_add(1)
+            .clamp(1, AUTO_MAX_CONNECTIONS_CAP),
+        Some(value) => value.parse().ok().filter(|&n| n > 0).unwrap_or(10),
+        None => 10,
+    }
+}
+
+/// Read an optional numeric environment variable, ignoring unparsable
+/// values.
+pub(crate) fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+/// Read a comma-separated environment list, trimming whitespace and dropping
+/// empty entries.
+pub(crate) fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|entry| entry.trim().to_ascii_lowercase())
+                .filter(|entry| !entry.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Read a boolean environment flag, accepting `true`/`false` and `1`/`0`.
+pub(crate) fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => matches!(value.to_ascii_lowercase().as_str(), "true" | "1"),
+        Err(_) => default,
+    }
+}
+
+impl Config {
+    /// Load configuration from the process environment.
+    ///
+    /// `DATABASE_URL` is required; everything else falls back to defaults.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let database_url =
+            env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("DATABASE_URL must be set"))?;
+        let server_port = env::var("SERVER_PORT")
+            .unwrap_or_else(|_| "3000".to_string())
+            .parse()
+            .unwrap_or(3000);
+        // Unlike most knobs this does not fall back on a bad value: a
+        // typo silently landing on `memory` would disable cross-replica
+        // coordination without a trace.
+        let state_backend = match env::var("STATE_BACKEND").ok().as_deref() {
+            None | Some("") | Some("memory") => StateBackend::Memory,
+            Some("postgres") => StateBackend::Postgres,
+            Some(other) => anyhow::bail!("STATE_BACKEND must be memory or postgres, got {other:?}"),
+        };
+
+        Ok(Config {
+            database_url,
+            server_port,
+            environment: environment(env::var("ENVIRONMENT").ok().as_deref())?,
+            so_reuseport: env_flag("SO_REUSEPORT", false),
+            strip_response_headers: env_list("STRIP_RESPONSE_HEADERS"),
+            expose_route_header: env_flag("EXPOSE_ROUTE_HEADER", false),
+            cors_allowed_origins: env_list(crate::middleware::cors::ORIGINS_ENV),
+            response_size_soft_limit: env_parse("RESPONSE_SIZE_SOFT_LIMIT_BYTES"),
+            response_size_hard_limit: env_parse("RESPONSE_SIZE_HARD_LIMIT_BYTES"),
+            database_connect_timeout_ms: env_parse("DATABASE_CONNECT_TIMEOUT_MS").unwrap_or(5_000),
+            drain_delay_secs: env_parse("PRESHUTDOWN_DELAY_SECS")
+                .or_else(|| env_parse("DRAIN_DELAY_SECS"))
+                .unwrap_or(5),
+            base_path: env::var("BASE_PATH").unwrap_or_default(),
+            health_path: env::var("HEALTH_PATH")
+                .ok()
+                .filter(|path| !path.trim().is_empty()),
+            avatar_max_bytes: env_parse("AVATAR_MAX_BYTES").unwrap_or(1_048_576),
+            max_body_bytes: env_parse("MAX_BODY_BYTES").unwrap_or(2_097_152),
+            bulk_max_body_bytes: env_parse("BULK_MAX_BODY_BYTES").unwrap_or(10_485_760),
+            lookup_max_emails: env_parse("LOOKUP_MAX_EMAILS").unwrap_or(100),
+            enumeration_protection: env_flag("ENUMERATION_PROTECTION", false),
+            enumeration_min_response_ms: env_parse("ENUMERATION_MIN_RESPONSE_MS").unwrap_or(100),
+            email_change_token_ttl_secs: env_parse("EMAIL_CHANGE_TOKEN_TTL_SECS").unwrap_or(86_400),
+            max_offset: env_parse("MAX_OFFSET").unwrap_or(100_000),
+            db_max_lifetime_secs: env_parse("DATABASE_MAX_LIFETIME_SECS").unwrap_or(1800),
+            db_max_lifetime_jitter: env_parse("DATABASE_MAX_LIFETIME_JITTER").unwrap_or(0.1),
+            db_idle_in_tx_timeout_ms: env_parse("DATABASE_IDLE_IN_TX_TIMEOUT_MS").unwrap_or(30_000),
+            db_acquire_warn_threshold_ms: env_parse("DB_ACQUIRE_WARN_THRESHOLD_MS").unwrap_or(1000),
+            strict_json_fields: env_flag("STRICT_JSON_FIELDS", false),
+            auth_jwt_secret: env::var("AUTH_JWT_SECRET").ok(),
+            run_migrations_on_startup: env_flag("RUN_MIGRATIONS_ON_STARTUP", true),
+            read_only_mode: env_flag("READ_ONLY_MODE", false),
+            schema_check_fatal: env_flag("SCHEMA_CHECK_FATAL", true),
+            tenants: env_list("TENANTS"),
+            user_cache: env_flag("USER_CACHE", false),
+            rate_limit_public_read: env_parse("RATE_LIMIT_PUBLIC_READ").unwrap_or(1200),
+            rate_limit_public_write: env_parse("RATE_LIMIT_PUBLIC_WRITE").unwrap_or(600),
+            rate_limit_expensive: env_parse("RATE_LIMIT_EXPENSIVE").unwrap_or(120),
+            docs_enabled: env_flag("DOCS_ENABLED", true),
+            docs_require_auth: env_flag("DOCS_REQUIRE_AUTH", false),
+            metrics_require_auth: env_flag("METRICS_REQUIRE_AUTH", false),
+            skip_warmup: env_flag("SKIP_WARMUP", false),
+            startup_warn_secs: env_parse("STARTUP_WARN_SECS").unwrap_or(10),
+            streaming_drain_budget_secs: env_parse("STREAMING_DRAIN_BUDGET_SECS").unwrap_or(3),
+            clock_skew_warn_secs: env_parse("CLOCK_SKEW_WARN_SECS").unwrap_or(5),
+            clock_skew_max_auth_secs: env_parse("CLOCK_SKEW_MAX_AUTH_SECS").unwrap_or(0),
+            log_sql: env_flag("LOG_SQL", false),
+            usage_max_callers: env_parse("USAGE_MAX_CALLERS").unwrap_or(100),
+            background_pool_size: env_parse("BACKGROUND_POOL_SIZE").unwrap_or(0),
+            server_timing: env_flag(
+                "SERVER_TIMING",
+                env::var("ENVIRONMENT")
+                    .map(|e| e != "production")
+                    .unwrap_or(true),
+            ),
+            normalize_emails: env_flag("NORMALIZE_EMAILS", true),
+            webhook_url: env::var("WEBHOOK_URL").ok().filter(|url| !url.is_empty()),
+            webhook_max_age_secs: env_parse("WEBHOOK_MAX_AGE_SECS").unwrap_or(300),
+            mtls_ca_cert: env::var("MTLS_CA_CERT")
+                .ok()
+                .filter(|path| !path.is_empty()),
+            mtls_server_cert: env::var("MTLS_SERVER_CERT")
+                .ok()
+                .filter(|path| !path.is_empty()),
+            mtls_server_key: env::var("MTLS_SERVER_KEY")
+                .ok()
+                .filter(|path| !path.is_empty()),
+            tls_min_version: tls_min_version(env::var("TLS_MIN_VERSION").ok().as_deref())?,
+            enable_http2: env_flag("ENABLE_HTTP2", false),
+            slo_availability: env_parse("SLO_AVAILABILITY").unwrap_or(99.9),
+            database_max_connections: max_connections(
+                env::var("DATABASE_MAX_CONNECTIONS").ok().as_deref(),
+                std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
+            ),
+            state_backend,
+            max_rows_per_query: env_parse("MAX_ROWS_PER_QUERY")
+                .unwrap_or(crate::repository::DEFAULT_MAX_ROWS_PER_QUERY),
+            access_log_sample_rate: env_parse("ACCESS_LOG_SAMPLE_RATE")
+                .map_or(1.0, |rate: f64| rate.clamp(0.0, 1.0)),
+            access_log_quiet_paths: if env::var("ACCESS_LOG_QUIET_PATHS").is_ok() {
+                env_list("ACCESS_LOG_QUIET_PATHS")
+            } else {
+                vec!["/health".to_string()]
+            },
+            route_concurrency_limit: env_parse("ROUTE_CONCURRENCY_LIMIT").unwrap_or(16),
+        })
+    }
+
+    /// Configuration used by the in-process test suites, with every knob at
+    /// its default.
+    #[cfg(test)]
+    pub(crate) fn for_tests() -> Self {
+        Config {
+            database_url: "postgres://localhost/test".to_string(),
+            server_port: 3000,
+            environment: Environment::Development,
+            so_reuseport: false,
+            strip_response_headers: Vec::new(),
+            expose_route_header: false,
+            cors_allowed_origins: Vec::new(),
+            response_size_soft_limit: None,
+            response_size_hard_limit: None,
+            database_connect_timeout_ms: 5_000,
+            drain_delay_secs: 5,
+            base_path: String::new(),
+            health_path: None,
+            avatar_max_bytes: 1_048_576,
+            max_body_bytes: 2_097_152,
+            bulk_max_body_bytes: 10_485_760,
+            lookup_max_emails: 100,
+            enumeration_protection: false,
+            enumeration_min_response_ms: 100,
+            email_change_token_ttl_secs: 86_400,
+            max_offset: 100_000,
+            db_max_lifetime_secs: 1800,
+            db_max_lifetime_jitter: 0.1,
+            db_idle_in_tx_timeout_ms: 30_000,
+            db_acquire_warn_threshold_ms: 1000,
+            strict_json_fields: false,
+            auth_jwt_secret: None,
+            run_migrations_on_startup: true,
+            read_only_mode: false,
+            schema_check_fatal: true,
+            tenants: Vec::new(),
+            user_cache: false,
+            rate_limit_public_read: 1200,
+            rate_limit_public_write: 600,
+            rate_limit_expensive: 120,
+            docs_enabled: true,
+            docs_require_auth: false,
+            metrics_require_auth: false,
+            skip_warmup: false,
+            startup_warn_secs: 10,
+            streaming_drain_budget_secs: 3,
+            clock_skew_warn_secs: 5,
+            clock_skew_max_auth_secs: 0,
+            log_sql: false,
+            usage_max_callers: 100,
+            background_pool_size: 0,
+            server_timing: true,
+            normalize_emails: true,
+            webhook_url: None,
+            webhook_max_age_secs: 300,
+            mtls_ca_cert: None,
+            mtls_server_cert: None,
+            mtls_server_key: None,
+            tls_min_version: TlsMinVersion::V1_2,
+            enable_http2: false,
+            slo_availability: 99.9,
+            database_max_connections: 10,
+            state_backend: StateBackend::Memory,
+            max_rows_per_query: crate::repository::DEFAULT_MAX_ROWS_PER_QUERY,
+            access_log_sample_rate: 1.0,
+            access_log_quiet_paths: vec!["/health".to_string()],
+            route_concurrency_limit: 16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_max_connections_scales_with_cpus_and_stays_bounded() {
+        assert_eq!(max_connections(Some("auto"), 1), 3);
+        assert_eq!(max_connections(Some("auto"), 4), 9);
+        // Capped: a 96-core box should not open 193 connections.
+        assert_eq!(max_connections(Some("auto"), 96), AUTO_MAX_CONNECTIONS_CAP);
+        // Positive even in the degenerate zero-cpu case.
+        assert_eq!(max_connections(Some("auto"), 0), 1);
+    }
+
+    #[test]
+    fn tls_min_version_accepts_the_baseline_and_rejects_legacy_protocols() {
+        assert_eq!(tls_min_version(None).unwrap(), TlsMinVersion::V1_2);
+        assert_eq!(tls_min_version(Some("1.2")).unwrap(), TlsMinVersion::V1_2);
+        assert_eq!(tls_min_version(Some("1.3")).unwrap(), TlsMinVersion::V1_3);
+
+        for legacy in ["1.0", "1.1", "ssl3", "latest"] {
+            assert!(
+                tls_min_version(Some(legacy)).is_err(),
+                "{legacy} must be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn environment_defaults_to_development_and_rejects_typos() {
+        assert_eq!(environment(None).unwrap(), Environment::Development);
+        assert_eq!(environment(Some("")).unwrap(), Environment::Development);
+        assert_eq!(environment(Some("staging")).unwrap(), Environment::Staging);
+        assert_eq!(
+            environment(Some("production")).unwrap(),
+            Environment::Production
+        );
+        assert!(environment(Some("prod")).is_err());
+        assert!(environment(Some("Production")).is_err());
+    }
+
+    /// Write a throwaway `.env` under the temp dir; unique per test so
+    /// parallel tests cannot collide.
+    fn temp_dotenv(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("rba-dotenv-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn dotenv_loads_in_development_but_process_env_wins() {
+        let path = temp_dotenv(
+            "dev",
+            "DOTENV_TEST_SHADOWED=from_file\nDOTENV_TEST_FRESH=from_file\n",
+        );
+        env::set_var("DOTENV_TEST_SHADOWED", "from_process");
+        env::remove_var("DOTENV_TEST_FRESH");
+
+        let status = load_dotenv_from(&path, true);
+        assert_eq!(
+            status,
+            DotenvStatus::Loaded {
+                path: path.clone(),
+                contributed: 1,
+            }
+        );
+        assert_eq!(
+            env::var("DOTENV_TEST_SHADOWED").as_deref(),
+            Ok("from_process"),
+            "the process environment must beat the file"
+        );
+        assert_eq!(env::var("DOTENV_TEST_FRESH").as_deref(), Ok("from_file"));
+
+        env::remove_var("DOTENV_TEST_SHADOWED");
+        env::remove_var("DOTENV_TEST_FRESH");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn dotenv_is_ignored_outside_development() {
+        let path = temp_dotenv("prod", "DOTENV_TEST_IGNORED=from_file\n");
+        env::remove_var("DOTENV_TEST_IGNORED");
+
+        let status = load_dotenv_from(&path, false);
+        assert_eq!(status, DotenvStatus::Ignored { path: path.clone() });
+        assert!(
+            env::var("DOTENV_TEST_IGNORED").is_err(),
+            "a non-development load must not touch the environment"
+        );
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn a_missing_dotenv_is_not_an_error() {
+        let path = std::env::temp_dir().join("rba-dotenv-does-not-exist");
+        assert_eq!(load_dotenv_from(&path, true), DotenvStatus::NotFound);
+        assert_eq!(load_dotenv_from(&path, false), DotenvStatus::NotFound);
+    }
+
+    #[test]
+    fn explicit_max_connections_overrides_and_bad_values_fall_back() {
+        assert_eq!(max_connections(Some("25"), 4), 25);
+        assert_eq!(max_connections(None, 4), 10);
+        assert_eq!(max_connections(Some("0"), 4), 10);
+        assert_eq!(max_connections(Some("lots"), 4), 10);
+    }
+}
@@ -0,0 +1,582 @@
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Application-level error type returned by handlers and repositories.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    /// A validation failure on a specific field, carried as a
+    /// language-neutral message key from [`crate::i18n::keys`]. The human
+    /// text is resolved against the caller's negotiated locale at render
+    /// time; the key itself is returned unchanged in the body so clients
+    /// can switch on it regardless of locale.
+    #[error("Validation error: {code}")]
+    ValidationField {
+        field: &'static str,
+        code: &'static str,
+    },
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Resource not found")]
+    NotFound,
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Internal server error")]
+    Internal,
+
+    /// The database connection refuses writes (read replica or read-only
+    /// role). Write endpoints answer 503 with this code so clients can
+    /// retry against a healthy deployment instead of parsing a masked 500.
+    #[error("database is read-only")]
+    ReadOnly,
+
+    /// A query returned more rows than `MAX_ROWS_PER_QUERY` allows. This
+    /// is a missing-LIMIT bug, not a user error: the body is a masked 500
+    /// while the alert is logged where the guard tripped.
+    #[error("query row limit exceeded")]
+    RowLimitExceeded,
+
+    /// Escape hatch for one-off endpoints that need an arbitrary status
+    /// without a dedicated variant. Prefer the named variants where one
+    /// fits.
+    #[error("{message}")]
+    Http { status: StatusCode, message: String },
+}
+
+impl AppError {
+    /// Build an [`AppError::Http`] with the given status and message.
+    pub fn http(status: StatusCode, message: impl Into<String>) -> Self {
+        AppError::Http {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// The variant name, used as the `variant` label on
+    /// [`crate::metrics::ERRORS_BY_CLASS`] and in the classified log line.
+    fn variant_name(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "Validation",
+            AppError::ValidationField { .. } => "ValidationField",
+            AppError::Database(_) => "Database",
+            AppError::NotFound => "NotFound",
+            AppError::Conflict(_) => "Conflict",
+            AppError::Internal => "Internal",
+            AppError::ReadOnly => "ReadOnly",
+            AppError::RowLimitExceeded => "RowLimitExceeded",
+            AppError::Http { .. } => "Http",
+        }
+    }
+}
+
+/// Malformed JSON is the client's mistake, not ours: handlers that parse
+/// payloads by hand (dynamic shapes, stored blobs from the request) can
+/// `?` the serde error straight into a 400 carrying the parse message,
+/// instead of it surfacing as a masked 500.
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Validation(format!("invalid JSON: {error}"))
+    }
+}
+
+/// Whether a request rejected with `status` is safe to retry, or `None`
+/// for statuses that carry no retry advice.
+///
+/// Only transient rejections — 429, 503, and 504 — are advised on.
+/// Idempotent methods (GET, HEAD, OPTIONS, PUT, DELETE) are always safe
+/// to resubmit; POST and PATCH only when the caller supplied an
+/// `Idempotency-Key`, since without one a retry can double-apply. The
+/// decision lives here so the load-shedding and rate-limit middleware
+/// and any handler returning these statuses agree on it.
+#[must_use]
+pub fn retry_advice(
+    method: &Method,
+    has_idempotency_key: bool,
+    status: StatusCode,
+) -> Option<bool> {
+    if !matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    ) {
+        return None;
+    }
+    Some(match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE => true,
+        _ => has_idempotency_key,
+    })
+}
+
+/// JSON body returned for every error response.
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub message: String,
+    /// Language-neutral message key for field validation errors, identical
+    /// across locales while `message` is localized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    /// The field a keyed validation error refers to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+}
+
+impl ErrorResponse {
+    fn plain(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+            code: None,
+            field: None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        // A write Postgres itself refused because the transaction is
+        // read-only (SQLSTATE 25006): the primary demoted after startup,
+        // so the startup probe never armed the read-only middleware.
+        // Render it as the same 503 `READ_ONLY` so clients see one
+        // degraded-read signal regardless of which layer caught it.
+        let error = match self {
+            AppError::Database(e)
+                if e.as_database_error()
+                    .and_then(sqlx::error::DatabaseError::code)
+                    .as_deref()
+                    == Some("25006") =>
+            {
+                AppError::ReadOnly
+            }
+            other => other,
+        };
+        let variant = error.variant_name();
+        let (status, error_response) = match error {
+            AppError::Validation(msg) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse::plain("VALIDATION_ERROR", msg),
+            ),
+            AppError::ValidationField { field, code } => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    error: "VALIDATION_ERROR".to_string(),
+                    message: crate::i18n::resolve(crate::i18n::current(), code).to_string(),
+                    code: Some(code),
+                    field: Some(field),
+                },
+            ),
+            AppError::NotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorResponse::plain("NOT_FOUND", "Resource not found"),
+            ),
+            AppError::Conflict(msg) => {
+                (StatusCode::CONFLICT, ErrorResponse::plain("CONFLICT", msg))
+            }
+            AppError::Database(e) => {
+                tracing::error!("Database error: {e:?}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorResponse::plain("DATABASE_ERROR", "A database error occurred"),
+                )
+            }
+            AppError::Http { status, message } => {
+                // Masking is per-variant: client errors (4xx) carry their
+                // message so callers can act on it, while server errors
+                // (5xx) are logged and masked — internals never leak
+                // through the escape hatch either.
+                let message = if status.is_server_error() {
+                    tracing::error!(status = %status, %message, "masked server error");
+                    status
+                        .canonical_reason()
+                        .unwrap_or("An internal error occurred")
+                        .to_string()
+                } else {
+                    message
+                };
+                (
+                    status,
+                    ErrorResponse::plain(
+                        status
+                            .canonical_reason()
+                            .unwrap_or("ERROR")
+                            .to_ascii_uppercase()
+                            .replace(' ', "_"),
+                        message,
+                    ),
+                )
+            }
+            AppError::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse::plain("INTERNAL_ERROR", "An internal error occurred"),
+            ),
+            AppError::RowLimitExceeded => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse::plain("INTERNAL_ERROR", "An internal error occurred"),
+            ),
+            // Not masked despite being 5xx: the message names no
+            // internals, and clients need to distinguish "retry elsewhere"
+            // from a generic failure.
+            AppError::ReadOnly => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse::plain("READ_ONLY", "service is connected to a read-only database"),
+            ),
+        };
+
+        // One classified line and counter bump per error response: `class`
+        // splits caller mistakes from our failures, `variant` names the
+        // source. 4xx at warn (expected in normal traffic), 5xx at error
+        // (every one is an incident signal).
+        let class = if status.is_server_error() {
+            "server_error"
+        } else {
+            "client_error"
+        };
+        crate::metrics::ERRORS_BY_CLASS
+            .with_label_values(&[class, variant])
+            .inc();
+        if status.is_server_error() {
+            tracing::error!(class, variant, status = status.as_u16(), "error response");
+        } else {
+            tracing::warn!(class, variant, status = status.as_u16(), "error response");
+        }
+
+        (status, Json(error_response)).into_response()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_variant_uses_the_provided_status() {
+        let response =
+            AppError::http(StatusCode::PAYMENT_REQUIRED, "payment required").into_response();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+
+        let response = AppError::http(StatusCode::IM_A_TEAPOT, "short and stout").into_response();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+    }
+
+    async fn body_message(response: Response) -> String {
+        use http_body_util::BodyExt;
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        body["message"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn client_errors_expose_details_and_server_errors_are_masked() {
+        let response = AppError::http(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "cannot merge a user into itself",
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body_message(response).await,
+            "cannot merge a user into itself"
+        );
+
+        let response = AppError::http(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "pool handle poisoned at foo.rs:42",
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let message = body_message(response).await;
+        assert!(
+            !message.contains("foo.rs"),
+            "internal details leaked: {message}"
+        );
+    }
+
+    async fn body_value(response: Response) -> serde_json::Value {
+        use http_body_util::BodyExt;
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    /// A minimal `DatabaseError` carrying SQLSTATE 25006, standing in for
+    /// Postgres refusing a write mid-failover.
+    #[derive(Debug)]
+    struct ReadOnlyTransaction;
+
+    impl std::fmt::Display for ReadOnlyTransaction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("cannot execute INSERT in a read-only transaction")
+        }
+    }
+
+    impl std::error::Error for ReadOnlyTransaction {}
+
+    impl sqlx::error::DatabaseError for ReadOnlyTransaction {
+        fn message(&self) -> &str {
+            "cannot execute INSERT in a read-only transaction"
+        }
+
+        fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+            Some("25006".into())
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::Other
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+    }
+
+    /// A primary demoted at runtime never trips the startup probe, so the
+    /// refusal arrives as a database error on the write itself. It must
+    /// surface as the same 503 `READ_ONLY` the middleware sends, not a
+    /// masked 500, while other database errors keep their masking.
+    #[tokio::test]
+    async fn a_read_only_transaction_error_maps_to_the_read_only_503() {
+        let error = AppError::Database(sqlx::Error::Database(Box::new(ReadOnlyTransaction)));
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_value(response).await;
+        assert_eq!(body["error"], "READ_ONLY", "body: {body}");
+    }
+
+    /// Snapshot of every variant's body, so the error schema — which
+    /// variants expose their message (4xx) and which are masked to a
+    /// generic text (5xx) — cannot drift without this test changing.
+    #[tokio::test]
+    async fn error_body_schema_snapshot() {
+        let cases: Vec<(AppError, StatusCode, serde_json::Value)> = vec![
+            (
+                AppError::Validation("limit too large".to_string()),
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({"error": "VALIDATION_ERROR", "message": "limit too large"}),
+            ),
+            (
+                AppError::ValidationField {
+                    field: "email",
+                    code: crate::i18n::keys::EMAIL_INVALID,
+                },
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({
+                    "error": "VALIDATION_ERROR",
+                    "message": "email must be a valid email address",
+                    "code": "validation.email_invalid",
+                    "field": "email",
+                }),
+            ),
+            (
+                AppError::NotFound,
+                StatusCode::NOT_FOUND,
+                serde_json::json!({"error": "NOT_FOUND", "message": "Resource not found"}),
+            ),
+            (
+                AppError::Conflict("email already in use".to_string()),
+                StatusCode::CONFLICT,
+                serde_json::json!({"error": "CONFLICT", "message": "email already in use"}),
+            ),
+            (
+                AppError::http(StatusCode::UNPROCESSABLE_ENTITY, "cannot merge"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                serde_json::json!({"error": "UNPROCESSABLE_ENTITY", "message": "cannot merge"}),
+            ),
+            (
+                AppError::Database(sqlx::Error::PoolTimedOut),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({
+                    "error": "DATABASE_ERROR",
+                    "message": "A database error occurred",
+                }),
+            ),
+            (
+                AppError::Internal,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({
+                    "error": "INTERNAL_ERROR",
+                    "message": "An internal error occurred",
+                }),
+            ),
+            (
+                AppError::http(StatusCode::BAD_GATEWAY, "secret upstream detail"),
+                StatusCode::BAD_GATEWAY,
+                serde_json::json!({"error": "BAD_GATEWAY", "message": "Bad Gateway"}),
+            ),
+            (
+                AppError::RowLimitExceeded,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({
+                    "error": "INTERNAL_ERROR",
+                    "message": "An internal error occurred",
+                }),
+            ),
+        ];
+
+        for (error, status, expected) in cases {
+            let response = error.into_response();
+            assert_eq!(response.status(), status);
+            assert_eq!(body_value(response).await, expected, "body for {status}");
+        }
+    }
+
+    #[test]
+    fn retry_advice_decision_matrix() {
+        use super::retry_advice;
+
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::GATEWAY_TIMEOUT,
+        ] {
+            // Idempotent methods are safe to retry with or without a key.
+            for method in [
+                Method::GET,
+                Method::HEAD,
+                Method::OPTIONS,
+                Method::PUT,
+                Method::DELETE,
+            ] {
+                assert_eq!(retry_advice(&method, false, status), Some(true));
+                assert_eq!(retry_advice(&method, true, status), Some(true));
+            }
+            // POST and PATCH need an Idempotency-Key to be resubmittable.
+            for method in [Method::POST, Method::PATCH] {
+                assert_eq!(retry_advice(&method, false, status), Some(false));
+                assert_eq!(retry_advice(&method, true, status), Some(true));
+            }
+        }
+
+        // Other statuses carry no advice, whatever the method.
+        for status in [
+            StatusCode::OK,
+            StatusCode::BAD_REQUEST,
+            StatusCode::CONFLICT,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ] {
+            assert_eq!(retry_advice(&Method::GET, true, status), None);
+            assert_eq!(retry_advice(&Method::POST, true, status), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_serde_error_converts_to_a_400_with_the_parse_message() {
+        let parse_error =
+            serde_json::from_str::<serde_json::Value>("{not json").expect_err("input is malformed");
+        let error: AppError = parse_error.into();
+        assert!(matches!(error, AppError::Validation(_)));
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_value(response).await;
+        assert_eq!(body["error"], "VALIDATION_ERROR");
+        assert!(
+            body["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("invalid JSON:"),
+            "message should carry the parse detail: {body}"
+        );
+    }
+
+    #[test]
+    fn named_variants_keep_their_statuses() {
+        assert_eq!(
+            AppError::NotFound.into_response().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            AppError::Validation("bad".to_string())
+                .into_response()
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    /// `MakeWriter` that appends to a shared buffer the test can inspect.
+    #[derive(Clone)]
+    struct Capture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    /// Render `error` under a capturing subscriber and return what it
+    /// logged.
+    fn captured_log(error: AppError) -> String {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = Capture(std::sync::Arc::new(std::sync::Mutex::new(Vec::new())));
+        let layer = crate::logging::RedactionLayer::new(crate::logging::redacted_fields())
+            .with_writer(capture.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let _ = error.into_response();
+
+        let output = capture.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Alerting splits on the `class` field: a 404 is the caller's
+    /// mistake at warn, a 500 is ours at error, and each bumps the
+    /// classified counter under its variant name.
+    #[test]
+    fn a_404_classifies_as_client_error_and_a_500_as_server_error() {
+        let not_found =
+            crate::metrics::ERRORS_BY_CLASS.with_label_values(&["client_error", "NotFound"]);
+        let internal =
+            crate::metrics::ERRORS_BY_CLASS.with_label_values(&["server_error", "Internal"]);
+        let (not_found_before, internal_before) = (not_found.get(), internal.get());
+
+        let output = captured_log(AppError::NotFound);
+        assert!(
+            output.contains("WARN")
+                && output.contains("class=client_error variant=NotFound status=404"),
+            "missing classified warn line: {output}"
+        );
+
+        let output = captured_log(AppError::Internal);
+        assert!(
+            output.contains("ERROR")
+                && output.contains("class=server_error variant=Internal status=500"),
+            "missing classified error line: {output}"
+        );
+
+        assert_eq!(not_found.get(), not_found_before + 1);
+        assert_eq!(internal.get(), internal_before + 1);
+    }
+}
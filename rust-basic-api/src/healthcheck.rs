@@ -0,0 +1,165 @@
+//! `--healthcheck` CLI mode backing the container `HEALTHCHECK`.
+//!
+//! Shipping curl in the image just to probe `/health/ready` bloats it and
+//! breaks on distroless bases, so the binary probes itself: a plain
+//! HTTP/1.1 GET against the local server, in the same deliberately
+//! TLS-free style as the webhook transport
+//! ([`crate::webhooks::HttpWebhookSender`]). The mode reads only
+//! `SERVER_PORT`, `BASE_PATH`, and `HEALTH_PATH` from the environment —
+//! no tracing subscriber, no config validation, and no database pool —
+//! so the probe is cheap and cannot fail for reasons unrelated to
+//! serving.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// How long the probe waits before declaring the server unhealthy. Kept
+/// short of Docker's own healthcheck timeout so the verdict is ours.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Run the probe against the locally served health endpoint, printing the
+/// outcome. Returns the process exit code: 0 when the endpoint answered
+/// 200, 1 otherwise. `--liveness` probes `/health` (process up) instead
+/// of `/health/ready` (able to take traffic).
+pub async fn run(liveness: bool) -> i32 {
+    let port = std::env::var("SERVER_PORT")
+        .ok()
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(3000);
+    let base_path = std::env::var("BASE_PATH").unwrap_or_default();
+    let health_path = std::env::var("HEALTH_PATH").unwrap_or_default();
+    check(port, &target_path(liveness, &base_path, &health_path)).await
+}
+
+/// Probe `path` on the local server, print the verdict, and map it to an
+/// exit code.
+pub async fn check(port: u16, path: &str) -> i32 {
+    match probe(port, path).await {
+        Ok(200) => {
+            println!("{path}: 200");
+            0
+        }
+        Ok(status) => {
+            println!("{path}: {status}");
+            1
+        }
+        Err(error) => {
+            println!("{path}: {error}");
+            1
+        }
+    }
+}
+
+/// The endpoint to probe, honoring a configured base path prefix and the
+/// `HEALTH_PATH` relocation of the readiness endpoint (liveness stays at
+/// `/health` regardless).
+fn target_path(liveness: bool, base_path: &str, health_path: &str) -> String {
+    let path = if liveness {
+        "/health".to_string()
+    } else {
+        let prefix =
+            crate::normalized_base_path(health_path).unwrap_or_else(|| "/health".to_string());
+        format!("{prefix}/ready")
+    };
+    match crate::normalized_base_path(base_path) {
+        Some(prefix) => format!("{prefix}{path}"),
+        None => path,
+    }
+}
+
+/// One HTTP/1.1 GET to the loopback server, returning the status code.
+async fn probe(port: u16, path: &str) -> std::result::Result<u16, String> {
+    let attempt = async {
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .map_err(|e| format!("connect 127.0.0.1:{port}: {e}"))?;
+        stream
+            .write_all(
+                format!("GET {path} HTTP/1.1\r\nhost: 127.0.0.1\r\nconnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .await
+            .map_err(|e| format!("write: {e}"))?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .map_err(|e| format!("read response: {e}"))?;
+        let status_line = response
+            .split(|&b| b == b'\r')
+            .next()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .unwrap_or_default();
+        status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| format!("malformed response: {status_line:?}"))
+    };
+    tokio::time::timeout(PROBE_TIMEOUT, attempt)
+        .await
+        .map_err(|_| format!("no response within {}s", PROBE_TIMEOUT.as_secs()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{test_app, test_state};
+
+    /// Serve a test app on an ephemeral loopback port. The probe's target
+    /// needs a real socket, not `oneshot`.
+    async fn serve_test_app() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            crate::server::serve(listener, test_app(test_state()), std::future::pending())
+                .await
+                .unwrap();
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn a_serving_app_probes_healthy_and_a_dead_port_does_not() {
+        let port = serve_test_app().await;
+
+        // Both probe targets answer 200 on a freshly started app; note no
+        // database pool exists here, so a passing probe also demonstrates
+        // the mode needs none.
+        assert_eq!(super::check(port, "/health/ready").await, 0);
+        assert_eq!(super::check(port, "/health").await, 0);
+
+        // A dead port — the crashed-server case HEALTHCHECK exists for.
+        let dead = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_port = dead.local_addr().unwrap().port();
+        drop(dead);
+        assert_eq!(super::check(dead_port, "/health/ready").await, 1);
+    }
+
+    #[tokio::test]
+    async fn non_200_statuses_exit_nonzero() {
+        let port = serve_test_app().await;
+        assert_eq!(super::check(port, "/no-such-endpoint").await, 1);
+    }
+
+    #[test]
+    fn target_path_honors_liveness_base_path_and_health_path() {
+        assert_eq!(super::target_path(false, "", ""), "/health/ready");
+        assert_eq!(super::target_path(true, "", ""), "/health");
+        assert_eq!(super::target_path(false, "api/", ""), "/api/health/ready");
+        assert_eq!(
+            super::target_path(false, "", "/internal/healthz-s3cret"),
+            "/internal/healthz-s3cret/ready"
+        );
+        // Liveness ignores the relocation; only the detailed endpoints move.
+        assert_eq!(
+            super::target_path(true, "", "/internal/healthz-s3cret"),
+            "/health"
+        );
+        assert_eq!(
+            super::target_path(false, "api", "internal/healthz-s3cret"),
+            "/api/internal/healthz-s3cret/ready"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Locale negotiation and message catalogs for error rendering.
+//!
+//! Validation failures on specific fields carry a language-neutral message
+//! key ([`crate::error::AppError::ValidationField`]); the human-readable
+//! text is resolved here against the locale negotiated from the request's
+//! `Accept-Language` header. The catalogs are compiled in as plain keyed
+//! tables — two locales and a handful of keys do not justify a runtime
+//! format like Fluent. Unknown and unsupported locales fall back to
+//! English, and the machine-readable key in the response body is identical
+//! across locales so clients never have to parse localized text.
+
+use axum::extract::Request;
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Locales with a compiled-in catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+/// Message keys live here so validation sites, catalogs, and tests cannot
+/// drift apart.
+pub mod keys {
+    pub const NAME_LENGTH: &str = "validation.name_length";
+    pub const EMAIL_INVALID: &str = "validation.email_invalid";
+}
+
+/// `(key, en, de)` rows; a test asserts every key resolves in every locale.
+const CATALOG: &[(&str, &str, &str)] = &[
+    (
+        keys::NAME_LENGTH,
+        "name must be between 1 and 255 characters",
+        "der Name muss zwischen 1 und 255 Zeichen lang sein",
+    ),
+    (
+        keys::EMAIL_INVALID,
+        "email must be a valid email address",
+        "die E-Mail-Adresse ist ungültig",
+    ),
+];
+
+/// The catalog text for the key, falling back to English for locales
+/// without a translation and to the key itself when the key is unknown —
+/// a missing catalog entry should degrade, not panic or blank out.
+pub fn resolve(locale: Locale, key: &str) -> &str {
+    CATALOG
+        .iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|(_, en, de)| match locale {
+            Locale::En => *en,
+            Locale::De => *de,
+        })
+        .unwrap_or_else(|| {
+            tracing::error!(key, "message key missing from the i18n catalog");
+            key
+        })
+}
+
+/// Pick the best supported locale from an `Accept-Language` header,
+/// honoring quality values (`fr, de;q=0.8` negotiates to German: French
+/// is preferred but not supported). No header, or no supported language,
+/// means English.
+pub fn negotiate(accept_language: Option<&str>) -> Locale {
+    let Some(header) = accept_language else {
+        return Locale::En;
+    };
+
+    let mut best: Option<(f32, Locale)> = None;
+    for entry in header.split(',') {
+        let mut parts = entry.split(';');
+        let tag = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        // Match on the primary subtag: `de-CH` negotiates to German.
+        let locale = match tag.split('-').next().unwrap_or_default() {
+            "en" => Locale::En,
+            "de" => Locale::De,
+            _ => continue,
+        };
+        if quality > 0.0 && best.map_or(true, |(best_q, _)| quality > best_q) {
+            best = Some((quality, locale));
+        }
+    }
+    best.map_or(Locale::En, |(_, locale)| locale)
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+/// The locale negotiated for the request being served, or English outside
+/// a request scope (background tasks, tests calling `into_response`
+/// directly).
+pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// Run the future with the given locale visible through [`current`].
+pub async fn with_locale<F: std::future::Future>(locale: Locale, future: F) -> F::Output {
+    LOCALE.scope(locale, future).await
+}
+
+/// Middleware negotiating the request's locale from `Accept-Language` and
+/// scoping it around the rest of the stack, so error rendering deep in a
+/// handler can resolve message keys without threading the header through.
+pub async fn negotiate_locale(request: Request, next: Next) -> Response {
+    let locale = negotiate(
+        request
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok()),
+    );
+    with_locale(locale, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keys, negotiate, resolve, Locale, CATALOG};
+
+    #[test]
+    fn negotiation_honors_quality_values_and_falls_back_to_english() {
+        assert_eq!(negotiate(Some("de")), Locale::De);
+        assert_eq!(negotiate(Some("de-CH")), Locale::De);
+        // French is preferred but unsupported; German wins on quality.
+        assert_eq!(negotiate(Some("fr, de;q=0.8")), Locale::De);
+        assert_eq!(negotiate(Some("de;q=0.5, en;q=0.9")), Locale::En);
+        // q=0 explicitly refuses a language.
+        assert_eq!(negotiate(Some("de;q=0")), Locale::En);
+        assert_eq!(negotiate(Some("fr")), Locale::En);
+        assert_eq!(negotiate(Some("not a header ;; q=")), Locale::En);
+        assert_eq!(negotiate(None), Locale::En);
+    }
+
+    #[test]
+    fn every_key_resolves_in_every_locale() {
+        for (key, _, _) in CATALOG {
+            for locale in [Locale::En, Locale::De] {
+                assert!(
+                    !resolve(locale, key).is_empty(),
+                    "{key} missing for {locale:?}"
+                );
+            }
+        }
+        assert_ne!(
+            resolve(Locale::En, keys::EMAIL_INVALID),
+            resolve(Locale::De, keys::EMAIL_INVALID)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Shared key-value state for cross-request bookkeeping.
+//!
+//! Process-local state (rate-limit counters, replay markers) resets on
+//! every deploy and cannot coordinate across replicas. [`KvStore`]
+//! abstracts that state behind three operations so deployments can pick
+//! where it lives via `STATE_BACKEND`: `memory` keeps today's in-process
+//! behavior, `postgres` shares it through the `app_kv` table so every
+//! replica sees the same counters and markers.
+//!
+//! Every entry carries a TTL. Expired entries read as absent; writers
+//! reap them opportunistically so neither backend needs a sweeper task.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::error::{AppError, Result};
+use crate::repository::{acquire, PoolHandle};
+
+/// Backend-agnostic key-value store with TTLs.
+#[async_trait]
+pub trait KvStore: Send + Sync {
+    /// The unexpired value stored under `key`.
+    async fn get(&self, key: &str) -> Result<Option<serde_json::Value>>;
+    /// Store `value` under `key` for `ttl`, but only when no unexpired
+    /// value is present. Returns whether this write won; `false` means
+    /// another writer got there first — the replay case.
+    async fn put_nx(&self, key: &str, value: serde_json::Value, ttl: Duration) -> Result<bool>;
+    /// Atomically add `by` to the counter under `key` and return the new
+    /// total. A missing or expired counter starts from zero with `ttl`
+    /// applied; the TTL is not refreshed on later increments, so the
+    /// counter expires on its original schedule (a fixed window).
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> Result<i64>;
+}
+
+struct Entry {
+    value: serde_json::Value,
+    expires_at: Instant,
+}
+
+/// Process-local [`KvStore`], the `STATE_BACKEND=memory` default.
+#[derive(Default)]
+pub struct MemoryKvStore {
+    inner: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryKvStore {
+    /// Drop expired entries; called on every write so the map cannot
+    /// accumulate dead windows.
+    fn sweep(inner: &mut HashMap<String, Entry>) {
+        let now = Instant::now();
+        inner.retain(|_, entry| entry.expires_at > now);
+    }
+}
+
+#[async_trait]
+impl KvStore for MemoryKvStore {
+    async fn get(&self, key: &str) -> Result<Option<serde_json::Value>> {
+        let inner = self.inner.lock().expect("kv lock poisoned");
+        Ok(inner
+            .get(key)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.value.clone()))
+    }
+
+    async fn put_nx(&self, key: &str, value: serde_json::Value, ttl: Duration) -> Result<bool> {
+        let mut inner = self.inner.lock().expect("kv lock poisoned");
+        Self::sweep(&mut inner);
+        if inner.contains_key(key) {
+            return Ok(false);
+        }
+        inner.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        Ok(true)
+    }
+
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> Result<i64> {
+        let mut inner = self.inner.lock().expect("kv lock poisoned");
+        Self::sweep(&mut inner);
+        if let Some(entry) = inner.get_mut(key) {
+            let current = entry
+                .value
+                .as_i64()
+                .ok_or_else(|| AppError::Validation(format!("kv entry {key} is not a counter")))?;
+            entry.value = serde_json::Value::from(current + by);
+            return Ok(current + by);
+        }
+        inner.insert(
+            key.to_string(),
+            Entry {
+                value: serde_json::Value::from(by),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        Ok(by)
+    }
+}
+
+/// Writes between opportunistic reaps of expired `app_kv` rows.
+const REAP_EVERY: u64 = 256;
+
+/// Postgres-backed [`KvStore`] over the `app_kv` table, selected with
+/// `STATE_BACKEND=postgres` so state survives deploys and is shared by
+/// every replica pointing at the same database.
+pub struct PostgresKvStore {
+    pool: PoolHandle,
+    acquire_warn_threshold: Duration,
+    writes: AtomicU64,
+}
+
+impl PostgresKvStore {
+    pub fn new(pool: PoolHandle, acquire_warn_threshold: Duration) -> Self {
+        Self {
+            pool,
+            acquire_warn_threshold,
+            writes: AtomicU64::new(0),
+        }
+    }
+
+    async fn conn(&self, context: &str) -> Result<sqlx::pool::PoolConnection<sqlx::Postgres>> {
+        let pool = self.pool.current();
+        acquire(&pool, self.acquire_warn_threshold, context).await
+    }
+
+    /// Every [`REAP_EVERY`]th write deletes expired rows. Best effort:
+    /// readers already treat expired rows as absent, so a failed reap
+    /// only delays space reclamation.
+    async fn maybe_reap(&self, conn: &mut sqlx::PgConnection) {
+        if self.writes.fetch_add(1, Ordering::Relaxed) % REAP_EVERY != 0 {
+            return;
+        }
+        if let Err(error) = sqlx::query("DELETE FROM app_kv WHERE expires_at <= NOW()")
+            .execute(conn)
+            .await
+        {
+            tracing::debug!(%error, "opportunistic app_kv reap failed");
+        }
+    }
+}
+
+#[async_trait]
+impl KvStore for PostgresKvStore {
+    async fn get(&self, key: &str) -> Result<Option<serde_json::Value>> {
+        let mut conn = self.conn("kv_get").await?;
+        let row: Option<(serde_json::Value,)> =
+            sqlx::query_as("SELECT value FROM app_kv WHERE key = $1 AND expires_at > NOW()")
+                .bind(key)
+                .fetch_optional(&mut *conn)
+                .await?;
+        Ok(row.map(|(value,)| value))
+    }
+
+    async fn put_nx(&self, key: &str, value: serde_json::Value, ttl: Duration) -> Result<bool> {
+        let mut conn = self.conn("kv_put_nx").await?;
+        self.maybe_reap(&mut conn).await;
+        // An expired row under the same key counts as absent and is
+        // overwritten in place.
+        let result = sqlx::query(
+            r"INSERT INTO app_kv (key, value, expires_at)
+              VALUES ($1, $2, NOW() + make_interval(secs => $3))
+              ON CONFLICT (key) DO UPDATE
+                  SET value = EXCLUDED.value, expires_at = EXCLUDED.expires_at
+                  WHERE app_kv.expires_at <= NOW()",
+        )
+        .bind(key)
+        .bind(&value)
+        .bind(ttl.as_secs_f64())
+        .execute(&mut *conn)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> Result<i64> {
+        let mut conn = self.conn("kv_incr").await?;
+        self.maybe_reap(&mut conn).await;
+        // One round trip creates, refreshes-after-expiry, and increments;
+        // the window's expiry is set on creation only.
+        let (total,): (i64,) = sqlx::query_as(
+            r"INSERT INTO app_kv (key, value, expires_at)
+              VALUES ($1, to_jsonb($2::bigint), NOW() + make_interval(secs => $3))
+              ON CONFLICT (key) DO UPDATE SET
+                  value = CASE WHEN app_kv.expires_at <= NOW()
+                               THEN to_jsonb($2::bigint)
+                               ELSE to_jsonb((app_kv.value #>> '{}')::bigint + $2) END,
+                  expires_at = CASE WHEN app_kv.expires_at <= NOW()
+                               THEN NOW() + make_interval(secs => $3)
+                               ELSE app_kv.expires_at END
+              RETURNING (value #>> '{}')::bigint",
+        )
+        .bind(key)
+        .bind(by)
+        .bind(ttl.as_secs_f64())
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{KvStore, MemoryKvStore};
+
+    /// Contract every backend must satisfy; the Postgres backend reuses
+    /// this against a live database from an integration harness (this
+    /// tree carries no database-backed tests).
+    async fn exercise_store(store: &dyn KvStore) {
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(store.get("missing").await.unwrap(), None);
+
+        // First writer wins; the loser sees the original value on read —
+        // the idempotency replay contract.
+        assert!(store
+            .put_nx("marker", serde_json::json!({"id": 1}), ttl)
+            .await
+            .unwrap());
+        assert!(!store
+            .put_nx("marker", serde_json::json!({"id": 2}), ttl)
+            .await
+            .unwrap());
+        assert_eq!(
+            store.get("marker").await.unwrap(),
+            Some(serde_json::json!({"id": 1}))
+        );
+
+        // Counters accumulate and report the running total.
+        assert_eq!(store.incr_with_ttl("counter", 1, ttl).await.unwrap(), 1);
+        assert_eq!(store.incr_with_ttl("counter", 2, ttl).await.unwrap(), 3);
+
+        // A zero TTL expires immediately: reads miss and writes win again.
+        assert!(store
+            .put_nx("gone", serde_json::json!(true), Duration::ZERO)
+            .await
+            .unwrap());
+        assert_eq!(store.get("gone").await.unwrap(), None);
+        assert!(store
+            .put_nx("gone", serde_json::json!(false), ttl)
+            .await
+            .unwrap());
+        assert_eq!(
+            store
+                .incr_with_ttl("expired-counter", 5, Duration::ZERO)
+                .await
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            store
+                .incr_with_ttl("expired-counter", 5, ttl)
+                .await
+                .unwrap(),
+            5,
+            "an expired counter restarts instead of resuming"
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_backend_satisfies_the_store_contract() {
+        exercise_store(&MemoryKvStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn replay_coordinates_across_instances_sharing_a_backend() {
+        // Two "instances" (two handles, as two AppStates sharing one
+        // database would hold) agree on who processed a key first.
+        let shared: Arc<dyn KvStore> = Arc::new(MemoryKvStore::default());
+        let first = shared.clone();
+        let second = shared.clone();
+
+        let ttl = Duration::from_secs(60);
+        assert!(first
+            .put_nx("request-42", serde_json::json!({"status": 201}), ttl)
+            .await
+            .unwrap());
+        assert!(!second
+            .put_nx("request-42", serde_json::json!({"status": 500}), ttl)
+            .await
+            .unwrap());
+        assert_eq!(
+            second.get("request-42").await.unwrap(),
+            Some(serde_json::json!({"status": 201})),
+            "the second instance replays the first one's outcome"
+        );
+    }
+}
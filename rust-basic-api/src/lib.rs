@@ -0,0 +1,737 @@
+pub mod auth;
+pub mod clock;
+pub mod config;
+pub mod error;
+pub mod healthcheck;
+pub mod i18n;
+pub mod kv;
+pub mod logging;
+pub mod metrics;
+pub mod middleware;
+pub mod models;
+pub mod repository;
+pub mod routes;
+#[cfg(feature = "runtime-metrics")]
+pub mod runtime_metrics;
+pub mod seed;
+pub mod server;
+pub mod webhooks;
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::routing::get;
+use axum::Router;
+use tower_http::trace::{DefaultMakeSpan, MakeSpan, TraceLayer};
+
+use config::Config;
+use repository::{SqlxUserRepository, UserRepository};
+
+/// Shared state handed to every handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub repository: Arc<dyn UserRepository>,
+    /// Per-tenant repositories keyed by tenant id; empty in single-tenant
+    /// deployments.
+    pub tenant_repositories: Arc<std::collections::HashMap<String, Arc<dyn UserRepository>>>,
+    pub config: Config,
+    pub readiness: server::ReadinessGate,
+    /// Handle to the live connection pool; `None` when running against a
+    /// non-database repository (tests).
+    pub db: Option<repository::PoolHandle>,
+    /// Dedicated pool for background work (`BACKGROUND_POOL_SIZE`); `None`
+    /// means background tasks share the main pool.
+    pub background_db: Option<repository::PoolHandle>,
+    /// Rolling per-caller usage window behind `GET /admin/usage`.
+    pub usage: Arc<middleware::UsageWindow>,
+    /// Per-class request limits enforced by the rate-limit middleware,
+    /// adjustable at runtime through `PATCH /admin/rate-limits`.
+    pub rate_limits: Arc<middleware::RateLimits>,
+    /// Shared concurrency ceiling for the DB-heavy routes
+    /// (`ROUTE_CONCURRENCY_LIMIT`); overflow is shed with a 503.
+    pub route_concurrency: Arc<middleware::RouteConcurrency>,
+    /// Cross-request state (rate-limit counters, replay markers):
+    /// process-local or shared through Postgres per `STATE_BACKEND`.
+    pub kv: Arc<dyn kv::KvStore>,
+    /// Flag streaming handlers observe to end their responses cleanly
+    /// (within `STREAMING_DRAIN_BUDGET_SECS`) once shutdown begins.
+    pub shutdown: server::ShutdownSignal,
+    /// Allowed CORS origins, swappable at runtime through
+    /// `POST /admin/cors/reload`.
+    pub cors: Arc<middleware::CorsOrigins>,
+    /// Latest host-vs-database clock comparison from the clock monitor;
+    /// consulted by the auth path in strict-skew mode.
+    pub clock: Arc<clock::ClockStatus>,
+    /// Set when startup found the database connection read-only (replica
+    /// or read-only role) with migrations disabled; write endpoints answer
+    /// 503 `READ_ONLY` while it is set.
+    pub read_only: bool,
+    /// Rolling per-route-class outcome windows behind `GET /admin/slo`
+    /// and the readiness `degraded` flag.
+    pub slo: Arc<middleware::SloWindows>,
+    /// Outbox feeding the webhook delivery worker. Producers go through
+    /// [`AppState::publish_event`], which drops events when `WEBHOOK_URL`
+    /// is unset so the queue cannot grow with no worker draining it.
+    pub webhooks: Arc<webhooks::WebhookOutbox>,
+    /// Fault-injection settings behind `POST /admin/chaos`; only present
+    /// in `chaos`-feature builds.
+    #[cfg(feature = "chaos")]
+    pub chaos: Arc<middleware::ChaosState>,
+}
+
+impl AppState {
+    /// The repository serving the given tenant, or the default repository
+    /// when multi-tenancy is disabled. The tenant middleware only admits
+    /// configured tenants, so a missing entry falls back to the default.
+    pub fn repository_for(
+        &self,
+        tenant: Option<&middleware::TenantContext>,
+    ) -> &Arc<dyn UserRepository> {
+        tenant
+            .and_then(|t| self.tenant_repositories.get(&t.id))
+            .unwrap_or(&self.repository)
+    }
+
+    /// The pool background tasks should run queries on: the dedicated
+    /// background pool when configured, otherwise the main pool.
+    pub fn background_pool(&self) -> Option<sqlx::PgPool> {
+        self.background_db
+            .as_ref()
+            .or(self.db.as_ref())
+            .map(repository::PoolHandle::current)
+    }
+
+    /// Queue a webhook event, stamped with the originating request's trace
+    /// id. A no-op when `WEBHOOK_URL` is unset: with no worker draining the
+    /// outbox, enqueueing would only grow memory.
+    pub fn publish_event(
+        &self,
+        kind: &'static str,
+        payload: serde_json::Value,
+        headers: &axum::http::HeaderMap,
+    ) {
+        if self.config.webhook_url.is_some() {
+            self.webhooks
+                .enqueue(kind, payload, webhooks::trace_id_from_headers(headers));
+        }
+    }
+}
+
+/// Build the application router over the given state.
+///
+/// When `BASE_PATH` is configured the whole API is nested under that prefix
+/// (for ingresses that do not strip it), while `/health` stays reachable
+/// unprefixed as well so liveness probes keep working.
+pub fn build_router(state: AppState) -> Router {
+    // The router-wide body cap is applied below the fold; bulk routes
+    // override it here, route-scoped, so only they accept the larger size.
+    let bulk_limit = axum::extract::DefaultBodyLimit::max(state.config.bulk_max_body_bytes);
+    // With `HEALTH_PATH` configured the detailed health endpoints move
+    // under that prefix and their default paths are simply never mounted;
+    // the plain `/health` liveness stays put.
+    let health_path = state
+        .config
+        .health_path
+        .as_deref()
+        .and_then(normalized_base_path);
+    let mut router = routes::route_table()
+        .into_iter()
+        .filter(|(spec, _)| health_path.is_none() || !spec.path.starts_with("/health/"))
+        .fold(Router::new(), |router, (spec, handler)| {
+            let handler = if spec.bulk_body_limit {
+                handler.layer(bulk_limit)
+            } else {
+                handler
+            };
+            router.route(spec.path, handler)
+        });
+
+    if let Some(health_path) = &health_path {
+        router = router
+            .route(
+                &format!("{health_path}/ready"),
+                get(routes::readiness_check),
+            )
+            .route(
+                &format!("{health_path}/migrations"),
+                get(routes::migrations_health),
+            );
+    }
+
+    if let Some(base_path) = normalized_base_path(&state.config.base_path) {
+        router = Router::new()
+            .nest(&base_path, router)
+            .route("/health", get(routes::health_check));
+    }
+
+    // Innermost of the app middleware so injected failures still flow
+    // through usage accounting, rate limiting, and CORS like real
+    // responses.
+    #[cfg(feature = "chaos")]
+    let router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        middleware::inject_chaos,
+    ));
+
+    router
+        // Innermost of the always-on layers so `total` reflects handler
+        // time, not the middleware stack above it.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::record_server_timing,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::strip_response_headers,
+        ))
+        // Inside usage, SLO, and rate-limit accounting so shed 503s show
+        // up there like any other response.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::limit_route_concurrency,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::track_body_sizes,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::track_caller_usage,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::track_slo_outcomes,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::enforce_rate_limits,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::require_mtls_on_admin,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::negotiate_consistency,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::reject_writes_when_read_only,
+        ))
+        // Outside every layer that sheds (concurrency ceiling, rate
+        // limiter, read-only guard) so their 429/503 bodies all carry
+        // the same retry advice.
+        .layer(axum::middleware::from_fn(middleware::annotate_retry_advice))
+        // Outside the shedding layers too, so their 429/503 responses are
+        // attributed to a route template like any other.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::expose_matched_route,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::resolve_tenant,
+        ))
+        // Outermost of the app middleware so error responses (including
+        // 429s from the rate limiter) carry CORS headers too.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::apply_cors,
+        ))
+        // Version guard outside everything: an HTTP/1.0 scraper gets its
+        // 505 without touching rate limiting or usage accounting.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::reject_unsupported_versions,
+        ))
+        // Access log outside the whole stack so its latency and status
+        // cover every layer, version rejections included.
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::log_requests,
+        ))
+        // Locale scope wraps everything beneath it so any error rendered
+        // inside the stack resolves message keys against the request's
+        // negotiated locale.
+        .layer(axum::middleware::from_fn(i18n::negotiate_locale))
+        // The global body cap. Position is immaterial — the layer only
+        // stamps the limit on the request for the extractors to enforce —
+        // and the route-scoped bulk override above wins by running closer
+        // to the handler.
+        .layer(axum::extract::DefaultBodyLimit::max(
+            state.config.max_body_bytes,
+        ))
+        // Quiet paths skip the per-request span too: opening a span
+        // allocates its field storage, which at probe rates dwarfs the
+        // liveness handler itself.
+        .layer({
+            let quiet = state.config.access_log_quiet_paths.clone();
+            TraceLayer::new_for_http().make_span_with(move |request: &axum::http::Request<_>| {
+                if quiet.iter().any(|path| path == request.uri().path()) {
+                    tracing::Span::none()
+                } else {
+                    DefaultMakeSpan::new().make_span(request)
+                }
+            })
+        })
+        .with_state(state)
+}
+
+/// Normalize the configured base path to `/prefix` form, or `None` when no
+/// prefix is configured.
+pub(crate) fn normalized_base_path(base_path: &str) -> Option<String> {
+    let trimmed = base_path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(format!("/{trimmed}"))
+    }
+}
+
+/// Connect to the database, run pending migrations, and serve the API until
+/// the process is stopped.
+pub async fn run_application(config: Config) -> anyhow::Result<()> {
+    let startup_started = std::time::Instant::now();
+    let mut timings = server::StartupTimings::default();
+    // Claim the port before doing any expensive startup work (pool
+    // creation, migrations) so a "port already in use" mistake fails fast
+    // with a clear message.
+    let listener = server::acquire_listener(&config).await.with_context(|| {
+        format!(
+            "could not bind server port {}: is another process already listening on it?",
+            config.server_port
+        )
+    })?;
+
+    let phase = std::time::Instant::now();
+    let pool = repository::create_pool(&config)
+        .await
+        .map_err(|error| repository::describe_connect_error(&config.database_url, error))?;
+    timings.pool_ms = phase.elapsed().as_millis() as u64;
+
+    let read_only = config.read_only_mode || repository::detect_read_only(&pool).await?;
+    repository::check_read_only(read_only, config.run_migrations_on_startup)?;
+    if config.read_only_mode {
+        tracing::info!(
+            "READ_ONLY_MODE enabled; write endpoints answer 405 and writing \
+             background workers stay parked"
+        );
+    } else if read_only {
+        tracing::warn!(
+            "database connection is read-only; starting with write endpoints answering 503"
+        );
+    }
+
+    let phase = std::time::Instant::now();
+    repository::migrations::log_embedded_set();
+    if config.run_migrations_on_startup {
+        let mut conn = pool.acquire().await?;
+        repository::migrations::run_locked(&mut conn).await?;
+    }
+
+    for tenant in &config.tenants {
+        repository::migrate_tenant(&pool, tenant).await?;
+    }
+    timings.migrations_ms = phase.elapsed().as_millis() as u64;
+
+    let readiness = server::ReadinessGate::new();
+    let schema_check = repository::applied_schema_version(&pool)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|applied| {
+            repository::check_schema_version(applied, repository::MIN_SCHEMA_VERSION)
+        });
+    if let Err(error) = schema_check {
+        if config.schema_check_fatal {
+            return Err(error);
+        }
+        tracing::error!(%error, "schema version check failed; starting degraded (not ready)");
+        readiness.set_ready(false);
+    }
+
+    let db = repository::PoolHandle::new(pool);
+    let background_db = if config.background_pool_size > 0 {
+        Some(repository::PoolHandle::new(
+            repository::create_background_pool(&config)?,
+        ))
+    } else {
+        None
+    };
+    let acquire_warn_threshold =
+        std::time::Duration::from_millis(config.db_acquire_warn_threshold_ms);
+    let mut tenant_repositories: std::collections::HashMap<String, Arc<dyn UserRepository>> =
+        std::collections::HashMap::new();
+    for tenant in &config.tenants {
+        tenant_repositories.insert(
+            tenant.clone(),
+            Arc::new(repository::TimedUserRepository::new(Arc::new(
+                SqlxUserRepository::with_schema(
+                    db.clone(),
+                    acquire_warn_threshold,
+                    repository::tenant_schema(tenant),
+                )
+                .with_row_limit(config.max_rows_per_query),
+            ))),
+        );
+    }
+    let base_repository: Arc<dyn UserRepository> = Arc::new(
+        SqlxUserRepository::new(db.clone(), acquire_warn_threshold)
+            .with_row_limit(config.max_rows_per_query),
+    );
+    // Single-flight coalescing beneath the cache: a cold-cache stampede
+    // on one hot id costs one query instead of one per caller.
+    let base_repository: Arc<dyn UserRepository> =
+        Arc::new(repository::CoalescedUserRepository::new(base_repository));
+    // With the cache enabled, a listener on `user_changed` keeps this
+    // replica's entries coherent with mutations committed elsewhere. Only
+    // the default repository is cached: the trigger payload carries no
+    // schema, so tenant repositories read through.
+    let (repository, listener_task, listener_shutdown) = if config.user_cache {
+        let cached = Arc::new(repository::CachedUserRepository::new(base_repository));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(repository::cache::invalidation_listener(
+            config.database_url.clone(),
+            cached.clone(),
+            async {
+                shutdown_rx.await.ok();
+            },
+        ));
+        (
+            cached as Arc<dyn UserRepository>,
+            Some(task),
+            Some(shutdown_tx),
+        )
+    } else {
+        (base_repository, None, None)
+    };
+    // Outermost decorator so cache hits are counted (fast) db time too.
+    let repository: Arc<dyn UserRepository> =
+        Arc::new(repository::TimedUserRepository::new(repository));
+
+    let kv: Arc<dyn kv::KvStore> = match config.state_backend {
+        config::StateBackend::Memory => Arc::new(kv::MemoryKvStore::default()),
+        config::StateBackend::Postgres => {
+            Arc::new(kv::PostgresKvStore::new(db.clone(), acquire_warn_threshold))
+        }
+    };
+    let streaming = server::ShutdownSignal::new();
+    let state = AppState {
+        repository,
+        tenant_repositories: Arc::new(tenant_repositories),
+        config: config.clone(),
+        readiness: readiness.clone(),
+        db: Some(db),
+        background_db,
+        usage: Arc::new(middleware::UsageWindow::new(
+            middleware::usage::WINDOW_MINUTES,
+            config.usage_max_callers,
+        )),
+        rate_limits: Arc::new(middleware::RateLimits::from_config(&config)),
+        route_concurrency: Arc::new(middleware::RouteConcurrency::from_config(&config)),
+        kv,
+        shutdown: streaming.clone(),
+        cors: Arc::new(middleware::CorsOrigins::from_config(&config)),
+        clock: Arc::new(clock::ClockStatus::default()),
+        read_only,
+        slo: Arc::new(middleware::SloWindows::new()),
+        webhooks: Arc::new(webhooks::WebhookOutbox::default()),
+        #[cfg(feature = "chaos")]
+        chaos: Arc::new(middleware::ChaosState::default()),
+    };
+    // In READ_ONLY_MODE the delivery worker stays parked: events still
+    // queue in the outbox, but nothing leaves the process or marks
+    // deliveries during a drill.
+    let webhook_worker = config
+        .webhook_url
+        .clone()
+        .filter(|_| !config.read_only_mode)
+        .map(|url| {
+            let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+            let task = tokio::spawn(webhooks::delivery_worker(
+                state.webhooks.clone(),
+                url,
+                std::time::Duration::from_secs(config.webhook_max_age_secs),
+                async {
+                    shutdown_rx.await.ok();
+                },
+            ));
+            (task, shutdown_tx)
+        });
+    let clock_monitor = state.background_pool().map(|pool| {
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(clock::monitor(
+            pool,
+            state.clock.clone(),
+            std::time::Duration::from_secs(config.clock_skew_warn_secs),
+            (config.clock_skew_max_auth_secs > 0)
+                .then(|| std::time::Duration::from_secs(config.clock_skew_max_auth_secs)),
+            async {
+                shutdown_rx.await.ok();
+            },
+        ));
+        (task, shutdown_tx)
+    });
+    #[cfg(feature = "runtime-metrics")]
+    let runtime_sampler = {
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(runtime_metrics::sampler(
+            tokio::runtime::Handle::current(),
+            async {
+                shutdown_rx.await.ok();
+            },
+        ));
+        (task, shutdown_tx)
+    };
+    let warmup_pool = state.db.as_ref().map(repository::PoolHandle::current);
+    let app = build_router(state);
+
+    // Hold readiness closed until warmup has paid the cold-start costs, so
+    // the load balancer's first routed request is not the one paying them.
+    // A failed schema check keeps the gate closed regardless.
+    let degraded = !readiness.is_ready();
+    readiness.set_ready(false);
+    let phase = std::time::Instant::now();
+    server::warmup(&config, &app, warmup_pool.as_ref()).await;
+    timings.warmup_ms = phase.elapsed().as_millis() as u64;
+    readiness.set_ready(!degraded);
+
+    server::warn_if_slow_startup(
+        startup_started.elapsed(),
+        std::time::Duration::from_secs(config.startup_warn_secs),
+        &timings,
+    );
+    tracing::info!("Listening on {}", listener.local_addr()?);
+    let drain = server::drain_on_shutdown(
+        readiness,
+        streaming,
+        std::time::Duration::from_secs(config.drain_delay_secs),
+        server::shutdown_signal(),
+    );
+    match server::mtls_server_config(&config)? {
+        Some(tls) => server::serve_mtls(listener, app, tls, drain).await?,
+        None => server::serve(listener, app, drain).await?,
+    }
+
+    if let Some(shutdown_tx) = listener_shutdown {
+        let _ = shutdown_tx.send(());
+    }
+    if let Some(task) = listener_task {
+        task.await.ok();
+    }
+    if let Some((task, shutdown_tx)) = webhook_worker {
+        let _ = shutdown_tx.send(());
+        task.await.ok();
+    }
+    if let Some((task, shutdown_tx)) = clock_monitor {
+        let _ = shutdown_tx.send(());
+        task.await.ok();
+    }
+    #[cfg(feature = "runtime-metrics")]
+    {
+        let (task, shutdown_tx) = runtime_sampler;
+        let _ = shutdown_tx.send(());
+        task.await.ok();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn base_path_nests_api_and_keeps_unprefixed_health() {
+        let mut state = test_state();
+        state.config.base_path = "/api".to_string();
+        let app = test_app(state);
+
+        for uri in ["/api/health", "/health", "/api/users"] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "uri: {uri}");
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn health_path_relocates_the_detailed_endpoints() {
+        let mut state = test_state();
+        state.config.health_path = Some("/internal/healthz-s3cret".to_string());
+        let app = test_app(state);
+
+        // The detailed endpoints answer on the secret prefix; their
+        // default paths are never mounted. Plain liveness stays public.
+        for (uri, expected) in [
+            ("/internal/healthz-s3cret/ready", StatusCode::OK),
+            ("/internal/healthz-s3cret/migrations", StatusCode::OK),
+            ("/health", StatusCode::OK),
+            ("/health/ready", StatusCode::NOT_FOUND),
+            ("/health/migrations", StatusCode::NOT_FOUND),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "uri: {uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn migrations_health_is_clean_without_a_database() {
+        // Without a pool attached there is nothing to apply; the endpoint
+        // reports clean rather than failing the probe. The pending-count
+        // arithmetic itself is covered in `repository::tests`.
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health/migrations")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["pending_migrations"], 0);
+        assert_eq!(body["degraded"], false);
+    }
+
+    #[tokio::test]
+    async fn server_time_is_current_and_rfc3339() {
+        let app = test_app(test_state());
+
+        let before = chrono::Utc::now();
+        let response = app
+            .oneshot(Request::builder().uri("/time").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let after = chrono::Utc::now();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let utc = chrono::DateTime::parse_from_rfc3339(body["utc"].as_str().unwrap())
+            .expect("utc field parses as RFC 3339")
+            .with_timezone(&chrono::Utc);
+        assert!(utc >= before - chrono::Duration::seconds(1) && utc <= after);
+        assert_eq!(body["unix"].as_i64().unwrap(), utc.timestamp());
+    }
+
+    #[tokio::test]
+    async fn background_work_uses_the_dedicated_pool_when_configured() {
+        let lazy_pool = |max: u32| {
+            sqlx::postgres::PgPoolOptions::new()
+                .max_connections(max)
+                .connect_lazy("postgres://localhost/test")
+                .unwrap()
+        };
+
+        let mut state = test_state();
+        state.db = Some(crate::repository::PoolHandle::new(lazy_pool(10)));
+
+        // Without a dedicated pool, background work shares the main pool.
+        let shared = state.background_pool().expect("main pool available");
+        assert_eq!(shared.options().get_max_connections(), 10);
+
+        state.background_db = Some(crate::repository::PoolHandle::new(lazy_pool(2)));
+        let dedicated = state.background_pool().expect("background pool available");
+        assert_eq!(dedicated.options().get_max_connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn startup_reports_occupied_port_clearly() {
+        let occupied = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = occupied.local_addr().unwrap().port();
+
+        let mut config = crate::config::Config::for_tests();
+        config.server_port = port;
+
+        let error = crate::run_application(config)
+            .await
+            .expect_err("startup should fail on an occupied port");
+        assert!(
+            error.to_string().contains(&port.to_string()),
+            "error should name the port: {error:#}"
+        );
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use std::sync::Arc;
+
+    use axum::Router;
+
+    use crate::config::Config;
+    use crate::repository::MemoryUserRepository;
+    use crate::AppState;
+
+    /// State backed by the in-memory repository with default configuration.
+    pub fn test_state() -> AppState {
+        AppState {
+            repository: Arc::new(crate::repository::TimedUserRepository::new(Arc::new(
+                MemoryUserRepository::new(),
+            ))),
+            tenant_repositories: Arc::new(std::collections::HashMap::new()),
+            config: Config::for_tests(),
+            readiness: crate::server::ReadinessGate::new(),
+            db: None,
+            background_db: None,
+            usage: Arc::new(crate::middleware::UsageWindow::new(
+                crate::middleware::usage::WINDOW_MINUTES,
+                100,
+            )),
+            rate_limits: Arc::new(crate::middleware::RateLimits::from_config(
+                &Config::for_tests(),
+            )),
+            route_concurrency: Arc::new(crate::middleware::RouteConcurrency::from_config(
+                &Config::for_tests(),
+            )),
+            kv: Arc::new(crate::kv::MemoryKvStore::default()),
+            shutdown: crate::server::ShutdownSignal::new(),
+            cors: Arc::new(crate::middleware::CorsOrigins::from_config(
+                &Config::for_tests(),
+            )),
+            clock: Arc::new(crate::clock::ClockStatus::default()),
+            read_only: false,
+            slo: Arc::new(crate::middleware::SloWindows::new()),
+            webhooks: Arc::new(crate::webhooks::WebhookOutbox::default()),
+            #[cfg(feature = "chaos")]
+            chaos: Arc::new(crate::middleware::ChaosState::default()),
+        }
+    }
+
+    /// The full application router over the given test state.
+    pub fn test_app(state: AppState) -> Router {
+        crate::build_router(state)
+    }
+}
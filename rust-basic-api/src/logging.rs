@@ -0,0 +1,305 @@
+//! Log output with PII redaction.
+//!
+//! Compliance requires emails, tokens, and connection strings scrubbed from
+//! logs by default. [`RedactionLayer`] is a formatting layer that replaces
+//! the values of a configurable set of field names with a hash-prefixed
+//! placeholder; the hash is deterministic so one user's occurrences can
+//! still be correlated across log lines. Set `LOG_REDACTION=false` to get
+//! plaintext output for local debugging, and `LOG_REDACT_FIELDS` to change
+//! which field names are scrubbed.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// Field names redacted when `LOG_REDACT_FIELDS` is not set.
+pub const DEFAULT_REDACTED_FIELDS: &[&str] =
+    &["email", "authorization", "x-api-key", "database_url"];
+
+/// Replace a sensitive value with a deterministic placeholder.
+///
+/// The hash lets log readers correlate occurrences of the same value
+/// without revealing it. Handlers can call this directly for values the
+/// field-name list cannot catch.
+pub fn redact(value: &str) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("[redacted:{:08x}]", hasher.finish() as u32)
+}
+
+/// Whether redaction is enabled (`LOG_REDACTION`, default on).
+pub fn redaction_enabled() -> bool {
+    crate::config::env_flag("LOG_REDACTION", true)
+}
+
+/// The field names to redact, from `LOG_REDACT_FIELDS` or the default set.
+pub fn redacted_fields() -> Vec<String> {
+    let configured = crate::config::env_list("LOG_REDACT_FIELDS");
+    if configured.is_empty() {
+        DEFAULT_REDACTED_FIELDS
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    } else {
+        configured
+    }
+}
+
+/// Whether structured JSON log output was requested (`LOG_FORMAT=json`).
+pub fn json_format_enabled() -> bool {
+    std::env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+}
+
+/// Install a panic hook that emits one parseable JSON line per panic.
+///
+/// Only active with `LOG_FORMAT=json`; the default hook's multi-line
+/// report is fine for plain output but breaks line-oriented log tooling.
+/// The backtrace rides along when `RUST_BACKTRACE` enables capture.
+pub fn install_panic_hook() {
+    if !json_format_enabled() {
+        return;
+    }
+    std::panic::set_hook(Box::new(|info| {
+        let message = panic_message(info.payload());
+        let location = info.location().map(|l| l.to_string());
+        eprintln!(
+            "{}",
+            panic_event(
+                &message,
+                location.as_deref(),
+                captured_backtrace().as_deref()
+            )
+        );
+    }));
+}
+
+/// The panic payload as text; panics overwhelmingly carry `&str` or
+/// `String`, anything else is reported opaquely.
+fn panic_message(payload: &dyn std::any::Any) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+/// The backtrace for the current context when `RUST_BACKTRACE` asks for
+/// one, `None` otherwise (capture is not free).
+fn captured_backtrace() -> Option<String> {
+    matches!(
+        std::env::var("RUST_BACKTRACE").as_deref(),
+        Ok("1") | Ok("full")
+    )
+    .then(|| std::backtrace::Backtrace::force_capture().to_string())
+}
+
+/// Render one panic as a single-line JSON event. Pure so tests can pin
+/// the shape without tripping a real panic hook.
+fn panic_event(message: &str, location: Option<&str>, backtrace: Option<&str>) -> String {
+    serde_json::json!({
+        "level": "error",
+        "panic": message,
+        "location": location,
+        "backtrace": backtrace,
+    })
+    .to_string()
+}
+
+/// Event-formatting layer that writes one line per event, redacting the
+/// values of the configured field names.
+pub struct RedactionLayer<W> {
+    fields: Vec<String>,
+    make_writer: W,
+}
+
+impl RedactionLayer<fn() -> std::io::Stdout> {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            make_writer: std::io::stdout,
+        }
+    }
+}
+
+impl<W> RedactionLayer<W> {
+    /// Redirect output, mainly so tests can capture it.
+    pub fn with_writer<W2>(self, make_writer: W2) -> RedactionLayer<W2> {
+        RedactionLayer {
+            fields: self.fields,
+            make_writer,
+        }
+    }
+}
+
+impl<S, W> Layer<S> for RedactionLayer<W>
+where
+    S: Subscriber,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = RedactingVisitor {
+            sensitive: &self.fields,
+            message: String::new(),
+            fields: String::new(),
+        };
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let _ = writeln!(
+            self.make_writer.make_writer(),
+            "{} {}: {}{}",
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields,
+        );
+    }
+}
+
+struct RedactingVisitor<'a> {
+    sensitive: &'a [String],
+    message: String,
+    fields: String,
+}
+
+impl RedactingVisitor<'_> {
+    fn record(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else if self
+            .sensitive
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(field.name()))
+        {
+            let _ = write!(self.fields, " {}={}", field.name(), redact(value));
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+impl Visit for RedactingVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let rendered = format!("{value:?}");
+        self.record(field, rendered.trim_matches('"'));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    /// `MakeWriter` that appends to a shared buffer the test can inspect.
+    #[derive(Clone)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn panics_render_as_parseable_json_events() {
+        let caught =
+            std::panic::catch_unwind(|| panic!("boom in handler")).expect_err("closure panicked");
+        let event = super::panic_event(
+            &super::panic_message(caught.as_ref()),
+            Some("src/routes/user_routes.rs:1:1"),
+            Some("0: frame"),
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str(&event).expect("one JSON line");
+        assert_eq!(parsed["panic"], "boom in handler");
+        assert_eq!(parsed["location"], "src/routes/user_routes.rs:1:1");
+        assert_eq!(parsed["backtrace"], "0: frame");
+        assert!(!event.contains('\n'));
+    }
+
+    #[test]
+    fn redact_is_deterministic_and_hides_the_value() {
+        let placeholder = super::redact("alice@example.com");
+        assert!(placeholder.starts_with("[redacted:"));
+        assert!(!placeholder.contains("alice"));
+        assert_eq!(placeholder, super::redact("alice@example.com"));
+        assert_ne!(placeholder, super::redact("bob@example.com"));
+    }
+
+    #[tokio::test]
+    async fn request_logs_carry_placeholders_instead_of_pii() {
+        let capture = Capture(Arc::new(Mutex::new(Vec::new())));
+        let layer =
+            super::RedactionLayer::new(super::redacted_fields()).with_writer(capture.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = test_app(test_state());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer super-secret-token")
+                    .body(Body::from(
+                        r#"{"name":"Alice","email":"alice@example.com"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::CREATED);
+        tracing::info!(
+            authorization = "Bearer super-secret-token",
+            "authenticated request"
+        );
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            !output.contains("alice@example.com"),
+            "plaintext email leaked: {output}"
+        );
+        assert!(
+            !output.contains("super-secret-token"),
+            "plaintext token leaked: {output}"
+        );
+        assert!(
+            output.contains(&super::redact("alice@example.com")),
+            "expected email placeholder in: {output}"
+        );
+    }
+}
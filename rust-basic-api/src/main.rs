@@ -0,0 +1,86 @@
+use rust_basic_api::config::Config;
+use rust_basic_api::logging;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Development-only; the outcome is logged below once tracing is up.
+    let dotenv_status = rust_basic_api::config::load_dotenv();
+
+    // Emit the route manifest and exit before any config validation or
+    // port binding, so gateway config can be generated offline.
+    if std::env::args().any(|arg| arg == "--print-routes") {
+        let base_path = std::env::var("BASE_PATH").unwrap_or_default();
+        println!("{}", rust_basic_api::routes::manifest_json(&base_path));
+        return Ok(());
+    }
+
+    // `--healthcheck` probes the already-running server and exits with
+    // its verdict, for the container HEALTHCHECK. Like `--print-routes`
+    // it runs before tracing and config validation: the probe must not
+    // fail because of an unrelated startup concern, and must not log.
+    if std::env::args().any(|arg| arg == "--healthcheck") {
+        let liveness = std::env::args().any(|arg| arg == "--liveness");
+        std::process::exit(rust_basic_api::healthcheck::run(liveness).await);
+    }
+
+    logging::install_panic_hook();
+
+    let output_layer = if logging::redaction_enabled() {
+        logging::RedactionLayer::new(logging::redacted_fields()).boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+        ))
+        .with(output_layer)
+        .init();
+
+    match &dotenv_status {
+        rust_basic_api::config::DotenvStatus::Loaded { path, contributed } => {
+            tracing::info!(
+                path = %path.display(),
+                contributed,
+                "loaded environment variables from .env"
+            );
+        }
+        rust_basic_api::config::DotenvStatus::Ignored { path } => {
+            tracing::warn!(
+                path = %path.display(),
+                ".env file present but ignored: ENVIRONMENT is not development"
+            );
+        }
+        rust_basic_api::config::DotenvStatus::NotFound => {}
+    }
+
+    let config = Config::from_env()?;
+
+    // `--seed [count]`: load deterministic development data and exit
+    // instead of serving. `--force` overrides the production guard.
+    if let Some(count) = seed_count() {
+        let force = std::env::args().any(|arg| arg == "--force");
+        return rust_basic_api::seed::run(config, count, force).await;
+    }
+
+    rust_basic_api::run_application(config).await
+}
+
+/// The requested seed count when `--seed` was passed: the following
+/// argument if it parses as a number, the default otherwise.
+fn seed_count() -> Option<usize> {
+    let mut args = std::env::args().peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            return Some(
+                args.peek()
+                    .and_then(|next| next.parse().ok())
+                    .unwrap_or(rust_basic_api::seed::DEFAULT_COUNT),
+            );
+        }
+    }
+    None
+}
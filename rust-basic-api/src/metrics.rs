@@ -0,0 +1,107 @@
+//! Prometheus metrics registered against the default registry and exposed
+//! at `GET /metrics`.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    Histogram, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+
+/// Request body sizes in bytes, labeled by matched route.
+pub static REQUEST_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_request_size_bytes",
+        "HTTP request body size in bytes",
+        &["route"],
+        prometheus::exponential_buckets(64.0, 4.0, 10).unwrap()
+    )
+    .expect("metric registration")
+});
+
+/// Response body sizes in bytes, labeled by matched route.
+pub static RESPONSE_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_response_size_bytes",
+        "HTTP response body size in bytes",
+        &["route"],
+        prometheus::exponential_buckets(64.0, 4.0, 10).unwrap()
+    )
+    .expect("metric registration")
+});
+
+/// Responses that exceeded the configured soft size limit.
+pub static RESPONSE_OVERSIZED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_response_oversized_total",
+        "Responses larger than the configured soft size limit",
+        &["route"]
+    )
+    .expect("metric registration")
+});
+
+/// Requests by caller identity and status class. The caller label set is
+/// bounded by `USAGE_MAX_CALLERS` with an `other` overflow bucket.
+pub static REQUESTS_BY_CALLER: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_by_caller_total",
+        "HTTP requests by caller identity and status class",
+        &["caller", "status"]
+    )
+    .expect("metric registration")
+});
+
+/// Error responses by status class and [`crate::error::AppError`] variant.
+/// `class` is `client_error` (4xx) or `server_error` (5xx), so dashboards
+/// can split caller mistakes from our failures with one label filter.
+pub static ERRORS_BY_CLASS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "app_errors_total",
+        "Error responses by status class and error variant",
+        &["class", "variant"]
+    )
+    .expect("metric registration")
+});
+
+/// Time spent waiting for a connection from the pool.
+pub static DB_ACQUIRE_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "db_pool_acquire_duration_seconds",
+        "Time waiting to acquire a database connection from the pool",
+        prometheus::exponential_buckets(0.001, 4.0, 8).unwrap()
+    )
+    .expect("metric registration")
+});
+
+/// Requests abandoned by the client while a query was still running.
+pub static REQUESTS_ABANDONED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "requests_abandoned_total",
+        "Requests whose client disconnected while a query was running"
+    )
+    .expect("metric registration")
+});
+
+/// Pool acquisitions that hit the acquire timeout.
+pub static DB_ACQUIRE_TIMEOUTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "db_pool_acquire_timeouts_total",
+        "Database pool acquisitions that timed out"
+    )
+    .expect("metric registration")
+});
+
+/// Pool acquisitions slower than the configured warn threshold.
+pub static DB_ACQUIRE_SLOW: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "db_pool_acquire_slow_total",
+        "Database pool acquisitions slower than the warn threshold"
+    )
+    .expect("metric registration")
+});
+
+/// Render the default registry in the Prometheus text exposition format.
+pub fn render() -> String {
+    TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_default()
+}
@@ -0,0 +1,185 @@
+//! Sampled per-request access logging.
+//!
+//! One structured line per request is expensive at high RPS, and the
+//! successful ones are mostly noise. `ACCESS_LOG_SAMPLE_RATE` keeps only
+//! a sampled fraction of success lines (default `1.0`, everything),
+//! while error responses — anything 4xx or 5xx — are always logged:
+//! those are the lines an investigation starts from. Sampling uses a
+//! cheap thread-local xorshift generator, so the hot path costs a few
+//! arithmetic ops and no locking. Liveness probes are noisier still:
+//! `ACCESS_LOG_QUIET_PATHS` (default `/health`) skips those paths
+//! entirely, errors included.
+
+use std::cell::Cell;
+use std::time::Instant;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+thread_local! {
+    static RNG: Cell<u64> = Cell::new(seed());
+}
+
+/// Per-thread seed; `RandomState` is randomly keyed per instance, which
+/// is all the quality a log sampler needs.
+fn seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    // xorshift must not start at zero.
+    hasher.finish() | 1
+}
+
+/// Roll the sampler: `true` on a fraction `rate` of calls. The edge
+/// rates short-circuit so `1.0` never drops a line and `0.0` never
+/// burns a random number.
+fn sampled(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        // Top 53 bits give a uniform float in [0, 1).
+        #[allow(clippy::cast_precision_loss)]
+        let roll = (x >> 11) as f64 / (1u64 << 53) as f64;
+        roll < rate
+    })
+}
+
+/// Log one line per request: errors always, successes per
+/// `ACCESS_LOG_SAMPLE_RATE`.
+pub async fn log_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    // Quiet paths (liveness probes) skip the log and its allocations
+    // outright, errors included — at fleet probe rates even the path
+    // clone below is measurable.
+    if state
+        .config
+        .access_log_quiet_paths
+        .iter()
+        .any(|path| path == request.uri().path())
+    {
+        return next.run(request).await;
+    }
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let is_error = status.is_client_error() || status.is_server_error();
+    if is_error || sampled(state.config.access_log_sample_rate) {
+        tracing::info!(
+            method = %method,
+            path = %path,
+            status = status.as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "handled request"
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    /// `MakeWriter` that appends to a shared buffer the test can inspect.
+    #[derive(Clone)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    async fn captured_output(rate: f64, uris: &[&str]) -> String {
+        let capture = Capture(Arc::new(Mutex::new(Vec::new())));
+        let layer = crate::logging::RedactionLayer::new(crate::logging::redacted_fields())
+            .with_writer(capture.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut state = test_state();
+        state.config.access_log_sample_rate = rate;
+        let app = test_app(state);
+        for uri in uris {
+            app.clone()
+                .oneshot(Request::builder().uri(*uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+        let output = capture.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_zero_rate_suppresses_successes_but_not_errors() {
+        // `/users` succeeds; `/users/999` is a forced 404.
+        let output = captured_output(0.0, &["/users", "/users/999"]).await;
+        assert!(
+            !output.contains("path=/users status=200"),
+            "sampled-out success logged: {output}"
+        );
+        assert!(
+            output.contains("path=/users/999 status=404"),
+            "error line missing: {output}"
+        );
+    }
+
+    #[tokio::test]
+    async fn quiet_paths_never_log_even_at_full_rate() {
+        let output = captured_output(1.0, &["/health", "/users"]).await;
+        assert!(
+            !output.contains("path=/health"),
+            "quiet path logged: {output}"
+        );
+        assert!(
+            output.contains("path=/users status=200"),
+            "non-quiet success missing: {output}"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_full_rate_logs_every_request() {
+        let output = captured_output(1.0, &["/users", "/users/999"]).await;
+        assert!(
+            output.contains("path=/users status=200"),
+            "success line missing: {output}"
+        );
+        assert!(
+            output.contains("path=/users/999 status=404"),
+            "error line missing: {output}"
+        );
+    }
+}
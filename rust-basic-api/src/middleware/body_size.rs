@@ -0,0 +1,257 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::Body;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+
+use crate::metrics;
+use crate::AppState;
+
+/// Record request and response body sizes and enforce the configured
+/// response size limits.
+///
+/// Sizes come from `Content-Length` when present; otherwise the body is
+/// wrapped in a streaming counter that records once the body finishes,
+/// without buffering. When a response exceeds the soft limit a warning is
+/// logged and `http_response_oversized_total` is incremented; when it
+/// exceeds the hard limit the body stream is aborted.
+pub async fn track_body_sizes(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |m| m.as_str().to_string());
+
+    let req = match content_length(req.headers()) {
+        Some(size) => {
+            metrics::REQUEST_SIZE_BYTES
+                .with_label_values(&[&route])
+                .observe(size as f64);
+            req
+        }
+        None => {
+            let observe_route = route.clone();
+            req.map(|body| {
+                Body::new(CountingBody::new(body, move |size| {
+                    metrics::REQUEST_SIZE_BYTES
+                        .with_label_values(&[&observe_route])
+                        .observe(size as f64);
+                }))
+            })
+        }
+    };
+
+    let response = next.run(req).await;
+
+    let soft_limit = state.config.response_size_soft_limit;
+    let hard_limit = state.config.response_size_hard_limit;
+    let observe_route = route.clone();
+    let observe = move |size: u64| {
+        metrics::RESPONSE_SIZE_BYTES
+            .with_label_values(&[&observe_route])
+            .observe(size as f64);
+        if soft_limit.is_some_and(|limit| size > limit) {
+            metrics::RESPONSE_OVERSIZED
+                .with_label_values(&[&observe_route])
+                .inc();
+            tracing::warn!(
+                route = %observe_route,
+                size,
+                limit = soft_limit.unwrap_or_default(),
+                "response exceeded soft size limit"
+            );
+        }
+    };
+
+    match content_length(response.headers()) {
+        Some(size) => {
+            observe(size);
+            response
+        }
+        None => response.map(|body| {
+            let mut counting = CountingBody::new(body, observe);
+            counting.hard_limit = hard_limit;
+            Body::new(counting)
+        }),
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(axum::http::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Streaming body wrapper that counts bytes as frames pass through and
+/// invokes a callback with the total once the body completes.
+struct CountingBody<F> {
+    inner: Body,
+    counted: u64,
+    hard_limit: Option<u64>,
+    on_complete: Option<F>,
+}
+
+impl<F: FnOnce(u64)> CountingBody<F> {
+    fn new(inner: Body, on_complete: F) -> Self {
+        Self {
+            inner,
+            counted: 0,
+            hard_limit: None,
+            on_complete: Some(on_complete),
+        }
+    }
+
+    fn complete(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(self.counted);
+        }
+    }
+}
+
+impl<F: FnOnce(u64) + Send + Unpin> HttpBody for CountingBody<F> {
+    type Data = axum::body::Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.counted += data.len() as u64;
+                    if self.hard_limit.is_some_and(|limit| self.counted > limit) {
+                        self.complete();
+                        return Poll::Ready(Some(Err(axum::Error::new(
+                            "response exceeded hard size limit",
+                        ))));
+                    }
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.complete();
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(None) => {
+                self.complete();
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::metrics;
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn records_response_sizes_and_flags_oversized() {
+        let mut state = test_state();
+        state.config.response_size_soft_limit = Some(256);
+        let app = test_app(state.clone());
+
+        // A large enough user list to blow past the soft limit.
+        for i in 0..20 {
+            let name = "x".repeat(200);
+            app.clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/users")
+                        .header("content-type", "application/json")
+                        .body(Body::from(format!(
+                            r#"{{"name":"{name}","email":"user{i}@example.com"}}"#
+                        )))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // Drive the counting wrapper to completion.
+        response.into_body().collect().await.unwrap();
+
+        let rendered = metrics::render();
+        assert!(
+            rendered.contains("http_response_size_bytes_bucket"),
+            "missing size histogram: {rendered}"
+        );
+        assert!(
+            metrics::RESPONSE_OVERSIZED
+                .with_label_values(&["/users"])
+                .get()
+                >= 1,
+            "oversized counter not incremented"
+        );
+    }
+
+    #[tokio::test]
+    async fn every_response_lands_a_sample_in_the_size_histogram() {
+        let app = test_app(test_state());
+        let before = metrics::RESPONSE_SIZE_BYTES
+            .with_label_values(&["/health"])
+            .get_sample_count();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response.into_body().collect().await.unwrap();
+
+        assert_eq!(
+            metrics::RESPONSE_SIZE_BYTES
+                .with_label_values(&["/health"])
+                .get_sample_count(),
+            before + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_serves_exposition_format() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+}
@@ -0,0 +1,198 @@
+//! Test-only fault injection behind the `chaos` cargo feature, so QA can
+//! rehearse downstream behavior under latency and failures without
+//! touching the real database. The whole module — middleware, state, and
+//! the `POST /admin/chaos` endpoint — is compiled out of default builds,
+//! so the feature cannot be switched on in production by configuration
+//! alone.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderName, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// Header marking a response as synthesized by the chaos layer, so load
+/// tests and dashboards can tell injected failures from real ones.
+pub const HEADER: &str = "x-chaos";
+
+/// Injection settings applied by [`inject_chaos`], set at runtime through
+/// `POST /admin/chaos`. The default (empty) settings inject nothing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChaosSettings {
+    /// Artificial delay added to every matching request, in milliseconds.
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Percentage (0–100) of matching responses replaced with an injected
+    /// failure.
+    #[serde(default)]
+    pub error_rate: u8,
+    /// Status injected failures carry: 500 or 503 (the default).
+    #[serde(default)]
+    pub error_status: Option<u16>,
+    /// Path prefixes the injection applies to; empty matches nothing.
+    #[serde(default)]
+    pub affected_routes: Vec<String>,
+}
+
+/// Live injection settings, swappable at runtime through the admin
+/// endpoint. Same snapshot pattern as [`crate::middleware::CorsOrigins`]:
+/// a request keeps the settings snapshot it started with even if they are
+/// replaced mid-flight.
+#[derive(Default)]
+pub struct ChaosState {
+    settings: RwLock<Arc<ChaosSettings>>,
+    /// Monotonic counter driving the error-rate sampling: matching request
+    /// `n` fails when `n % 100 < error_rate`. Deterministic on purpose — a
+    /// configured 100% rate fails every request, which tests rely on.
+    sample: AtomicU64,
+}
+
+impl ChaosState {
+    /// The settings snapshot for the current request.
+    pub fn current(&self) -> Arc<ChaosSettings> {
+        self.settings.read().expect("chaos lock poisoned").clone()
+    }
+
+    /// Swap in new settings, returning the set now in effect.
+    pub fn replace(&self, settings: ChaosSettings) -> Arc<ChaosSettings> {
+        let settings = Arc::new(settings);
+        *self.settings.write().expect("chaos lock poisoned") = settings.clone();
+        settings
+    }
+}
+
+/// Inject the configured latency and failures into matching requests.
+/// Injected failures carry an `X-Chaos: injected` header and a distinct
+/// log line; everything else passes through untouched.
+pub async fn inject_chaos(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let settings = state.chaos.current();
+    let path = request.uri().path();
+    if !settings
+        .affected_routes
+        .iter()
+        .any(|route| path.starts_with(route.as_str()))
+    {
+        return next.run(request).await;
+    }
+
+    if settings.latency_ms > 0 {
+        tokio::time::sleep(std::time::Duration::from_millis(settings.latency_ms)).await;
+    }
+
+    let roll = state.chaos.sample.fetch_add(1, Ordering::Relaxed) % 100;
+    if roll < u64::from(settings.error_rate.min(100)) {
+        let status = match settings.error_status {
+            Some(500) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        tracing::warn!(
+            path,
+            status = status.as_u16(),
+            "chaos: replacing response with an injected failure"
+        );
+        return (status, [(HeaderName::from_static(HEADER), "injected")]).into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    fn configure(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/admin/chaos")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn list_users() -> Request<Body> {
+        Request::builder()
+            .uri("/users")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn full_error_rate_injects_marked_failures_until_reset() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(configure(
+                r#"{"error_rate":100,"affected_routes":["/users"]}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(list_users()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(super::HEADER).unwrap(), "injected");
+
+        // The admin endpoint itself only matches when configured to, so
+        // injection can always be turned back off.
+        let response = app.clone().oneshot(configure("{}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(list_users()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(super::HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn latency_and_error_status_are_configurable() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(configure(
+                r#"{"latency_ms":20,"error_rate":100,"error_status":500,"affected_routes":["/users"]}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let started = std::time::Instant::now();
+        let response = app.oneshot(list_users()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn invalid_settings_are_rejected() {
+        let app = test_app(test_state());
+
+        for body in [
+            r#"{"error_rate":150,"affected_routes":["/users"]}"#,
+            r#"{"error_rate":100,"error_status":404,"affected_routes":["/users"]}"#,
+        ] {
+            let response = app.clone().oneshot(configure(body)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "body: {body}");
+        }
+    }
+
+    #[test]
+    fn the_endpoint_appears_in_the_manifest_with_the_feature_on() {
+        assert!(crate::routes::manifest("")
+            .iter()
+            .any(|route| route.path == "/admin/chaos"));
+    }
+}
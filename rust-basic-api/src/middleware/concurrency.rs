@@ -0,0 +1,176 @@
+//! Concurrency ceiling for DB-heavy routes.
+//!
+//! Scan endpoints (the user listing and its changes feed) hold a pool
+//! connection for much longer than point lookups; enough of them in
+//! flight starve every other route of connections. Routes marked
+//! `concurrency_limited` in [`crate::routes::route_table`] share a
+//! semaphore sized by `ROUTE_CONCURRENCY_LIMIT`, and overflow is shed
+//! immediately with a 503 rather than queued — the caller can retry,
+//! while queuing would only move the pile-up in front of the pool. Light
+//! routes are untouched, so `/health` stays responsive however saturated
+//! the heavy routes are.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use once_cell::sync::Lazy;
+use tokio::sync::Semaphore;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::AppState;
+
+/// Routes that count against the ceiling, derived from the route table
+/// once.
+static LIMITED_ROUTES: Lazy<HashSet<(&'static str, &'static str)>> = Lazy::new(|| {
+    crate::routes::route_table()
+        .into_iter()
+        .filter(|(spec, _)| spec.concurrency_limited)
+        .map(|(spec, _)| (spec.method, spec.path))
+        .collect()
+});
+
+/// Shared ceiling for the DB-heavy routes; `None` when the limit is
+/// disabled (`ROUTE_CONCURRENCY_LIMIT=0`).
+pub struct RouteConcurrency(Option<Arc<Semaphore>>);
+
+impl RouteConcurrency {
+    pub fn from_config(config: &Config) -> Self {
+        Self(match config.route_concurrency_limit {
+            0 => None,
+            limit => Some(Arc::new(Semaphore::new(limit))),
+        })
+    }
+
+    /// Take one permit directly, standing in for a slow in-flight scan.
+    /// `None` when the ceiling is disabled or already exhausted.
+    #[cfg(test)]
+    pub(crate) fn try_hold(&self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        self.0.as_ref()?.clone().try_acquire_owned().ok()
+    }
+}
+
+/// Shed DB-heavy requests over the concurrency ceiling with a 503; all
+/// other routes (and unmatched requests) pass through untouched.
+pub async fn limit_route_concurrency(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(semaphore) = &state.route_concurrency.0 else {
+        return next.run(request).await;
+    };
+    if !is_limited(&state, &request) {
+        return next.run(request).await;
+    }
+    match semaphore.try_acquire() {
+        // The permit spans the handler, releasing on response (or drop).
+        Ok(_permit) => next.run(request).await,
+        Err(_) => AppError::http(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server is busy; retry shortly",
+        )
+        .into_response(),
+    }
+}
+
+/// Whether the matched route is marked `concurrency_limited`, accounting
+/// for a configured base path prefix (as the rate limiter does).
+fn is_limited(state: &AppState, request: &Request) -> bool {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|matched| {
+            let path = crate::normalized_base_path(&state.config.base_path)
+                .and_then(|prefix| {
+                    matched
+                        .as_str()
+                        .strip_prefix(prefix.as_str())
+                        .filter(|rest| rest.starts_with('/'))
+                })
+                .unwrap_or(matched.as_str());
+            LIMITED_ROUTES.contains(&(request.method().as_str(), path))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn saturated_heavy_routes_shed_while_health_stays_responsive() {
+        let mut state = test_state();
+        state.config.route_concurrency_limit = 1;
+        state.route_concurrency =
+            std::sync::Arc::new(super::RouteConcurrency::from_config(&state.config));
+        // Hold the single permit, standing in for a slow in-flight scan.
+        let held = state.route_concurrency.try_hold().unwrap();
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Light routes do not share the ceiling.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Releasing the permit lets the heavy route through again.
+        drop(held);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_zero_limit_disables_the_ceiling() {
+        let mut state = test_state();
+        state.config.route_concurrency_limit = 0;
+        state.route_concurrency =
+            std::sync::Arc::new(super::RouteConcurrency::from_config(&state.config));
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,260 @@
+//! Read-after-write consistency negotiation.
+//!
+//! With replica routing, a client that creates a user and immediately
+//! reads it back can hit a replica that has not replayed the write yet.
+//! Two escape hatches are offered:
+//!
+//! * `Consistency: strong` (or `?consistency=strong`) forces the
+//!   request's reads onto the primary unconditionally;
+//! * every successful mutation returns an `X-Consistency-Token` carrying
+//!   the primary's WAL position; a read presenting the token is served
+//!   from the replica only once replay has caught up to it
+//!   ([`crate::repository::UserRepository::wal_caught_up`]), and falls
+//!   back to the primary otherwise.
+//!
+//! The chosen source is echoed in `X-Consistency-Source`. This
+//! deployment currently runs a single pool, so "replica" and "primary"
+//! name the same database — the negotiation (and the LSN ordering behind
+//! it) is what this module pins down, so wiring in a real replica pool
+//! changes routing, not the protocol.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::AppError;
+use crate::repository::Lsn;
+use crate::AppState;
+
+/// Response header carrying the post-mutation consistency token.
+pub const TOKEN_HEADER: &str = "x-consistency-token";
+
+/// Response header naming where a read was served from.
+pub const SOURCE_HEADER: &str = "x-consistency-source";
+
+/// Request header selecting a consistency level (`strong` only).
+pub const CONSISTENCY_HEADER: &str = "consistency";
+
+/// Negotiate the read source for the request and stamp mutation
+/// responses with a fresh consistency token.
+pub async fn negotiate_consistency(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Everything needed from the request is extracted up front: the
+    // request cannot stay borrowed across the replay check below.
+    let decision = match read_decision(&request) {
+        Ok(decision) => decision,
+        Err(error) => return error.into_response(),
+    };
+    let source = match decision {
+        ReadDecision::NotARead | ReadDecision::Default => None,
+        ReadDecision::Strong => Some("primary"),
+        // An error checking replay position must not fail the read; the
+        // primary can always serve it.
+        ReadDecision::Token(token) => Some(match state.repository.wal_caught_up(token).await {
+            Ok(true) => "replica",
+            Ok(false) | Err(_) => "primary",
+        }),
+    };
+    let is_write = matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+
+    let mut response = next.run(request).await;
+
+    if let Some(source) = source {
+        response
+            .headers_mut()
+            .insert(SOURCE_HEADER, HeaderValue::from_static(source));
+    }
+    if is_write && response.status().is_success() {
+        if let Ok(token) = state.repository.wal_token().await {
+            if let Ok(value) = HeaderValue::from_str(&token.to_string()) {
+                response.headers_mut().insert(TOKEN_HEADER, value);
+            }
+        }
+    }
+    response
+}
+
+/// What a request asked of the read path, decided before any I/O.
+enum ReadDecision {
+    /// Not a read; there is no source to negotiate.
+    NotARead,
+    /// A read with no stated preference.
+    Default,
+    /// A read demanding the primary.
+    Strong,
+    /// A read gated on the replica having replayed up to the token.
+    Token(Lsn),
+}
+
+/// Classify the request's consistency demands.
+fn read_decision(request: &Request) -> crate::error::Result<ReadDecision> {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return Ok(ReadDecision::NotARead);
+    }
+    if wants_strong(request)? {
+        return Ok(ReadDecision::Strong);
+    }
+    Ok(match presented_token(request)? {
+        Some(token) => ReadDecision::Token(token),
+        None => ReadDecision::Default,
+    })
+}
+
+/// Whether the request asked for strong consistency, via header or query
+/// parameter. Levels other than `strong` are rejected so a typo cannot
+/// silently read stale data.
+fn wants_strong(request: &Request) -> crate::error::Result<bool> {
+    if let Some(level) = request.headers().get(CONSISTENCY_HEADER) {
+        return match level.to_str() {
+            Ok(level) if level.eq_ignore_ascii_case("strong") => Ok(true),
+            _ => Err(AppError::Validation(
+                "unsupported consistency level; only \"strong\" is recognized".to_string(),
+            )),
+        };
+    }
+    Ok(request
+        .uri()
+        .query()
+        .is_some_and(|query| query.split('&').any(|pair| pair == "consistency=strong")))
+}
+
+/// The consistency token presented on the request, if any.
+fn presented_token(request: &Request) -> crate::error::Result<Option<Lsn>> {
+    let Some(raw) = request.headers().get(TOKEN_HEADER) else {
+        return Ok(None);
+    };
+    let raw = raw
+        .to_str()
+        .map_err(|_| AppError::Validation("invalid consistency token".to_string()))?;
+    raw.parse().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn mutations_issue_tokens_that_reads_can_present() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"name":"Strong","email":"strong@example.com"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = response.headers()[super::TOKEN_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Compare-equal path: the single backing store has "replayed" its
+        // own write, so the replica may serve the read.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users/1")
+                    .header(super::TOKEN_HEADER, &token)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[super::SOURCE_HEADER], "replica");
+
+        // A token from the future (a write this replica has not replayed)
+        // falls back to the primary.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/1")
+                    .header(super::TOKEN_HEADER, "FFFF/0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[super::SOURCE_HEADER], "primary");
+    }
+
+    #[tokio::test]
+    async fn strong_consistency_forces_the_primary() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .header(super::CONSISTENCY_HEADER, "strong")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[super::SOURCE_HEADER], "primary");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?consistency=strong")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[super::SOURCE_HEADER], "primary");
+    }
+
+    #[tokio::test]
+    async fn malformed_tokens_and_unknown_levels_are_rejected() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .header(super::TOKEN_HEADER, "not-an-lsn")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .header(super::CONSISTENCY_HEADER, "eventual")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
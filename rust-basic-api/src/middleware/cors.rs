@@ -0,0 +1,243 @@
+//! Browser CORS support for the configured frontend origins.
+//!
+//! The allowed origins come from `CORS_ALLOWED_ORIGINS` (comma-separated;
+//! empty disables CORS entirely) and live behind a swappable snapshot so
+//! `POST /admin/cors/reload` can pick up a changed environment without a
+//! restart. Only the origin set is hot-reloadable: the allowed methods and
+//! headers below are part of the deployed code and change with it.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+
+use axum::extract::{Request, State};
+use axum::http::header::{HeaderValue, ORIGIN, VARY};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::config::Config;
+use crate::AppState;
+
+/// The environment variable the origin set is read (and re-read) from.
+pub const ORIGINS_ENV: &str = "CORS_ALLOWED_ORIGINS";
+
+/// Methods and headers granted to allowed origins; fixed at build time.
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+const ALLOWED_HEADERS: &str = "authorization, content-type, if-none-match";
+
+/// The allowed-origin set behind a swappable snapshot (the same pattern as
+/// [`crate::repository::PoolHandle`]), so a reload swaps atomically while
+/// in-flight requests keep the snapshot they started with.
+pub struct CorsOrigins {
+    origins: RwLock<Arc<BTreeSet<String>>>,
+}
+
+impl CorsOrigins {
+    /// The origin set as configured through the environment at startup.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            origins: RwLock::new(Arc::new(
+                config.cors_allowed_origins.iter().cloned().collect(),
+            )),
+        }
+    }
+
+    /// The current origin snapshot.
+    pub fn current(&self) -> Arc<BTreeSet<String>> {
+        self.origins.read().expect("cors lock poisoned").clone()
+    }
+
+    /// Swap in a new origin set, returning the snapshot now in effect.
+    pub fn replace(&self, origins: Vec<String>) -> Arc<BTreeSet<String>> {
+        let snapshot = Arc::new(origins.into_iter().collect::<BTreeSet<String>>());
+        *self.origins.write().expect("cors lock poisoned") = snapshot.clone();
+        snapshot
+    }
+
+    /// Whether the given `Origin` header value is allowed. Origins are
+    /// matched case-insensitively; scheme and host are not case-sensitive
+    /// per RFC 6454 and the configured list is already lowercased.
+    pub fn allows(&self, origin: &str) -> bool {
+        self.current().contains(&origin.to_ascii_lowercase())
+    }
+}
+
+/// Attach CORS headers for allowed origins and answer their preflights.
+///
+/// With no origins configured the middleware is inert. Requests from
+/// origins outside the set pass through without CORS headers — the browser
+/// enforces the block; the server does not reject them, since non-browser
+/// callers send no `Origin` at all.
+pub async fn apply_cors(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let origin = request
+        .headers()
+        .get(ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let allowed = origin
+        .as_deref()
+        .is_some_and(|origin| state.cors.allows(origin));
+
+    if allowed
+        && request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key("access-control-request-method")
+    {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        apply_headers(&mut response, origin.as_deref().unwrap_or_default());
+        response
+            .headers_mut()
+            .insert("access-control-allow-methods", allowed_methods());
+        response
+            .headers_mut()
+            .insert("access-control-allow-headers", allowed_headers());
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    if allowed {
+        apply_headers(&mut response, origin.as_deref().unwrap_or_default());
+    }
+    response
+}
+
+fn apply_headers(response: &mut Response, origin: &str) {
+    if let Ok(value) = HeaderValue::from_str(origin) {
+        response
+            .headers_mut()
+            .insert("access-control-allow-origin", value);
+        // The response varies by origin; caches must not serve one
+        // origin's grant to another.
+        response
+            .headers_mut()
+            .append(VARY, HeaderValue::from_static("origin"));
+    }
+}
+
+fn allowed_methods() -> HeaderValue {
+    HeaderValue::from_static(ALLOWED_METHODS)
+}
+
+fn allowed_headers() -> HeaderValue {
+    HeaderValue::from_static(ALLOWED_HEADERS)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    fn cors_state(origins: &[&str]) -> crate::AppState {
+        let mut state = test_state();
+        state.config.cors_allowed_origins = origins.iter().map(|s| s.to_string()).collect();
+        state.cors = std::sync::Arc::new(super::CorsOrigins::from_config(&state.config));
+        state
+    }
+
+    fn get_users(origin: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/users")
+            .header("origin", origin)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn allowed_origins_get_cors_headers_and_others_do_not() {
+        let app = test_app(cors_state(&["https://app.example.com"]));
+
+        let response = app
+            .clone()
+            .oneshot(get_users("https://app.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .unwrap(),
+            "https://app.example.com"
+        );
+
+        let response = app
+            .oneshot(get_users("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn preflight_is_answered_for_allowed_origins() {
+        let app = test_app(cors_state(&["https://app.example.com"]));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/users")
+                    .header("origin", "https://app.example.com")
+                    .header("access-control-request-method", "POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response
+            .headers()
+            .get("access-control-allow-methods")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+    }
+
+    #[tokio::test]
+    async fn reload_picks_up_a_newly_added_origin() {
+        let app = test_app(cors_state(&["https://app.example.com"]));
+        let added = "https://new-frontend.example.com";
+
+        let response = app.clone().oneshot(get_users(added)).await.unwrap();
+        assert!(response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none());
+
+        // No other test reads this variable, so mutating the process
+        // environment here cannot race.
+        std::env::set_var(
+            super::ORIGINS_ENV,
+            "https://app.example.com, https://new-frontend.example.com",
+        );
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/cors/reload")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(get_users(added)).await.unwrap();
+        assert_eq!(
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .unwrap(),
+            added
+        );
+    }
+}
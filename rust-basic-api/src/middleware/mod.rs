@@ -0,0 +1,37 @@
+pub mod access_log;
+pub mod body_size;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod concurrency;
+pub mod consistency;
+pub mod cors;
+pub mod http_version;
+pub mod mtls;
+pub mod rate_limit;
+pub mod read_only;
+pub mod retry_advice;
+pub mod route_header;
+pub mod server_timing;
+pub mod slo;
+pub mod strip_headers;
+pub mod tenant;
+pub mod usage;
+
+pub use access_log::log_requests;
+pub use body_size::track_body_sizes;
+#[cfg(feature = "chaos")]
+pub use chaos::{inject_chaos, ChaosState};
+pub use concurrency::{limit_route_concurrency, RouteConcurrency};
+pub use consistency::negotiate_consistency;
+pub use cors::{apply_cors, CorsOrigins};
+pub use http_version::reject_unsupported_versions;
+pub use mtls::require_mtls_on_admin;
+pub use rate_limit::{enforce_rate_limits, RateLimits};
+pub use read_only::reject_writes_when_read_only;
+pub use retry_advice::annotate_retry_advice;
+pub use route_header::expose_matched_route;
+pub use server_timing::record_server_timing;
+pub use slo::{track_slo_outcomes, SloWindows};
+pub use strip_headers::strip_response_headers;
+pub use tenant::{resolve_tenant, Tenant, TenantContext};
+pub use usage::{track_caller_usage, UsageWindow};
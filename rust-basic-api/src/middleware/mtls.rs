@@ -0,0 +1,104 @@
+//! Client-certificate (mTLS) enforcement for admin endpoints.
+//!
+//! When `MTLS_CA_CERT` is configured the server terminates TLS itself
+//! (see [`crate::server::mtls_server_config`]) and records, per
+//! connection, whether the peer presented a certificate signed by that
+//! CA. This middleware then requires a verified certificate on `/admin/*`
+//! routes only — regular API routes stay reachable with server-side TLS
+//! alone, so the zero-trust requirement on operator tooling does not
+//! force every client integration to manage certificates.
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::AppError;
+use crate::AppState;
+
+/// Per-connection marker inserted by the TLS accept loop: whether the
+/// peer presented a certificate (which the handshake verified against
+/// `MTLS_CA_CERT`; unverifiable certificates never complete the
+/// handshake).
+#[derive(Debug, Clone, Copy)]
+pub struct ClientCertificate {
+    pub presented: bool,
+}
+
+/// Reject `/admin/*` requests lacking a verified client certificate with
+/// a 403. A no-op unless `MTLS_CA_CERT` is configured, so plain-HTTP
+/// deployments (and the in-process test apps) are unaffected.
+pub async fn require_mtls_on_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.config.mtls_ca_cert.is_some() && is_admin_path(&state, request.uri().path()) {
+        let presented = request
+            .extensions()
+            .get::<ClientCertificate>()
+            .is_some_and(|cert| cert.presented);
+        if !presented {
+            return AppError::http(
+                StatusCode::FORBIDDEN,
+                "admin endpoints require a client certificate",
+            )
+            .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Whether the request path addresses the admin surface, accounting for
+/// a configured base path prefix.
+fn is_admin_path(state: &AppState, path: &str) -> bool {
+    let path = crate::normalized_base_path(&state.config.base_path)
+        .and_then(|prefix| {
+            path.strip_prefix(prefix.as_str())
+                .filter(|rest| rest.starts_with('/'))
+        })
+        .unwrap_or(path);
+    path == "/admin" || path.starts_with("/admin/")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn admin_requests_without_a_certificate_are_rejected_when_configured() {
+        let mut state = test_state();
+        state.config.mtls_ca_cert = Some("/etc/certs/ca.pem".to_string());
+        let app = test_app(state);
+
+        // No TLS accept loop ran, so no `ClientCertificate` extension is
+        // present — exactly the shape of an uncertified request.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/routes")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Non-admin routes are not subject to the certificate requirement.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
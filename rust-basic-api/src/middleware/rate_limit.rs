@@ -0,0 +1,314 @@
+//! Rate limiting by route class.
+//!
+//! Every route in [`crate::routes::route_table`] carries a rate-limit
+//! class (`public_read`, `public_write`, or `expensive`); this middleware
+//! counts requests per class over a one-minute window and rejects the
+//! overflow with 429. Counters live in the configured
+//! [`crate::kv::KvStore`], so with `STATE_BACKEND=postgres` the limits
+//! are shared across replicas and survive deploys; the default memory
+//! backend keeps them process-local. Limits start from the environment
+//! and can be adjusted at runtime through `PATCH /admin/rate-limits`
+//! during an incident; overrides are process-local and ephemeral — they
+//! do not survive a restart and are not propagated to other replicas.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::kv::KvStore;
+use crate::AppState;
+
+/// The three route classes limits are grouped by.
+pub mod classes {
+    /// Cheap reads: health, metrics, user lookups and listings.
+    pub const PUBLIC_READ: &str = "public_read";
+    /// User-facing mutations.
+    pub const PUBLIC_WRITE: &str = "public_write";
+    /// Admin and scan-heavy operations.
+    pub const EXPENSIVE: &str = "expensive";
+}
+
+/// Bounds accepted for any class limit, per minute.
+pub const MIN_LIMIT: u32 = 1;
+pub const MAX_LIMIT: u32 = 1_000_000;
+
+/// Where a class's effective limit came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitSource {
+    /// The value configured through the environment at startup.
+    Env,
+    /// A runtime override applied through `PATCH /admin/rate-limits`.
+    Runtime,
+}
+
+/// One class's effective limit.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClassLimit {
+    pub per_minute: u32,
+    pub source: LimitSource,
+}
+
+/// The full limit set, snapshotted atomically on every read.
+pub type RateLimitConfig = BTreeMap<&'static str, ClassLimit>;
+
+/// Shared limiter state: the current limits behind a swappable snapshot
+/// (the same pattern as [`crate::repository::PoolHandle`]). The counters
+/// themselves live in the [`KvStore`] passed to [`RateLimits::check`].
+pub struct RateLimits {
+    config: RwLock<Arc<RateLimitConfig>>,
+}
+
+impl RateLimits {
+    /// Limits as configured through the environment.
+    pub fn from_config(config: &Config) -> Self {
+        let mut classes: RateLimitConfig = BTreeMap::new();
+        for (class, per_minute) in [
+            (classes::PUBLIC_READ, config.rate_limit_public_read),
+            (classes::PUBLIC_WRITE, config.rate_limit_public_write),
+            (classes::EXPENSIVE, config.rate_limit_expensive),
+        ] {
+            classes.insert(
+                class,
+                ClassLimit {
+                    per_minute,
+                    source: LimitSource::Env,
+                },
+            );
+        }
+        Self {
+            config: RwLock::new(Arc::new(classes)),
+        }
+    }
+
+    /// The current limit snapshot.
+    pub fn current(&self) -> Arc<RateLimitConfig> {
+        self.config
+            .read()
+            .expect("rate limit lock poisoned")
+            .clone()
+    }
+
+    /// Apply a runtime override for one class, validated against
+    /// [`MIN_LIMIT`]/[`MAX_LIMIT`]. Unknown classes are rejected so a typo
+    /// cannot silently create a limit nothing enforces.
+    pub fn override_class(&self, class: &str, per_minute: u32) -> crate::error::Result<()> {
+        self.apply_overrides(std::iter::once((class, per_minute)))
+    }
+
+    /// Apply a batch of runtime overrides atomically: every entry is
+    /// validated before any limit changes, so a bad entry cannot leave a
+    /// partially applied batch behind.
+    pub fn apply_overrides<'a>(
+        &self,
+        overrides: impl IntoIterator<Item = (&'a str, u32)> + Clone,
+    ) -> crate::error::Result<()> {
+        let mut config = self.config.write().expect("rate limit lock poisoned");
+        let mut updated = (**config).clone();
+
+        for (class, per_minute) in overrides.clone() {
+            if !(MIN_LIMIT..=MAX_LIMIT).contains(&per_minute) {
+                return Err(AppError::Validation(format!(
+                    "limit for {class} must be between {MIN_LIMIT} and {MAX_LIMIT} per minute"
+                )));
+            }
+            if !updated.keys().any(|name| *name == class) {
+                return Err(AppError::Validation(format!(
+                    "unknown rate limit class: {class}"
+                )));
+            }
+        }
+        for (class, per_minute) in overrides {
+            let class = updated
+                .keys()
+                .copied()
+                .find(|name| *name == class)
+                .expect("validated above");
+            updated.insert(
+                class,
+                ClassLimit {
+                    per_minute,
+                    source: LimitSource::Runtime,
+                },
+            );
+        }
+        *config = Arc::new(updated);
+        Ok(())
+    }
+
+    /// Count one request against the class; `false` means over the limit.
+    /// Unknown classes (routes predating a limit entry) are allowed.
+    ///
+    /// Windows are fixed one-minute buckets keyed by epoch minute, so
+    /// every replica sharing the store agrees on the window boundaries.
+    /// One `incr_with_ttl` per request is the whole bookkeeping: the
+    /// window "refills" by expiring wholesale rather than by per-token
+    /// refund writes, which keeps a remote backend at a single round
+    /// trip per request.
+    pub async fn check(&self, kv: &dyn KvStore, class: &'static str) -> bool {
+        let Some(limit) = self.current().get(class).map(|limit| limit.per_minute) else {
+            return true;
+        };
+        let minute = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 60;
+        let key = format!("rate_limit:{class}:{minute}");
+        // TTL covers the window plus slack for clock skew between
+        // writers; expiry only reclaims space, correctness comes from
+        // the minute in the key.
+        match kv.incr_with_ttl(&key, 1, Duration::from_secs(120)).await {
+            Ok(count) => count <= i64::from(limit),
+            Err(error) => {
+                // Fail open: an unreachable state backend should degrade
+                // to unlimited traffic, not a full outage.
+                tracing::warn!(%error, class, "rate-limit state unavailable; allowing request");
+                true
+            }
+        }
+    }
+}
+
+/// Class per registered route, derived from the route table once.
+static ROUTE_CLASSES: Lazy<HashMap<(&'static str, &'static str), &'static str>> = Lazy::new(|| {
+    crate::routes::route_table()
+        .into_iter()
+        .map(|(spec, _)| ((spec.method, spec.path), spec.rate_limit_class))
+        .collect()
+});
+
+/// The route class for a matched request, shared with the SLO tracker.
+/// `None` for unmatched requests (404s).
+pub(crate) fn route_class(state: &AppState, request: &Request) -> Option<&'static str> {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|matched| {
+            // Nested routers report the base path in the matched pattern.
+            let path = crate::normalized_base_path(&state.config.base_path)
+                .and_then(|prefix| {
+                    matched
+                        .as_str()
+                        .strip_prefix(prefix.as_str())
+                        .filter(|rest| rest.starts_with('/'))
+                })
+                .unwrap_or(matched.as_str());
+            ROUTE_CLASSES
+                .get(&(request.method().as_str(), path))
+                .copied()
+        })
+}
+
+/// Reject requests whose route class is over its limit with a 429.
+/// Unmatched requests (404s) pass through uncounted.
+pub async fn enforce_rate_limits(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let class = route_class(&state, &request);
+
+    if let Some(class) = class {
+        if !state.rate_limits.check(&*state.kv, class).await {
+            let mut response = AppError::http(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("rate limit exceeded for class {class}"),
+            )
+            .into_response();
+            // Windows are fixed epoch-minute buckets, so the budget
+            // refills at the next minute boundary.
+            let seconds_left = 60
+                - std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    % 60;
+            response.headers_mut().insert(
+                axum::http::header::RETRY_AFTER,
+                axum::http::HeaderValue::from(seconds_left),
+            );
+            return response;
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classes, LimitSource, RateLimits};
+    use crate::config::Config;
+    use crate::kv::MemoryKvStore;
+
+    #[tokio::test]
+    async fn requests_over_the_class_limit_are_denied() {
+        let mut config = Config::for_tests();
+        config.rate_limit_expensive = 2;
+        let limits = RateLimits::from_config(&config);
+        let kv = MemoryKvStore::default();
+
+        assert!(limits.check(&kv, classes::EXPENSIVE).await);
+        assert!(limits.check(&kv, classes::EXPENSIVE).await);
+        assert!(!limits.check(&kv, classes::EXPENSIVE).await);
+        // Other classes keep their own budgets.
+        assert!(limits.check(&kv, classes::PUBLIC_READ).await);
+    }
+
+    #[tokio::test]
+    async fn counters_are_shared_through_the_store() {
+        let mut config = Config::for_tests();
+        config.rate_limit_expensive = 2;
+        let kv = MemoryKvStore::default();
+        // Two replicas (two limiter instances) draining one shared
+        // backend consume a single budget.
+        let first = RateLimits::from_config(&config);
+        let second = RateLimits::from_config(&config);
+
+        assert!(first.check(&kv, classes::EXPENSIVE).await);
+        assert!(second.check(&kv, classes::EXPENSIVE).await);
+        assert!(!first.check(&kv, classes::EXPENSIVE).await);
+        assert!(!second.check(&kv, classes::EXPENSIVE).await);
+    }
+
+    #[tokio::test]
+    async fn runtime_overrides_take_effect_without_rebuilding() {
+        let mut config = Config::for_tests();
+        config.rate_limit_public_write = 1;
+        let limits = RateLimits::from_config(&config);
+        let kv = MemoryKvStore::default();
+
+        assert!(limits.check(&kv, classes::PUBLIC_WRITE).await);
+        assert!(!limits.check(&kv, classes::PUBLIC_WRITE).await);
+
+        limits.override_class(classes::PUBLIC_WRITE, 10).unwrap();
+        assert!(limits.check(&kv, classes::PUBLIC_WRITE).await);
+        assert_eq!(
+            limits.current()[classes::PUBLIC_WRITE].source,
+            LimitSource::Runtime
+        );
+    }
+
+    #[test]
+    fn overrides_are_validated() {
+        let limits = RateLimits::from_config(&Config::for_tests());
+
+        limits
+            .override_class(classes::PUBLIC_READ, 0)
+            .expect_err("zero is below the minimum");
+        limits
+            .override_class(classes::PUBLIC_READ, super::MAX_LIMIT + 1)
+            .expect_err("above the maximum");
+        limits
+            .override_class("no_such_class", 10)
+            .expect_err("unknown classes are rejected");
+    }
+}
@@ -0,0 +1,163 @@
+//! Proactive rejections for write requests on a read-only service.
+//!
+//! When startup detects a read-only connection (and migrations are
+//! disabled, so startup proceeds degraded), every mutating request is
+//! answered with `503` and the `READ_ONLY` code up front, instead of each
+//! write failing deep in Postgres with a masked 500.
+//!
+//! Under `READ_ONLY_MODE` — an operator's deliberate choice for a
+//! disaster-recovery drill, not an outage — the same body goes out with
+//! `405`: mutating methods are not allowed on this deployment, and
+//! "retry elsewhere" advice would be wrong.
+
+use axum::extract::{Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::AppError;
+use crate::AppState;
+
+/// Reject mutating methods while the database is read-only.
+pub async fn reject_writes_when_read_only(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if (state.read_only || state.config.read_only_mode)
+        && matches!(
+            *req.method(),
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        )
+    {
+        let mut response = AppError::ReadOnly.into_response();
+        if state.config.read_only_mode {
+            *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        }
+        return response;
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn writes_get_503_with_read_only_code_while_reads_pass() {
+        let mut state = test_state();
+        state.read_only = true;
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name": "X", "email": "x@example.com"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"], "READ_ONLY", "body: {body}");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_gets_405_with_read_only_code_while_reads_pass() {
+        let mut state = test_state();
+        state.config.read_only_mode = true;
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name": "X", "email": "x@example.com"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"], "READ_ONLY", "body: {body}");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn readiness_payload_reports_read_only() {
+        let mut state = test_state();
+        state.read_only = true;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health/ready")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["read_only"], true, "body: {body}");
+        // Degraded, not down: the probe keeps its 200 so read traffic
+        // keeps flowing through a primary failover.
+        assert_eq!(body["status"], "degraded (read-only)", "body: {body}");
+
+        // The operator-forced mode advertises the same way.
+        let mut state = test_state();
+        state.config.read_only_mode = true;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health/ready")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["read_only"], true, "body: {body}");
+    }
+}
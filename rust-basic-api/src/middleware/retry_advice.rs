@@ -0,0 +1,178 @@
+//! Retry advice on transient rejections.
+//!
+//! When the server sheds load (503 from the concurrency ceiling or a
+//! read-only database, 429 from the rate limiter, 504 from a timed-out
+//! upstream), clients need to know whether resubmitting is safe. This
+//! middleware annotates those JSON error bodies with `retryable` —
+//! decided by [`crate::error::retry_advice`] from the method and the
+//! presence of an `Idempotency-Key` header — and `retry_after_ms`
+//! mirroring the `Retry-After` header when one is set. Centralizing the
+//! annotation here means every producer of these statuses gives the
+//! same advice without carrying request context into its error path.
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+/// Upper bound when buffering an error body for annotation; our error
+/// bodies are a few hundred bytes, so this is pure defense.
+const ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Add `retryable` (and `retry_after_ms`, when `Retry-After` is set) to
+/// 429/503/504 JSON bodies; everything else passes through untouched.
+pub async fn annotate_retry_advice(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let has_idempotency_key = request.headers().contains_key("idempotency-key");
+    let response = next.run(request).await;
+
+    let Some(retryable) =
+        crate::error::retry_advice(&method, has_idempotency_key, response.status())
+    else {
+        return response;
+    };
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, ERROR_BODY_LIMIT).await else {
+        return parts.status.into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        // A non-JSON body (e.g. from a layer outside the error renderer)
+        // is passed along unchanged.
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if let Some(object) = value.as_object_mut() {
+        object.insert("retryable".to_string(), retryable.into());
+        if let Some(ms) = retry_after_ms(&parts.headers) {
+            object.insert("retry_after_ms".to_string(), ms.into());
+        }
+    }
+    let bytes = serde_json::to_vec(&value).unwrap_or_else(|_| bytes.to_vec());
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// The `Retry-After` header in milliseconds; only the delta-seconds form
+/// is produced by this server, so the HTTP-date form is not parsed.
+fn retry_after_ms(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|seconds| seconds * 1_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::config::Config;
+    use crate::test_helpers::{test_app, test_state};
+
+    async fn body_json(response: axum::response::Response) -> serde_json::Value {
+        use http_body_util::BodyExt;
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn shed_requests_report_retryability_by_method_and_key() {
+        // Exhaust the concurrency ceiling so limited routes are shed.
+        let mut state = test_state();
+        state.config.route_concurrency_limit = 1;
+        state.route_concurrency = Arc::new(crate::middleware::RouteConcurrency::from_config(
+            &state.config,
+        ));
+        let _held = state.route_concurrency.try_hold().unwrap();
+        let app = test_app(state);
+
+        // An idempotent read is always safe to retry.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_json(response).await["retryable"], true);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_posts_need_an_idempotency_key_to_be_retryable() {
+        let mut config = Config::for_tests();
+        config.rate_limit_public_write = 0;
+        let mut state = test_state();
+        state.rate_limits = Arc::new(crate::middleware::RateLimits::from_config(&config));
+        let app = test_app(state);
+
+        let request = |key: bool| {
+            let builder = Request::builder()
+                .method("POST")
+                .uri("/users")
+                .header("content-type", "application/json");
+            let builder = if key {
+                builder.header("idempotency-key", "3f6d")
+            } else {
+                builder
+            };
+            builder
+                .body(Body::from(r#"{"name":"Shed","email":"shed@example.com"}"#))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request(false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = body_json(response).await;
+        assert_eq!(body["retryable"], false);
+        // The 429 carries Retry-After; the body mirrors it in ms.
+        let ms = body["retry_after_ms"].as_u64().unwrap();
+        assert!(ms > 0 && ms <= 60_000, "retry_after_ms = {ms}");
+
+        let response = app.clone().oneshot(request(true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body_json(response).await["retryable"], true);
+    }
+
+    #[tokio::test]
+    async fn other_responses_are_left_alone() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_json(response).await.get("retryable").is_none());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/999")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body_json(response).await.get("retryable").is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// The header carrying the matched route template.
+pub const ROUTE_HEADER: &str = "x-route";
+
+/// Attach the matched route template (`/users/:id`, not `/users/42`) to
+/// every response as `X-Route`, so clients can aggregate their own
+/// latency metrics by route without inventing high-cardinality path
+/// labels or leaking ids into them. Off unless `EXPOSE_ROUTE_HEADER` is
+/// set; unmatched requests (404s) carry no header either way.
+pub async fn expose_matched_route(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.config.expose_route_header {
+        return next.run(req).await;
+    }
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string());
+
+    let mut response = next.run(req).await;
+    if let Some(route) = route {
+        if let Ok(value) = HeaderValue::from_str(&route) {
+            response.headers_mut().insert(ROUTE_HEADER, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn the_header_carries_the_template_not_the_concrete_path() {
+        let mut state = test_state();
+        state.config.expose_route_header = true;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[super::ROUTE_HEADER], "/users/:id");
+    }
+
+    #[tokio::test]
+    async fn the_header_is_absent_by_default_and_on_unmatched_paths() {
+        let response = test_app(test_state())
+            .oneshot(
+                Request::builder()
+                    .uri("/users/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key(super::ROUTE_HEADER));
+
+        let mut state = test_state();
+        state.config.expose_route_header = true;
+        let response = test_app(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/no-such-route")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key(super::ROUTE_HEADER));
+    }
+}
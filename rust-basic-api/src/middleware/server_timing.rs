@@ -0,0 +1,166 @@
+//! `Server-Timing` response header for latency breakdowns without a
+//! tracing backend.
+//!
+//! Three phases per the spec's millisecond-float format: `total` (the
+//! whole handler stack), `db` (summed repository call durations, fed by
+//! [`crate::repository::TimedUserRepository`] through a task-local
+//! accumulator), and `serialize` (draining the response body). On by
+//! default outside production (`ENVIRONMENT != production`) and
+//! overridable either way with `SERVER_TIMING`.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+tokio::task_local! {
+    /// Repository time accumulated for the current request; only present
+    /// while [`record_server_timing`] is scoping the handler stack.
+    static DB_TIME: Cell<Duration>;
+}
+
+/// Add `elapsed` to the current request's `db` phase. A no-op outside a
+/// timed request scope (background tasks, or server timing disabled), so
+/// callers never need to know whether timing is on.
+pub fn record_db(elapsed: Duration) {
+    let _ = DB_TIME.try_with(|db| db.set(db.get() + elapsed));
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Attach the `Server-Timing` header to every response.
+pub async fn record_server_timing(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.config.server_timing {
+        return next.run(req).await;
+    }
+
+    let started = Instant::now();
+    let (response, db) = DB_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let response = next.run(req).await;
+            (response, DB_TIME.with(Cell::get))
+        })
+        .await;
+
+    // Draining the body forces (and times) any serialization the handler
+    // deferred into the body stream; handlers here buffer their bodies, so
+    // the bytes sent are unchanged.
+    let (mut parts, body) = response.into_parts();
+    let serialize_started = Instant::now();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => Body::from(bytes),
+        Err(error) => {
+            // The stream failed mid-body; the client would have seen a
+            // truncated response either way.
+            tracing::warn!(%error, "response body failed while buffering for Server-Timing");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let serialize = serialize_started.elapsed();
+
+    let header = format!(
+        "total;dur={:.2}, db;dur={:.2}, serialize;dur={:.2}",
+        millis(started.elapsed()),
+        millis(db),
+        millis(serialize)
+    );
+    if let Ok(value) = HeaderValue::from_str(&header) {
+        parts.headers.insert("server-timing", value);
+    }
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    fn create_request() -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/users")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"name": "Timed", "email": "timed@example.com"}"#,
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn user_fetch_reports_phase_durations() {
+        let app = test_app(test_state());
+        let response = app.clone().oneshot(create_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = {
+            use http_body_util::BodyExt;
+            let bytes = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["id"]
+                .as_i64()
+                .unwrap()
+        };
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let header = response
+            .headers()
+            .get("server-timing")
+            .expect("server-timing header present")
+            .to_str()
+            .unwrap()
+            .to_string();
+        for phase in ["total;dur=", "db;dur=", "serialize;dur="] {
+            assert!(header.contains(phase), "header: {header}");
+        }
+        let db: f64 = header
+            .split("db;dur=")
+            .nth(1)
+            .and_then(|rest| rest.split(',').next())
+            .unwrap()
+            .parse()
+            .expect("db phase is a millisecond float");
+        assert!((0.0..1_000.0).contains(&db), "db phase: {db}");
+    }
+
+    #[tokio::test]
+    async fn production_mode_omits_the_header() {
+        let mut state = test_state();
+        state.config.server_timing = false;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("server-timing").is_none());
+    }
+}
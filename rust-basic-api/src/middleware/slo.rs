@@ -0,0 +1,298 @@
+//! Per-route-class error budgets.
+//!
+//! Every completed request is recorded against its route class in rolling
+//! per-minute buckets, and `GET /admin/slo` reports availability, burn
+//! rate, and remaining error budget over 5-minute and 1-hour windows
+//! against the configured target (`SLO_AVAILABILITY`, default 99.9). When
+//! any class burns budget faster than [`FAST_BURN_THRESHOLD`] over the
+//! short window, the readiness payload flips its `degraded` flag so the
+//! condition is visible to the same probes that already watch the
+//! service. The burn-rate math lives in pure functions on
+//! [`WindowStats`] so it can be tested without a clock.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::AppState;
+
+/// How many per-minute buckets are kept: enough for the 1-hour window.
+pub const WINDOW_MINUTES: usize = 60;
+
+/// The short (fast-burn) window, in minutes.
+pub const SHORT_WINDOW_MINUTES: usize = 5;
+
+/// Burn rate over the short window beyond which the service reports
+/// itself degraded. At 14.4x, a full 30-day budget would be gone in about
+/// two days — the conventional fast-burn paging threshold.
+pub const FAST_BURN_THRESHOLD: f64 = 14.4;
+
+/// Request and 5xx counts over one observation window.
+///
+/// The SLO math hangs off this so it stays pure: aggregation happens in
+/// [`SloWindows`], arithmetic here.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct WindowStats {
+    pub requests: u64,
+    pub errors: u64,
+}
+
+impl WindowStats {
+    fn add(&mut self, other: WindowStats) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+    }
+
+    /// Fraction of requests that succeeded; an idle window counts as
+    /// fully available.
+    pub fn availability(&self) -> f64 {
+        if self.requests == 0 {
+            1.0
+        } else {
+            1.0 - self.errors as f64 / self.requests as f64
+        }
+    }
+
+    /// How fast the error budget is being consumed, as a multiple of the
+    /// sustainable rate: 1.0 exactly exhausts the budget over the SLO
+    /// period, below 1.0 is within budget. `target` is a fraction
+    /// (0.999, not 99.9).
+    pub fn burn_rate(&self, target: f64) -> f64 {
+        let budget = 1.0 - target;
+        if budget <= 0.0 {
+            return f64::INFINITY;
+        }
+        (1.0 - self.availability()) / budget
+    }
+
+    /// Fraction of the window's error budget still unspent, clamped to
+    /// zero once overspent.
+    pub fn remaining_budget(&self, target: f64) -> f64 {
+        (1.0 - self.burn_rate(target)).max(0.0)
+    }
+}
+
+/// One minute of per-class counts.
+struct Bucket {
+    minute: i64,
+    counts: HashMap<&'static str, WindowStats>,
+}
+
+/// Rolling per-minute windows of request outcomes per route class,
+/// shared on [`AppState`].
+#[derive(Default)]
+pub struct SloWindows {
+    /// Oldest bucket first; at most [`WINDOW_MINUTES`] entries.
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl SloWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one completed request for the class.
+    pub fn record(&self, class: &'static str, is_server_error: bool) {
+        let minute = Utc::now().timestamp() / 60;
+        let mut buckets = self.buckets.lock().expect("slo window lock poisoned");
+        if buckets.back().map_or(true, |b| b.minute != minute) {
+            buckets.push_back(Bucket {
+                minute,
+                counts: HashMap::new(),
+            });
+            while buckets.len() > WINDOW_MINUTES {
+                buckets.pop_front();
+            }
+        }
+        let stats = buckets
+            .back_mut()
+            .expect("bucket pushed above")
+            .counts
+            .entry(class)
+            .or_default();
+        stats.requests += 1;
+        if is_server_error {
+            stats.errors += 1;
+        }
+    }
+
+    /// Aggregate counts for the class over the last `minutes` buckets.
+    pub fn stats(&self, class: &str, minutes: usize) -> WindowStats {
+        let since_minute = Utc::now().timestamp() / 60 - minutes as i64 + 1;
+        let buckets = self.buckets.lock().expect("slo window lock poisoned");
+        let mut total = WindowStats::default();
+        for bucket in buckets.iter().filter(|b| b.minute >= since_minute) {
+            if let Some(stats) = bucket.counts.get(class) {
+                total.add(*stats);
+            }
+        }
+        total
+    }
+}
+
+/// One class's row in the `GET /admin/slo` report.
+#[derive(Debug, Serialize)]
+pub struct ClassSlo {
+    pub class: &'static str,
+    pub short_window: WindowStats,
+    pub long_window: WindowStats,
+    pub availability_5m: f64,
+    pub availability_1h: f64,
+    pub burn_rate_5m: f64,
+    pub burn_rate_1h: f64,
+    pub remaining_budget_1h: f64,
+}
+
+/// Response body for `GET /admin/slo`.
+#[derive(Debug, Serialize)]
+pub struct SloReport {
+    /// The availability target as configured (percent, e.g. `99.9`).
+    pub target: f64,
+    /// True when any class's short-window burn rate exceeds
+    /// [`FAST_BURN_THRESHOLD`]; mirrored into the readiness payload.
+    pub degraded: bool,
+    pub classes: Vec<ClassSlo>,
+}
+
+/// Build the report for every route class against the target from
+/// `SLO_AVAILABILITY` (a percentage).
+pub fn report(windows: &SloWindows, target_percent: f64) -> SloReport {
+    let target = target_percent / 100.0;
+    let classes: Vec<ClassSlo> = [
+        super::rate_limit::classes::PUBLIC_READ,
+        super::rate_limit::classes::PUBLIC_WRITE,
+        super::rate_limit::classes::EXPENSIVE,
+    ]
+    .into_iter()
+    .map(|class| {
+        let short = windows.stats(class, SHORT_WINDOW_MINUTES);
+        let long = windows.stats(class, WINDOW_MINUTES);
+        ClassSlo {
+            class,
+            availability_5m: short.availability(),
+            availability_1h: long.availability(),
+            burn_rate_5m: short.burn_rate(target),
+            burn_rate_1h: long.burn_rate(target),
+            remaining_budget_1h: long.remaining_budget(target),
+            short_window: short,
+            long_window: long,
+        }
+    })
+    .collect();
+    SloReport {
+        target: target_percent,
+        degraded: classes
+            .iter()
+            .any(|class| class.burn_rate_5m > FAST_BURN_THRESHOLD),
+        classes,
+    }
+}
+
+/// Whether the fast-burn threshold is currently exceeded for any class.
+pub fn fast_burn_exceeded(windows: &SloWindows, target_percent: f64) -> bool {
+    report(windows, target_percent).degraded
+}
+
+/// Record every matched request's outcome (5xx = error) against its
+/// route class. Unmatched requests (404s) have no class and pass
+/// uncounted, same as rate limiting.
+pub async fn track_slo_outcomes(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let class = super::rate_limit::route_class(&state, &request);
+    let response = next.run(request).await;
+    if let Some(class) = class {
+        state.slo.record(class, response.status().is_server_error());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::rate_limit::classes;
+
+    #[test]
+    fn burn_rate_math_matches_hand_computation() {
+        // 1 error in 100 requests at a 99.9% target: the error rate is
+        // 0.01 against a 0.001 budget — burning 10x.
+        let stats = WindowStats {
+            requests: 100,
+            errors: 1,
+        };
+        assert!((stats.availability() - 0.99).abs() < 1e-9);
+        assert!((stats.burn_rate(0.999) - 10.0).abs() < 1e-9);
+        assert_eq!(stats.remaining_budget(0.999), 0.0);
+
+        // Exactly on budget burns at 1x with nothing to spare.
+        let on_budget = WindowStats {
+            requests: 1000,
+            errors: 1,
+        };
+        assert!((on_budget.burn_rate(0.999) - 1.0).abs() < 1e-9);
+
+        // An idle window is fully available and burns nothing.
+        let idle = WindowStats::default();
+        assert_eq!(idle.availability(), 1.0);
+        assert_eq!(idle.burn_rate(0.999), 0.0);
+        assert!((idle.remaining_budget(0.999) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn synthetic_outcomes_drive_the_degraded_transition() {
+        let windows = SloWindows::new();
+        for _ in 0..99 {
+            windows.record(classes::PUBLIC_READ, false);
+        }
+        assert!(!fast_burn_exceeded(&windows, 99.9), "healthy traffic");
+
+        // Two failures out of ~100 requests is a ~20x burn at 99.9%,
+        // past the 14.4x fast-burn threshold.
+        windows.record(classes::PUBLIC_READ, true);
+        windows.record(classes::PUBLIC_READ, true);
+        assert!(fast_burn_exceeded(&windows, 99.9));
+
+        // Other classes keep their own budgets.
+        let report = report(&windows, 99.9);
+        let row = |class: &str| {
+            report
+                .classes
+                .iter()
+                .find(|c| c.class == class)
+                .expect("every class is reported")
+        };
+        assert!(row(classes::PUBLIC_READ).burn_rate_5m > FAST_BURN_THRESHOLD);
+        assert_eq!(row(classes::PUBLIC_WRITE).burn_rate_5m, 0.0);
+        assert_eq!(row(classes::EXPENSIVE).short_window, WindowStats::default());
+    }
+
+    #[test]
+    fn windows_aggregate_requests_and_errors_per_class() {
+        let windows = SloWindows::new();
+        windows.record(classes::PUBLIC_WRITE, false);
+        windows.record(classes::PUBLIC_WRITE, true);
+        windows.record(classes::EXPENSIVE, false);
+
+        let short = windows.stats(classes::PUBLIC_WRITE, SHORT_WINDOW_MINUTES);
+        assert_eq!(
+            short,
+            WindowStats {
+                requests: 2,
+                errors: 1,
+            }
+        );
+        // Everything just recorded is inside the long window too.
+        assert_eq!(short, windows.stats(classes::PUBLIC_WRITE, WINDOW_MINUTES));
+        assert_eq!(
+            windows.stats(classes::EXPENSIVE, WINDOW_MINUTES).requests,
+            1
+        );
+    }
+}
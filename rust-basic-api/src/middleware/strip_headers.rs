@@ -0,0 +1,61 @@
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// Remove configured sensitive headers from every response before it leaves
+/// the service.
+///
+/// The denylist comes from the comma-separated `STRIP_RESPONSE_HEADERS`
+/// environment variable; an empty list makes this a no-op.
+pub async fn strip_response_headers(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    for name in &state.config.strip_response_headers {
+        response.headers_mut().remove(name.as_str());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    use crate::test_helpers::test_state;
+
+    #[tokio::test]
+    async fn strips_only_denylisted_headers() {
+        let mut state = test_state();
+        state.config.strip_response_headers =
+            vec!["x-internal-debug".to_string(), "server".to_string()];
+
+        let app = Router::new()
+            .route(
+                "/test",
+                get(|| async {
+                    (
+                        [("x-internal-debug", "pool=7"), ("x-request-id", "abc")],
+                        "ok",
+                    )
+                }),
+            )
+            .layer(from_fn_with_state(state, super::strip_response_headers));
+
+        let response = app
+            .oneshot(Request::builder().uri("/test").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert!(!response.headers().contains_key("x-internal-debug"));
+        assert_eq!(response.headers()["x-request-id"], "abc");
+    }
+}
@@ -0,0 +1,220 @@
+//! Multi-tenant request routing.
+//!
+//! When `TENANTS` lists one or more tenant ids, every data-bearing request
+//! must carry an `X-Tenant-Id` header naming one of them. The middleware
+//! resolves the header into a [`TenantContext`] stored in request
+//! extensions, which handlers read through the [`Tenant`] extractor to pick
+//! the tenant's repository. Unknown tenants get a 404 (so ids cannot be
+//! probed apart from missing routes) and a missing header gets a 400.
+//! Public probe endpoints stay tenant-free, and with `TENANTS` unset the
+//! whole mechanism is inert.
+
+use axum::extract::{FromRequestParts, MatchedPath, Request, State};
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::AppError;
+use crate::AppState;
+
+/// Header carrying the caller's tenant id.
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+/// The resolved tenant for the current request.
+#[derive(Debug, Clone)]
+pub struct TenantContext {
+    pub id: String,
+}
+
+/// Extractor handing handlers the resolved tenant; `None` when
+/// multi-tenancy is disabled.
+pub struct Tenant(pub Option<TenantContext>);
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Tenant {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(parts.extensions.get::<TenantContext>().cloned()))
+    }
+}
+
+/// Routes that never require a tenant: liveness and metrics scraping are
+/// infrastructure traffic, not tenant data access.
+fn tenant_exempt(matched_path: &str) -> bool {
+    matched_path.ends_with("/health")
+        || matched_path.ends_with("/health/ready")
+        || matched_path.ends_with("/metrics")
+}
+
+/// Resolve the `X-Tenant-Id` header into a [`TenantContext`] when
+/// multi-tenancy is enabled.
+pub async fn resolve_tenant(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if state.config.tenants.is_empty() {
+        return next.run(req).await;
+    }
+
+    let matched = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |m| m.as_str().to_string());
+    if tenant_exempt(&matched) {
+        return next.run(req).await;
+    }
+
+    let Some(id) = req
+        .headers()
+        .get(TENANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    else {
+        return AppError::Validation(format!("the {TENANT_HEADER} header is required"))
+            .into_response();
+    };
+    if !state.config.tenants.contains(&id) {
+        return AppError::NotFound.into_response();
+    }
+
+    req.extensions_mut().insert(TenantContext { id });
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use crate::repository::{MemoryUserRepository, UserRepository};
+    use crate::test_helpers::{test_app, test_state};
+
+    fn tenant_state() -> crate::AppState {
+        let mut state = test_state();
+        state.config.tenants = vec!["acme".to_string(), "globex".to_string()];
+        let mut repositories: HashMap<String, Arc<dyn UserRepository>> = HashMap::new();
+        for tenant in &state.config.tenants {
+            repositories.insert(tenant.clone(), Arc::new(MemoryUserRepository::new()));
+        }
+        state.tenant_repositories = Arc::new(repositories);
+        state
+    }
+
+    fn create_request(tenant: Option<&str>, email: &str) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri("/users")
+            .header("content-type", "application/json");
+        if let Some(tenant) = tenant {
+            builder = builder.header("x-tenant-id", tenant);
+        }
+        builder
+            .body(Body::from(format!(
+                r#"{{"name":"Tenant Test","email":"{email}"}}"#
+            )))
+            .unwrap()
+    }
+
+    async fn list_emails(app: &axum::Router, tenant: &str) -> Vec<String> {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .header("x-tenant-id", tenant)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        body["users"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| u["email"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn tenants_are_fully_isolated() {
+        let app = test_app(tenant_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request(Some("acme"), "a@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = app
+            .clone()
+            .oneshot(create_request(Some("globex"), "g@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        assert_eq!(list_emails(&app, "acme").await, vec!["a@example.com"]);
+        assert_eq!(list_emails(&app, "globex").await, vec!["g@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn missing_header_is_400_and_unknown_tenant_404() {
+        let app = test_app(tenant_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request(None, "x@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(create_request(Some("initech"), "x@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Probe endpoints stay tenant-free.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn disabled_multi_tenancy_ignores_the_header() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request(None, "x@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // A stray header on a single-tenant deployment is harmless.
+        let response = app
+            .oneshot(create_request(Some("acme"), "y@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
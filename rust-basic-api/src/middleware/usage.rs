@@ -0,0 +1,299 @@
+//! Per-caller request accounting.
+//!
+//! Every request is attributed to a caller identity (JWT `sub`, or
+//! `anonymous`) and recorded both in the `http_requests_by_caller_total`
+//! metric and in an in-memory rolling window of per-minute buckets served
+//! by `GET /admin/usage`. The set of distinct caller labels is bounded by
+//! `USAGE_MAX_CALLERS`; callers past the cap fold into an `other` bucket so
+//! a key-guessing client cannot explode metric cardinality.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::metrics;
+use crate::AppState;
+
+/// How many per-minute buckets the usage window keeps.
+pub const WINDOW_MINUTES: usize = 60;
+
+/// Label applied to unauthenticated traffic.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Overflow label for callers beyond the distinct-label cap.
+pub const OTHER: &str = "other";
+
+/// Record the request against the caller's usage bucket and the per-caller
+/// request metric. Responses with a 4xx or 5xx status count as errors.
+pub async fn track_caller_usage(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let caller =
+        crate::auth::caller_identity(req.headers(), state.config.auth_jwt_secret.as_deref());
+    let response = next.run(req).await;
+
+    let status = response.status();
+    let label = state.usage.record(
+        &caller,
+        status.is_client_error() || status.is_server_error(),
+    );
+    let class = match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    };
+    metrics::REQUESTS_BY_CALLER
+        .with_label_values(&[&label, class])
+        .inc();
+
+    response
+}
+
+/// Request and error counts for one caller.
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    requests: u64,
+    errors: u64,
+}
+
+/// One minute of per-caller counts.
+struct Bucket {
+    minute: i64,
+    counts: HashMap<String, Counts>,
+}
+
+#[derive(Default)]
+struct WindowInner {
+    /// Oldest bucket first; at most [`WINDOW_MINUTES`] entries.
+    buckets: VecDeque<Bucket>,
+    /// Callers granted their own label; later arrivals fold into `other`.
+    callers: HashSet<String>,
+}
+
+/// Rolling window of per-minute usage buckets, shared on [`AppState`].
+pub struct UsageWindow {
+    minutes: usize,
+    max_callers: usize,
+    inner: Mutex<WindowInner>,
+}
+
+impl UsageWindow {
+    pub fn new(minutes: usize, max_callers: usize) -> Self {
+        Self {
+            minutes,
+            max_callers,
+            inner: Mutex::new(WindowInner::default()),
+        }
+    }
+
+    /// Record one request for the caller, returning the bounded label it
+    /// was accounted under (the caller itself, or `other` past the cap).
+    pub fn record(&self, caller: &str, is_error: bool) -> String {
+        let minute = Utc::now().timestamp() / 60;
+        let mut inner = self.inner.lock().expect("usage window lock poisoned");
+
+        let label = if caller == ANONYMOUS || inner.callers.contains(caller) {
+            caller.to_string()
+        } else if inner.callers.len() < self.max_callers {
+            inner.callers.insert(caller.to_string());
+            caller.to_string()
+        } else {
+            OTHER.to_string()
+        };
+
+        if inner.buckets.back().map_or(true, |b| b.minute != minute) {
+            inner.buckets.push_back(Bucket {
+                minute,
+                counts: HashMap::new(),
+            });
+            while inner.buckets.len() > self.minutes {
+                inner.buckets.pop_front();
+            }
+        }
+        let counts = inner
+            .buckets
+            .back_mut()
+            .expect("bucket pushed above")
+            .counts
+            .entry(label.clone())
+            .or_default();
+        counts.requests += 1;
+        if is_error {
+            counts.errors += 1;
+        }
+
+        label
+    }
+
+    /// Per-caller totals over buckets at or after `since`.
+    pub fn summary(&self, since: DateTime<Utc>) -> Vec<CallerUsage> {
+        let since_minute = since.timestamp() / 60;
+        let inner = self.inner.lock().expect("usage window lock poisoned");
+
+        let mut totals: HashMap<String, Counts> = HashMap::new();
+        for bucket in inner.buckets.iter().filter(|b| b.minute >= since_minute) {
+            for (caller, counts) in &bucket.counts {
+                let total = totals.entry(caller.clone()).or_default();
+                total.requests += counts.requests;
+                total.errors += counts.errors;
+            }
+        }
+
+        let mut summary: Vec<CallerUsage> = totals
+            .into_iter()
+            .map(|(caller, counts)| CallerUsage {
+                caller,
+                requests: counts.requests,
+                errors: counts.errors,
+                error_rate: if counts.requests == 0 {
+                    0.0
+                } else {
+                    counts.errors as f64 / counts.requests as f64
+                },
+            })
+            .collect();
+        summary.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.caller.cmp(&b.caller)));
+        summary
+    }
+}
+
+/// Per-caller summary row returned by `GET /admin/usage`.
+#[derive(Debug, Serialize)]
+pub struct CallerUsage {
+    pub caller: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use jsonwebtoken::{EncodingKey, Header};
+    use tower::ServiceExt;
+
+    use crate::metrics;
+    use crate::test_helpers::{test_app, test_state};
+
+    const SECRET: &str = "usage-secret";
+
+    fn mint(sub: &str) -> String {
+        jsonwebtoken::encode(
+            &Header::default(),
+            &serde_json::json!({ "sub": sub, "scope": "admin" }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn drive(app: &axum::Router, uri: &str, token: &str) {
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn usage_summary_counts_requests_and_errors_per_caller() {
+        let mut state = test_state();
+        state.config.auth_jwt_secret = Some(SECRET.to_string());
+        let app = test_app(state.clone());
+        let (alice, bob) = (mint("usage-alice"), mint("usage-bob"));
+
+        drive(&app, "/users", &alice).await;
+        drive(&app, "/users", &alice).await;
+        drive(&app, "/users/999", &alice).await; // 404 counts as an error
+        drive(&app, "/users", &bob).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/usage")
+                    .header("authorization", format!("Bearer {alice}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let summary: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let row = |caller: &str| {
+            summary
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|r| r["caller"] == caller)
+                .unwrap_or_else(|| panic!("no row for {caller}: {summary}"))
+                .clone()
+        };
+        // The summary request itself has not been recorded yet when the
+        // handler reads the window, so alice has exactly three rows here.
+        assert_eq!(row("usage-alice")["requests"], 3);
+        assert_eq!(row("usage-alice")["errors"], 1);
+        assert!((row("usage-alice")["error_rate"].as_f64().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(row("usage-bob")["requests"], 1);
+        assert_eq!(row("usage-bob")["errors"], 0);
+
+        assert!(
+            metrics::REQUESTS_BY_CALLER
+                .with_label_values(&["usage-alice", "2xx"])
+                .get()
+                >= 2
+        );
+        assert!(
+            metrics::REQUESTS_BY_CALLER
+                .with_label_values(&["usage-alice", "4xx"])
+                .get()
+                >= 1
+        );
+        assert!(
+            metrics::REQUESTS_BY_CALLER
+                .with_label_values(&["usage-bob", "2xx"])
+                .get()
+                >= 1
+        );
+    }
+
+    #[test]
+    fn callers_past_the_cap_fold_into_other() {
+        let window = super::UsageWindow::new(super::WINDOW_MINUTES, 2);
+
+        assert_eq!(window.record("a", false), "a");
+        assert_eq!(window.record("b", true), "b");
+        assert_eq!(window.record("c", false), "other");
+        // Already-admitted callers keep their label; anonymous is never
+        // counted against the cap.
+        assert_eq!(window.record("a", false), "a");
+        assert_eq!(window.record("anonymous", false), "anonymous");
+
+        let summary = window.summary(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+        let row = |caller: &str| {
+            summary
+                .iter()
+                .find(|r| r.caller == caller)
+                .unwrap_or_else(|| panic!("no row for {caller}"))
+        };
+        assert_eq!(row("a").requests, 2);
+        assert_eq!(row("b").errors, 1);
+        assert_eq!(row("other").requests, 1);
+        assert_eq!(row("anonymous").requests, 1);
+    }
+}
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// An `audit_log` row recording an action taken against a user.
+#[derive(Debug, Serialize, Clone, PartialEq, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub action: String,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A `user_audit` row written by the trigger on `users`, carrying full
+/// before/after row images for compliance review.
+#[derive(Debug, Serialize, Clone, PartialEq, sqlx::FromRow)]
+pub struct UserAuditEntry {
+    pub id: i32,
+    pub user_id: i32,
+    /// `insert`, `update`, or `delete` (the trigger operation).
+    pub action: String,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub changed_at: DateTime<Utc>,
+    /// The row before the change; `None` for inserts.
+    pub old_row: Option<serde_json::Value>,
+    /// The row after the change; `None` for deletes.
+    pub new_row: Option<serde_json::Value>,
+}
@@ -0,0 +1,79 @@
+use crate::error::{AppError, Result};
+
+/// An avatar blob as stored in the `user_avatars` table, addressed by the
+/// SHA-256 of its bytes.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Avatar {
+    pub hash: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Image formats accepted for avatars, validated by magic bytes rather
+/// than the Content-Type header alone.
+const FORMATS: &[(&str, &[u8])] = &[
+    ("image/png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", b"\xff\xd8\xff"),
+];
+
+/// Verify the declared content type is supported and matches the payload's
+/// magic bytes, returning the canonical content type.
+pub fn validate_image(content_type: &str, bytes: &[u8]) -> Result<&'static str> {
+    let declared = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((canonical, magic)) = FORMATS.iter().find(|(name, _)| *name == declared) else {
+        return Err(AppError::Validation(format!(
+            "unsupported avatar content type {declared:?}; expected image/png or image/jpeg"
+        )));
+    };
+    if !bytes.starts_with(magic) {
+        return Err(AppError::Validation(format!(
+            "avatar payload does not look like {canonical}"
+        )));
+    }
+    Ok(canonical)
+}
+
+/// Lowercase hex SHA-256 of the avatar bytes, the blob's storage key and
+/// its ETag.
+pub fn content_hash(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    /// Smallest valid-enough PNG header for tests.
+    pub(crate) const PNG: &[u8] = b"\x89PNG\r\n\x1a\n-test-image-data";
+
+    #[test]
+    fn magic_bytes_must_match_the_declared_type() {
+        assert_eq!(
+            super::validate_image("image/png", PNG).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            super::validate_image("image/jpeg", b"\xff\xd8\xff\xe0rest").unwrap(),
+            "image/jpeg"
+        );
+
+        // A PNG payload labelled as JPEG (or vice versa) is rejected.
+        super::validate_image("image/jpeg", PNG).expect_err("mislabeled payload");
+        super::validate_image("image/png", b"GIF89a").expect_err("wrong magic");
+        super::validate_image("image/gif", b"GIF89a").expect_err("unsupported type");
+    }
+
+    #[test]
+    fn hash_is_lowercase_hex_sha256() {
+        // SHA-256 of the empty input is a well-known vector.
+        assert_eq!(
+            super::content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
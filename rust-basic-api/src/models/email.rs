@@ -0,0 +1,161 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+/// A structurally valid email address, normalized to lowercase.
+///
+/// Construction is the only place the structural check runs —
+/// `TryFrom<String>`, `FromStr`, and serde deserialization all go through
+/// it — so once a value of this type exists the address is known valid
+/// and already normalized, and code past the request boundary never
+/// re-validates. The check matches what the SQL layer relies on: at most
+/// 255 bytes, a non-empty local part, and a domain containing a dot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct EmailAddress(String);
+
+impl EmailAddress {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+/// Lowercase and trim a raw email before validation and persistence, so
+/// the unique constraint treats `A@x.com` and `a@x.com` as one address.
+/// The write handlers apply this when `NORMALIZE_EMAILS` is enabled
+/// (the default); with it disabled the raw input is stored as received.
+pub fn normalize(raw: &str) -> String {
+    raw.trim().to_lowercase()
+}
+
+impl TryFrom<String> for EmailAddress {
+    type Error = AppError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let valid = value.len() <= 255
+            && value
+                .split_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+        if !valid {
+            return Err(AppError::ValidationField {
+                field: "email",
+                code: crate::i18n::keys::EMAIL_INVALID,
+            });
+        }
+        Ok(Self(value.to_lowercase()))
+    }
+}
+
+impl FromStr for EmailAddress {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s.to_string())
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for EmailAddress {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for EmailAddress {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for EmailAddress {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for EmailAddress {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+/// Decoding runs the same structural check as parsing, so a corrupt row
+/// surfaces as a decode error instead of leaking an invalid address into
+/// the application.
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for EmailAddress {
+    fn decode(
+        value: sqlx::postgres::PgValueRef<'r>,
+    ) -> std::result::Result<Self, sqlx::error::BoxDynError> {
+        let raw = <String as sqlx::Decode<'r, sqlx::Postgres>>::decode(value)?;
+        Ok(Self::try_from(raw)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailAddress;
+
+    #[test]
+    fn valid_addresses_parse() {
+        for input in [
+            "user@example.com",
+            "first.last+tag@example.co.uk",
+            "x@sub.domain.example",
+        ] {
+            let email: EmailAddress = input.parse().unwrap();
+            assert_eq!(email.as_str(), input);
+        }
+    }
+
+    #[test]
+    fn parsing_normalizes_to_lowercase() {
+        let email: EmailAddress = "First.Last@Example.COM".parse().unwrap();
+        assert_eq!(email.as_str(), "first.last@example.com");
+        assert_eq!(email.to_string(), "first.last@example.com");
+        assert_eq!(
+            serde_json::to_string(&email).unwrap(),
+            r#""first.last@example.com""#
+        );
+    }
+
+    #[test]
+    fn normalize_trims_and_lowercases() {
+        assert_eq!(
+            super::normalize("  First.Last@Example.COM  "),
+            "first.last@example.com"
+        );
+        assert_eq!(super::normalize("plain@example.com"), "plain@example.com");
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        for input in [
+            "",
+            "plainaddress",
+            "@example.com",
+            "user@nodot",
+            &format!("{}@example.com", "a".repeat(250)),
+        ] {
+            assert!(input.parse::<EmailAddress>().is_err(), "input: {input}");
+        }
+    }
+
+    #[test]
+    fn deserialization_runs_the_same_validation() {
+        let email: EmailAddress = serde_json::from_str(r#""User@Example.com""#).unwrap();
+        assert_eq!(email.as_str(), "user@example.com");
+        assert!(serde_json::from_str::<EmailAddress>(r#""not-an-email""#).is_err());
+    }
+}
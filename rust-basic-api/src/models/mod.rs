@@ -0,0 +1,48 @@
+pub mod audit;
+pub mod avatar;
+pub mod email;
+pub mod serde_rfc3339;
+pub mod tag;
+pub mod user;
+
+pub use audit::{AuditEntry, UserAuditEntry};
+pub use avatar::Avatar;
+pub use email::EmailAddress;
+pub use tag::SetUserTagsRequest;
+pub use user::{
+    CreateUserRequest, ImportRowError, ImportUserRecord, UpdateUserRequest, User, UserStats,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{AppError, Result};
+
+/// Deserialize a request body according to the configured unknown-fields
+/// policy.
+///
+/// In lenient mode (the default) unknown fields are ignored, matching
+/// serde's behavior. In strict mode (`STRICT_JSON_FIELDS=true`) a payload
+/// containing a field outside `known_fields` is rejected with a 400 naming
+/// the offending field, which catches client-side typos early.
+pub fn from_json_value<T: DeserializeOwned>(
+    value: serde_json::Value,
+    strict: bool,
+    known_fields: &[&str],
+) -> Result<T> {
+    if strict {
+        if let Some(object) = value.as_object() {
+            if let Some(unknown) = object
+                .keys()
+                .find(|key| !known_fields.contains(&key.as_str()))
+            {
+                return Err(AppError::Validation(format!(
+                    "unknown field `{unknown}`, expected one of: {}",
+                    known_fields.join(", ")
+                )));
+            }
+        }
+    }
+
+    serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("invalid request body: {e}")))
+}
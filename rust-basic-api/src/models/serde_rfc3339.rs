@@ -0,0 +1,89 @@
+//! Shared `#[serde(with = ...)]` module giving every timestamp field the
+//! same wire format: RFC 3339 with millisecond precision and a `Z` suffix
+//! (e.g. `2024-01-02T03:04:05.678Z`).
+//!
+//! Deserialization tolerates any RFC 3339 offset and normalizes to UTC.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&dt.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(serde::de::Error::custom)
+}
+
+/// Companion for `Option<DateTime<Utc>>` fields: the same wire format,
+/// with `None` as JSON `null`.
+pub mod option {
+    use super::{DateTime, Deserialize, Deserializer, SecondsFormat, Serializer, Utc};
+
+    pub fn serialize<S: Serializer>(
+        dt: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match dt {
+            Some(dt) => serializer.serialize_str(&dt.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|raw| {
+                DateTime::parse_from_rfc3339(&raw)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "super")]
+        at: DateTime<Utc>,
+    }
+
+    #[test]
+    fn serializes_with_millis_and_z_suffix() {
+        let wrapper = Wrapper {
+            at: Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+        };
+        assert_eq!(
+            serde_json::to_string(&wrapper).unwrap(),
+            r#"{"at":"2024-01-02T03:04:05.000Z"}"#
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let wrapper = Wrapper {
+            at: Utc.timestamp_millis_opt(1_700_000_000_123).unwrap(),
+        };
+        let json = serde_json::to_string(&wrapper).unwrap();
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap(), wrapper);
+    }
+
+    #[test]
+    fn accepts_offsets_and_normalizes_to_utc() {
+        let wrapper: Wrapper =
+            serde_json::from_str(r#"{"at":"2024-01-02T05:04:05.000+02:00"}"#).unwrap();
+        assert_eq!(
+            wrapper.at,
+            Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use serde::Deserialize;
+
+use crate::error::{AppError, Result};
+
+/// Longest accepted tag name, after normalization.
+pub const MAX_TAG_LENGTH: usize = 50;
+
+/// Request body for `PUT /users/:id/tags`.
+#[derive(Debug, Deserialize)]
+pub struct SetUserTagsRequest {
+    pub tags: Vec<String>,
+}
+
+impl SetUserTagsRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["tags"];
+
+    /// Normalize the submitted tags, deduplicated and sorted.
+    pub fn normalized(&self) -> Result<Vec<String>> {
+        let mut tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<_>>()?;
+        tags.sort();
+        tags.dedup();
+        Ok(tags)
+    }
+}
+
+/// Normalize a tag name: trimmed and lowercased, non-empty, and at most
+/// [`MAX_TAG_LENGTH`] characters.
+pub fn normalize_tag(raw: &str) -> Result<String> {
+    let tag = raw.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err(AppError::Validation(
+            "tag names must not be empty".to_string(),
+        ));
+    }
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(AppError::Validation(format!(
+            "tag {tag:?} exceeds {MAX_TAG_LENGTH} characters"
+        )));
+    }
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        assert_eq!(super::normalize_tag("  Beta ").unwrap(), "beta");
+        assert_eq!(super::normalize_tag("VIP").unwrap(), "vip");
+    }
+
+    #[test]
+    fn empty_and_oversized_tags_are_rejected() {
+        super::normalize_tag("   ").expect_err("blank tag should be rejected");
+        super::normalize_tag(&"x".repeat(super::MAX_TAG_LENGTH + 1))
+            .expect_err("oversized tag should be rejected");
+        super::normalize_tag(&"x".repeat(super::MAX_TAG_LENGTH)).expect("tag at the limit passes");
+    }
+}
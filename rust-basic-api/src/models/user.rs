@@ -0,0 +1,238 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+use crate::models::EmailAddress;
+
+/// A user row as stored in the `users` table.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, sqlx::FromRow)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    /// Always structurally valid and lowercased: the column decodes
+    /// through [`EmailAddress`], normalizing legacy mixed-case rows on
+    /// read.
+    pub email: EmailAddress,
+    /// Non-sequential public identifier (a UUID, selected as text), for
+    /// clients that must reference users without exposing the enumerable
+    /// serial id. Rotatable via `POST /users/:id/rotate-public-id`.
+    pub public_id: String,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub updated_at: DateTime<Utc>,
+    /// Principal that created the row; serialized for admin-scoped callers
+    /// only. `None` for rows predating attribution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// Principal behind the most recent mutation; same visibility rules as
+    /// `created_by`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+}
+
+impl User {
+    /// Serializable field names, the whitelist for the `fields` query
+    /// parameter. Must stay in sync with the struct; the serialization
+    /// snapshot test pins the wire shape these refer to.
+    pub const FIELDS: &'static [&'static str] = &[
+        "id",
+        "name",
+        "email",
+        "public_id",
+        "created_at",
+        "updated_at",
+        "created_by",
+        "updated_by",
+    ];
+
+    /// Copy of the user with attribution removed, the shape served to
+    /// callers without the admin scope.
+    #[must_use]
+    pub fn without_attribution(mut self) -> Self {
+        self.created_by = None;
+        self.updated_by = None;
+        self
+    }
+}
+
+/// Response body for `GET /users/stats`: collection-wide aggregates for
+/// dashboard summary cards.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserStats {
+    /// Surviving (not soft-deleted) users.
+    pub count: i64,
+    /// `created_at` of the oldest user; `null` when there are none.
+    #[serde(with = "crate::models::serde_rfc3339::option")]
+    pub first_created_at: Option<DateTime<Utc>>,
+    /// `created_at` of the newest user; `null` when there are none.
+    #[serde(with = "crate::models::serde_rfc3339::option")]
+    pub last_created_at: Option<DateTime<Utc>>,
+}
+
+/// Payload for `POST /users`.
+///
+/// `email` stays a raw `String` on the wire: deserializing straight into
+/// [`EmailAddress`] would surface invalid input as an opaque serde error,
+/// while `validate()` (which runs the same parse) produces the
+/// field-keyed, localizable validation error clients rely on.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
+    pub email: String,
+}
+
+impl CreateUserRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["name", "email"];
+
+    pub fn validate(&self) -> Result<()> {
+        validate_name(&self.name)?;
+        validate_email(&self.email)?;
+        Ok(())
+    }
+}
+
+/// Payload for `PUT /users/:id`. Absent fields are left unchanged.
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserRequest {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl UpdateUserRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["name", "email"];
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.name {
+            validate_name(name)?;
+        }
+        if let Some(email) = &self.email {
+            validate_email(email)?;
+        }
+        Ok(())
+    }
+}
+
+/// One row of `POST /admin/users/import-with-ids`: a user carried over
+/// from the legacy system together with its original id.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportUserRecord {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+}
+
+impl ImportUserRecord {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["id", "name", "email"];
+
+    pub fn validate(&self) -> Result<()> {
+        if self.id < 1 {
+            return Err(AppError::Validation(format!(
+                "id {} is not a valid user id; ids start at 1",
+                self.id
+            )));
+        }
+        validate_name(&self.name)?;
+        validate_email(&self.email)?;
+        Ok(())
+    }
+}
+
+/// Per-row failure from an explicit-id import: the offending legacy id
+/// plus why the row was skipped. The remaining rows still import.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ImportRowError {
+    pub id: i32,
+    pub error: String,
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 255 {
+        return Err(AppError::ValidationField {
+            field: "name",
+            code: crate::i18n::keys::NAME_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+fn validate_email(email: &str) -> Result<()> {
+    // The structural check lives in `EmailAddress`; this just discards the
+    // parsed value.
+    email.parse::<EmailAddress>().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn user_round_trips_through_json() {
+        let now = Utc
+            .timestamp_millis_opt(Utc::now().timestamp_millis())
+            .unwrap();
+        let user = User {
+            id: 1,
+            name: "Test User".to_string(),
+            email: "test@example.com".parse().unwrap(),
+            public_id: "5f6a0bcb-7f3d-4e0a-9c27-1f5cf0e8a3d2".to_string(),
+            created_at: now,
+            updated_at: now,
+            created_by: Some("tester".to_string()),
+            updated_by: None,
+        };
+
+        let json = serde_json::to_string(&user).unwrap();
+        let deserialized: User = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(user, deserialized);
+    }
+
+    /// Snapshot of the documented wire format; a change here is a breaking
+    /// API change, not a refactor.
+    #[test]
+    fn user_serialization_snapshot() {
+        let at = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let user = User {
+            id: 7,
+            name: "Snapshot".to_string(),
+            email: "snapshot@example.com".parse().unwrap(),
+            public_id: "0e4f2b9a-1d83-4c57-8a66-3fb0a4c2d911".to_string(),
+            created_at: at,
+            updated_at: at,
+            created_by: None,
+            updated_by: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&user).unwrap(),
+            r#"{"id":7,"name":"Snapshot","email":"snapshot@example.com","public_id":"0e4f2b9a-1d83-4c57-8a66-3fb0a4c2d911","created_at":"2024-01-02T03:04:05.000Z","updated_at":"2024-01-02T03:04:05.000Z"}"#
+        );
+    }
+
+    #[test]
+    fn create_request_validation() {
+        let valid = CreateUserRequest {
+            name: "Test User".to_string(),
+            email: "test@example.com".to_string(),
+        };
+        assert!(valid.validate().is_ok());
+
+        let invalid_email = CreateUserRequest {
+            name: "Test User".to_string(),
+            email: "invalid-email".to_string(),
+        };
+        assert!(invalid_email.validate().is_err());
+
+        let empty_name = CreateUserRequest {
+            name: String::new(),
+            email: "test@example.com".to_string(),
+        };
+        assert!(empty_name.validate().is_err());
+    }
+}
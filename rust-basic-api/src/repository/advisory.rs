@@ -0,0 +1,124 @@
+//! Postgres advisory locks for singleton background work.
+//!
+//! With several replicas behind one database, periodic maintenance
+//! should run on exactly one of them. `pg_try_advisory_lock` gives that
+//! coordination for free — no extra infrastructure, and the lock dies
+//! with the session, so a crashed holder cannot wedge the job forever.
+//!
+//! Advisory locks are session-scoped, which interacts badly with a
+//! connection pool: returning the connection would hand the held lock to
+//! whichever caller gets the session next. [`AdvisoryLock`] therefore
+//! pins the connection it was acquired on and closes it (rather than
+//! recycling it) when the guard goes away.
+
+use sqlx::pool::PoolConnection;
+use sqlx::{PgPool, Postgres};
+use std::time::Duration;
+
+use crate::error::Result;
+
+/// A held advisory lock, pinned to its session. Keep it alive for as
+/// long as the singleton work runs; call [`AdvisoryLock::release`] on
+/// shutdown, or just drop it — the closed session releases the lock
+/// either way.
+pub struct AdvisoryLock {
+    conn: Option<PoolConnection<Postgres>>,
+    key: i64,
+}
+
+/// Try to become the holder of `key`, without blocking. `Ok(Some(..))`
+/// means this process holds the lock until the returned guard goes
+/// away; `Ok(None)` means another session (possibly another replica)
+/// already holds it.
+pub async fn try_advisory_lock(
+    pool: &PgPool,
+    warn_threshold: Duration,
+    key: i64,
+) -> Result<Option<AdvisoryLock>> {
+    let mut conn = super::acquire(pool, warn_threshold, "try_advisory_lock").await?;
+    let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+        .bind(key)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(crate::error::AppError::Database)?;
+    Ok(locked.then(|| AdvisoryLock {
+        conn: Some(conn),
+        key,
+    }))
+}
+
+impl AdvisoryLock {
+    /// Release the lock promptly (the shutdown path). Unlocking in place
+    /// beats waiting for the connection to close: the next holder can
+    /// take over without a session-teardown race.
+    pub async fn release(mut self) {
+        if let Some(mut conn) = self.conn.take() {
+            if let Err(error) = sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(self.key)
+                .execute(&mut *conn)
+                .await
+            {
+                tracing::warn!(%error, key = self.key, "advisory unlock failed; closing the session instead");
+            }
+            let _ = conn.close().await;
+        }
+    }
+}
+
+impl Drop for AdvisoryLock {
+    fn drop(&mut self) {
+        // The session must not go back to the pool still holding the
+        // lock; detaching closes it, and Postgres releases the lock with
+        // the session.
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::try_advisory_lock;
+    use std::time::Duration;
+
+    /// Needs a live Postgres (`DATABASE_URL`); run from the database
+    /// harness with `cargo test -- --ignored`. Two pools stand in for
+    /// two replicas: only one may hold a key at a time, and releasing
+    /// hands it over.
+    #[tokio::test]
+    #[ignore = "requires a live Postgres via DATABASE_URL"]
+    async fn only_one_pool_can_hold_a_key() {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL for the ignored DB test");
+        let first = sqlx::postgres::PgPoolOptions::new()
+            .connect(&url)
+            .await
+            .unwrap();
+        let second = sqlx::postgres::PgPoolOptions::new()
+            .connect(&url)
+            .await
+            .unwrap();
+        let threshold = Duration::from_secs(1);
+        let key = 0x5eed;
+
+        let held = try_advisory_lock(&first, threshold, key)
+            .await
+            .unwrap()
+            .expect("first pool acquires");
+        assert!(
+            try_advisory_lock(&second, threshold, key)
+                .await
+                .unwrap()
+                .is_none(),
+            "second pool must not acquire a held key"
+        );
+
+        held.release().await;
+        assert!(
+            try_advisory_lock(&second, threshold, key)
+                .await
+                .unwrap()
+                .is_some(),
+            "released key is acquirable again"
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Opt-in read-through cache over a [`UserRepository`] (`USER_CACHE=true`),
+//! kept coherent across replicas by the `user_changed` notification
+//! listener: every committed user mutation fires a trigger-driven
+//! `NOTIFY user_changed, '<id>'`, and each replica evicts that entry.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::Result;
+use crate::models::{
+    AuditEntry, Avatar, CreateUserRequest, ImportRowError, ImportUserRecord, UpdateUserRequest,
+    User, UserAuditEntry, UserStats,
+};
+use crate::repository::users::{CollectionVersion, UserQuery};
+use crate::repository::UserRepository;
+
+/// The notification channel the `users` trigger publishes to.
+pub const CHANNEL: &str = "user_changed";
+
+/// [`UserRepository`] decorator caching `get_user` results.
+///
+/// Local mutations evict their own entries immediately; mutations on other
+/// replicas arrive through the invalidation listener. Everything except
+/// the by-id read passes straight through.
+pub struct CachedUserRepository {
+    inner: Arc<dyn UserRepository>,
+    cache: Mutex<HashMap<i32, User>>,
+}
+
+impl CachedUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository>) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drop the cached entry for the given user, if any.
+    pub fn invalidate(&self, id: i32) {
+        self.cache.lock().expect("cache lock poisoned").remove(&id);
+    }
+
+    /// Drop every cached entry, used when a notification payload cannot be
+    /// parsed and the affected id is unknown.
+    pub fn invalidate_all(&self) {
+        self.cache.lock().expect("cache lock poisoned").clear();
+    }
+}
+
+#[async_trait]
+impl UserRepository for CachedUserRepository {
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User> {
+        self.inner.create_user(req, actor).await
+    }
+
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        self.inner.create_user_or_existing(req, actor).await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        if let Some(user) = self.cache.lock().expect("cache lock poisoned").get(&id) {
+            return Ok(Some(user.clone()));
+        }
+        let user = self.inner.get_user(id).await?;
+        if let Some(user) = &user {
+            self.cache
+                .lock()
+                .expect("cache lock poisoned")
+                .insert(id, user.clone());
+        }
+        Ok(user)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>> {
+        self.inner.get_users_by_emails(emails).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>> {
+        self.inner.list_users(query).await
+    }
+
+    async fn count_users(&self, query: &UserQuery) -> Result<i64> {
+        self.inner.count_users(query).await
+    }
+
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion> {
+        self.inner.collection_version(query).await
+    }
+
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>> {
+        self.inner.modified_since(since).await
+    }
+
+    async fn user_stats(&self) -> Result<UserStats> {
+        self.inner.user_stats().await
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let user = self.inner.update_user(id, req, actor).await?;
+        self.invalidate(id);
+        Ok(user)
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let user = self
+            .inner
+            .update_if_unchanged(id, req, expected_updated_at, actor)
+            .await?;
+        self.invalidate(id);
+        Ok(user)
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool> {
+        let deleted = self.inner.delete_user(id).await?;
+        self.invalidate(id);
+        Ok(deleted)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64> {
+        let removed = self.inner.delete_many(ids).await?;
+        for id in ids {
+            self.invalidate(*id);
+        }
+        Ok(removed)
+    }
+
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        let (user, inserted) = self.inner.upsert_user_by_email(req, actor).await?;
+        self.invalidate(user.id);
+        Ok((user, inserted))
+    }
+
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()> {
+        self.inner.record_audit(user_id, action).await
+    }
+
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>> {
+        self.inner.audit_entries(user_id).await
+    }
+
+    async fn wal_token(&self) -> Result<crate::repository::Lsn> {
+        self.inner.wal_token().await
+    }
+
+    async fn wal_caught_up(&self, token: crate::repository::Lsn) -> Result<bool> {
+        self.inner.wal_caught_up(token).await
+    }
+
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>> {
+        self.inner.user_history(user_id).await
+    }
+
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>> {
+        self.inner.set_user_avatar(user_id, avatar).await
+    }
+
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>> {
+        self.inner.user_avatar(user_id).await
+    }
+
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>> {
+        self.inner.set_user_tags(user_id, tags).await
+    }
+
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>> {
+        self.inner.user_tags(user_id).await
+    }
+
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User> {
+        let user = self
+            .inner
+            .merge_users(primary_id, duplicate_id, actor)
+            .await?;
+        self.invalidate(primary_id);
+        self.invalidate(duplicate_id);
+        Ok(user)
+    }
+
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>> {
+        // Imported ids are brand new, so nothing cached can refer to them.
+        self.inner.import_users_with_ids(rows, actor).await
+    }
+
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>> {
+        // Only `pending_email` changes, and that column is not part of the
+        // cached row.
+        self.inner
+            .request_email_change(user_id, new_email, token_hash, expires_at)
+            .await
+    }
+
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User> {
+        let user = self.inner.confirm_email_change(token_hash, actor).await?;
+        self.invalidate(user.id);
+        Ok(user)
+    }
+
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_public_id(public_id).await
+    }
+
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>> {
+        let user = self.inner.rotate_public_id(id, actor).await?;
+        self.invalidate(id);
+        Ok(user)
+    }
+}
+
+/// Run the `LISTEN user_changed` subscriber until `shutdown` resolves,
+/// evicting cache entries named by each notification payload.
+///
+/// The listener holds its own dedicated connection; when it drops, the
+/// whole cache is flushed (notifications may have been missed) and the
+/// connection is re-established with capped exponential backoff.
+pub async fn invalidation_listener(
+    database_url: String,
+    cache: Arc<CachedUserRepository>,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    let mut backoff = Duration::from_millis(100);
+    const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+    loop {
+        let mut listener = tokio::select! {
+            () = &mut shutdown => return,
+            connected = connect(&database_url) => match connected {
+                Ok(listener) => listener,
+                Err(error) => {
+                    tracing::warn!(%error, ?backoff, "cache invalidation listener failed to connect");
+                    tokio::select! {
+                        () = &mut shutdown => return,
+                        () = tokio::time::sleep(backoff) => {}
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            },
+        };
+        backoff = Duration::from_millis(100);
+        // Anything committed while we were disconnected was never seen.
+        cache.invalidate_all();
+        tracing::info!(channel = CHANNEL, "cache invalidation listener connected");
+
+        loop {
+            let notification = tokio::select! {
+                () = &mut shutdown => return,
+                notification = listener.recv() => notification,
+            };
+            match notification {
+                Ok(notification) => match notification.payload().parse::<i32>() {
+                    Ok(id) => cache.invalidate(id),
+                    Err(_) => {
+                        tracing::warn!(
+                            payload = notification.payload(),
+                            "unparsable invalidation payload; flushing cache"
+                        );
+                        cache.invalidate_all();
+                    }
+                },
+                Err(error) => {
+                    tracing::warn!(%error, "cache invalidation listener lost its connection");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn connect(database_url: &str) -> sqlx::Result<sqlx::postgres::PgListener> {
+    let mut listener = sqlx::postgres::PgListener::connect(database_url).await?;
+    listener.listen(CHANNEL).await?;
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::models::CreateUserRequest;
+    use crate::repository::{MemoryUserRepository, UserRepository};
+
+    async fn cached_with_inner() -> (super::CachedUserRepository, Arc<MemoryUserRepository>, i32) {
+        let inner = Arc::new(MemoryUserRepository::new());
+        let cached = super::CachedUserRepository::new(inner.clone());
+        let id = inner
+            .create_user(
+                CreateUserRequest {
+                    name: "Cached".to_string(),
+                    email: "cached@example.com".to_string(),
+                },
+                "seed",
+            )
+            .await
+            .unwrap()
+            .id;
+        (cached, inner, id)
+    }
+
+    #[tokio::test]
+    async fn reads_are_served_from_cache_until_invalidated() {
+        let (cached, inner, id) = cached_with_inner().await;
+
+        assert_eq!(cached.get_user(id).await.unwrap().unwrap().name, "Cached");
+
+        // A mutation on "another replica" (directly against the backing
+        // store) is invisible until the invalidation arrives.
+        inner
+            .update_user(
+                id,
+                crate::models::UpdateUserRequest {
+                    name: Some("Changed".to_string()),
+                    email: None,
+                },
+                "other-replica",
+            )
+            .await
+            .unwrap();
+        assert_eq!(cached.get_user(id).await.unwrap().unwrap().name, "Cached");
+
+        cached.invalidate(id);
+        assert_eq!(cached.get_user(id).await.unwrap().unwrap().name, "Changed");
+    }
+
+    #[tokio::test]
+    async fn local_mutations_evict_their_own_entries() {
+        let (cached, _inner, id) = cached_with_inner().await;
+
+        cached.get_user(id).await.unwrap();
+        cached
+            .update_user(
+                id,
+                crate::models::UpdateUserRequest {
+                    name: Some("Renamed".to_string()),
+                    email: None,
+                },
+                "local",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(cached.get_user(id).await.unwrap().unwrap().name, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn flush_clears_every_entry() {
+        let (cached, inner, id) = cached_with_inner().await;
+
+        cached.get_user(id).await.unwrap();
+        inner.delete_user(id).await.unwrap();
+        assert!(cached.get_user(id).await.unwrap().is_some());
+
+        cached.invalidate_all();
+        assert!(cached.get_user(id).await.unwrap().is_none());
+    }
+}
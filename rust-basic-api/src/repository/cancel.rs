@@ -0,0 +1,106 @@
+//! Propagating client disconnects to Postgres.
+//!
+//! When a client goes away mid-request, axum drops the handler future but
+//! the query it started keeps running on the server. A [`CancelGuard`]
+//! captures the connection's backend PID before a cancellable query runs;
+//! if the guard is dropped without [`CancelGuard::finish`] being called the
+//! future was abandoned, so we count it in `requests_abandoned_total` and
+//! ask Postgres to cancel the backend through a side connection.
+
+use sqlx::postgres::{PgConnection, PgPool};
+
+use crate::metrics;
+
+/// Drop-detector wrapped around a cancellable query.
+pub struct CancelGuard {
+    pid: Option<i32>,
+    pool: Option<PgPool>,
+    context: &'static str,
+    finished: bool,
+}
+
+impl CancelGuard {
+    /// Capture the backend PID of the given connection so the query it is
+    /// about to run can be cancelled server-side if the request is
+    /// abandoned. `pool` supplies the side connection the cancel is issued
+    /// on.
+    pub async fn capture(
+        conn: &mut PgConnection,
+        pool: PgPool,
+        context: &'static str,
+    ) -> Result<Self, sqlx::Error> {
+        let (pid,): (i32,) = sqlx::query_as(r"SELECT pg_backend_pid()")
+            .fetch_one(conn)
+            .await?;
+        Ok(Self {
+            pid: Some(pid),
+            pool: Some(pool),
+            context,
+            finished: false,
+        })
+    }
+
+    /// Mark the query complete; the guard no longer fires on drop.
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        metrics::REQUESTS_ABANDONED.inc();
+        tracing::warn!(
+            context = self.context,
+            pid = self.pid,
+            "request abandoned mid-query; cancelling backend"
+        );
+
+        // `pg_cancel_backend` only interrupts the running statement; the
+        // pooled connection itself stays usable and returns to the pool.
+        if let (Some(pid), Some(pool)) = (self.pid, self.pool.take()) {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    if let Err(error) = sqlx::query(r"SELECT pg_cancel_backend($1)")
+                        .bind(pid)
+                        .execute(&pool)
+                        .await
+                    {
+                        tracing::warn!(pid, %error, "failed to cancel abandoned backend");
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CancelGuard;
+    use crate::metrics;
+
+    fn guard() -> CancelGuard {
+        CancelGuard {
+            pid: None,
+            pool: None,
+            context: "test_query",
+            finished: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn dropping_an_unfinished_guard_counts_an_abandonment() {
+        let before = metrics::REQUESTS_ABANDONED.get();
+        drop(guard());
+        assert_eq!(metrics::REQUESTS_ABANDONED.get(), before + 1);
+    }
+
+    #[tokio::test]
+    async fn a_finished_guard_does_not_count() {
+        let before = metrics::REQUESTS_ABANDONED.get();
+        guard().finish();
+        assert_eq!(metrics::REQUESTS_ABANDONED.get(), before);
+    }
+}
@@ -0,0 +1,564 @@
+//! Single-flight coalescing for hot by-id reads.
+//!
+//! Under load, hundreds of concurrent `GET /users/42` requests each
+//! issue the same query. This decorator shares one in-flight fetch per
+//! id: the first caller runs the query, everyone arriving while it is
+//! running waits for the fan-out, and the entry is removed on
+//! completion. Nothing is cached here — that is the job of
+//! [`super::CachedUserRepository`], which sits above this layer so a
+//! cold-cache stampede costs one query instead of one per caller.
+//! Errors are neither cached nor fanned out: the leader gets its error
+//! back verbatim, and waiters retry (one of them becoming the next
+//! leader), so a transient failure is never replayed to callers who
+//! arrived after it.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+
+use crate::error::Result;
+use crate::models::{
+    AuditEntry, Avatar, CreateUserRequest, ImportRowError, ImportUserRecord, UpdateUserRequest,
+    User, UserAuditEntry, UserStats,
+};
+use crate::repository::users::{CollectionVersion, UserQuery};
+use crate::repository::UserRepository;
+
+/// [`UserRepository`] decorator coalescing concurrent `get_user` calls
+/// for the same id into one underlying query. Everything else passes
+/// straight through.
+pub struct CoalescedUserRepository {
+    inner: Arc<dyn UserRepository>,
+    in_flight: Mutex<HashMap<i32, broadcast::Sender<Option<User>>>>,
+}
+
+impl CoalescedUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository>) -> Self {
+        Self {
+            inner,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Removes a leader's `in_flight` entry on drop, covering cancellation:
+/// a leader abandoned mid-query (the client-disconnect case
+/// [`super::CancelGuard`] exists for) would otherwise leave its sender
+/// in the map forever, and every later caller would subscribe to a
+/// flight that can neither land nor close. Dropping the entry drops the
+/// sender, waiters' `recv` errors, and one of them takes over.
+struct FlightGuard<'a> {
+    in_flight: &'a Mutex<HashMap<i32, broadcast::Sender<Option<User>>>>,
+    id: i32,
+}
+
+impl FlightGuard<'_> {
+    /// Take the entry out for the fan-out, disarming the drop cleanup.
+    fn finish(self) -> Option<broadcast::Sender<Option<User>>> {
+        let sender = self
+            .in_flight
+            .lock()
+            .expect("in-flight lock poisoned")
+            .remove(&self.id);
+        std::mem::forget(self);
+        sender
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .expect("in-flight lock poisoned")
+            .remove(&self.id);
+    }
+}
+
+#[async_trait]
+impl UserRepository for CoalescedUserRepository {
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User> {
+        self.inner.create_user(req, actor).await
+    }
+
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        self.inner.create_user_or_existing(req, actor).await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        loop {
+            let waiter = {
+                let mut in_flight = self.in_flight.lock().expect("in-flight lock poisoned");
+                match in_flight.entry(id) {
+                    Entry::Occupied(entry) => Some(entry.get().subscribe()),
+                    Entry::Vacant(entry) => {
+                        // Capacity 1: the channel carries exactly one
+                        // fan-out before it is dropped.
+                        let (sender, _) = broadcast::channel(1);
+                        entry.insert(sender);
+                        None
+                    }
+                }
+            };
+            let Some(mut waiter) = waiter else {
+                // Leader: run the query with the guard armed, then take
+                // the entry out before fanning out so a caller arriving
+                // after completion starts a fresh flight. Waiters
+                // already subscribed still receive the buffered value.
+                let guard = FlightGuard {
+                    in_flight: &self.in_flight,
+                    id,
+                };
+                let result = self.inner.get_user(id).await;
+                let sender = guard.finish();
+                if let (Ok(user), Some(sender)) = (&result, sender) {
+                    // `send` errs only when no one is waiting.
+                    let _ = sender.send(user.clone());
+                }
+                return result;
+            };
+            match waiter.recv().await {
+                Ok(user) => return Ok(user),
+                // The leader failed and fanned out nothing; its error is
+                // not shared. Go around again — the retry either finds a
+                // newer flight to join or becomes the leader itself.
+                Err(_) => continue,
+            }
+        }
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>> {
+        self.inner.get_users_by_emails(emails).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>> {
+        self.inner.list_users(query).await
+    }
+
+    async fn count_users(&self, query: &UserQuery) -> Result<i64> {
+        self.inner.count_users(query).await
+    }
+
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion> {
+        self.inner.collection_version(query).await
+    }
+
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>> {
+        self.inner.modified_since(since).await
+    }
+
+    async fn user_stats(&self) -> Result<UserStats> {
+        self.inner.user_stats().await
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        self.inner.update_user(id, req, actor).await
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        self.inner
+            .update_if_unchanged(id, req, expected_updated_at, actor)
+            .await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool> {
+        self.inner.delete_user(id).await
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64> {
+        self.inner.delete_many(ids).await
+    }
+
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        self.inner.upsert_user_by_email(req, actor).await
+    }
+
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()> {
+        self.inner.record_audit(user_id, action).await
+    }
+
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>> {
+        self.inner.audit_entries(user_id).await
+    }
+
+    async fn wal_token(&self) -> Result<crate::repository::Lsn> {
+        self.inner.wal_token().await
+    }
+
+    async fn wal_caught_up(&self, token: crate::repository::Lsn) -> Result<bool> {
+        self.inner.wal_caught_up(token).await
+    }
+
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>> {
+        self.inner.user_history(user_id).await
+    }
+
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>> {
+        self.inner.set_user_avatar(user_id, avatar).await
+    }
+
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>> {
+        self.inner.user_avatar(user_id).await
+    }
+
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>> {
+        self.inner.set_user_tags(user_id, tags).await
+    }
+
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>> {
+        self.inner.user_tags(user_id).await
+    }
+
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User> {
+        self.inner
+            .merge_users(primary_id, duplicate_id, actor)
+            .await
+    }
+
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>> {
+        self.inner.import_users_with_ids(rows, actor).await
+    }
+
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>> {
+        self.inner
+            .request_email_change(user_id, new_email, token_hash, expires_at)
+            .await
+    }
+
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User> {
+        self.inner.confirm_email_change(token_hash, actor).await
+    }
+
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_public_id(public_id).await
+    }
+
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>> {
+        self.inner.rotate_public_id(id, actor).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use crate::error::{AppError, Result};
+    use crate::models::{CreateUserRequest, User};
+    use crate::repository::{MemoryUserRepository, UserRepository};
+
+    /// Counts `get_user` calls and holds each one open briefly, so every
+    /// concurrent caller arrives while the first query is still in
+    /// flight. Under the paused test clock the hold costs no real time.
+    /// Only the by-id read is exercised here; nothing else is
+    /// implemented.
+    struct CountingRepository {
+        inner: Arc<MemoryUserRepository>,
+        calls: AtomicUsize,
+        fail_next: AtomicBool,
+    }
+
+    #[async_trait]
+    impl UserRepository for CountingRepository {
+        async fn get_user(&self, id: i32) -> Result<Option<User>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if self.fail_next.swap(false, Ordering::SeqCst) {
+                return Err(AppError::Internal);
+            }
+            self.inner.get_user(id).await
+        }
+
+        async fn create_user(&self, _: CreateUserRequest, _: &str) -> Result<User> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn create_user_or_existing(
+            &self,
+            _: CreateUserRequest,
+            _: &str,
+        ) -> Result<(User, bool)> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn get_user_by_email(&self, _: &str) -> Result<Option<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn get_users_by_emails(&self, _: &[String]) -> Result<Vec<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn list_users(&self, _: &crate::repository::UserQuery) -> Result<Vec<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn count_users(&self, _: &crate::repository::UserQuery) -> Result<i64> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn collection_version(
+            &self,
+            _: &crate::repository::UserQuery,
+        ) -> Result<crate::repository::CollectionVersion> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn modified_since(&self, _: chrono::DateTime<chrono::Utc>) -> Result<Vec<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn user_stats(&self) -> Result<crate::models::UserStats> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn update_user(
+            &self,
+            _: i32,
+            _: crate::models::UpdateUserRequest,
+            _: &str,
+        ) -> Result<Option<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn update_if_unchanged(
+            &self,
+            _: i32,
+            _: crate::models::UpdateUserRequest,
+            _: chrono::DateTime<chrono::Utc>,
+            _: &str,
+        ) -> Result<Option<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn delete_user(&self, _: i32) -> Result<bool> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn delete_many(&self, _: &[i32]) -> Result<u64> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn upsert_user_by_email(
+            &self,
+            _: CreateUserRequest,
+            _: &str,
+        ) -> Result<(User, bool)> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn record_audit(&self, _: i32, _: &str) -> Result<()> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn audit_entries(&self, _: i32) -> Result<Vec<crate::models::AuditEntry>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn wal_token(&self) -> Result<crate::repository::Lsn> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn wal_caught_up(&self, _: crate::repository::Lsn) -> Result<bool> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn user_history(&self, _: i32) -> Result<Vec<crate::models::UserAuditEntry>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn set_user_avatar(&self, _: i32, _: crate::models::Avatar) -> Result<Option<()>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn user_avatar(&self, _: i32) -> Result<Option<crate::models::Avatar>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn set_user_tags(&self, _: i32, _: &[String]) -> Result<Option<Vec<String>>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn user_tags(&self, _: i32) -> Result<Option<Vec<String>>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn merge_users(&self, _: i32, _: i32, _: &str) -> Result<User> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn import_users_with_ids(
+            &self,
+            _: &[crate::models::ImportUserRecord],
+            _: &str,
+        ) -> Result<Vec<crate::models::ImportRowError>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn request_email_change(
+            &self,
+            _: i32,
+            _: &str,
+            _: &str,
+            _: chrono::DateTime<chrono::Utc>,
+        ) -> Result<Option<()>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn confirm_email_change(&self, _: &str, _: &str) -> Result<User> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn get_user_by_public_id(&self, _: &str) -> Result<Option<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+
+        async fn rotate_public_id(&self, _: i32, _: &str) -> Result<Option<User>> {
+            unimplemented!("not exercised by the coalescer tests")
+        }
+    }
+
+    async fn coalesced_with_counts() -> (
+        Arc<super::CoalescedUserRepository>,
+        Arc<CountingRepository>,
+        i32,
+    ) {
+        let memory = Arc::new(MemoryUserRepository::new());
+        let id = memory
+            .create_user(
+                CreateUserRequest {
+                    name: "Hot".to_string(),
+                    email: "hot@example.com".to_string(),
+                },
+                "seed",
+            )
+            .await
+            .unwrap()
+            .id;
+        let counting = Arc::new(CountingRepository {
+            inner: memory,
+            calls: AtomicUsize::new(0),
+            fail_next: AtomicBool::new(false),
+        });
+        let coalesced = Arc::new(super::CoalescedUserRepository::new(counting.clone()));
+        (coalesced, counting, id)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_stampede_on_one_id_costs_one_query() {
+        let (coalesced, counting, id) = coalesced_with_counts().await;
+
+        let fetches: Vec<_> = (0..200)
+            .map(|_| {
+                let coalesced = coalesced.clone();
+                tokio::spawn(async move { coalesced.get_user(id).await })
+            })
+            .collect();
+        for fetch in fetches {
+            assert_eq!(fetch.await.unwrap().unwrap().unwrap().name, "Hot");
+        }
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 1);
+
+        // The entry is gone once the flight lands, so a later caller
+        // queries again rather than reading anything stale.
+        assert!(coalesced.in_flight.lock().unwrap().is_empty());
+        assert!(coalesced.get_user(id).await.unwrap().is_some());
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn errors_are_not_fanned_out_or_cached() {
+        let (coalesced, counting, id) = coalesced_with_counts().await;
+        counting.fail_next.store(true, Ordering::SeqCst);
+
+        // Two concurrent callers: the leader eats the injected error,
+        // the waiter retries and succeeds on a fresh query.
+        let first = tokio::spawn({
+            let coalesced = coalesced.clone();
+            async move { coalesced.get_user(id).await }
+        });
+        let second = tokio::spawn({
+            let coalesced = coalesced.clone();
+            async move { coalesced.get_user(id).await }
+        });
+        let results = [first.await.unwrap(), second.await.unwrap()];
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Ok(Some(user)) if user.name == "Hot")));
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+
+        // Nothing lingers after the failed flight either.
+        assert!(coalesced.in_flight.lock().unwrap().is_empty());
+    }
+
+    /// A leader abandoned mid-query — the client-disconnect case — must
+    /// not strand its waiters on a flight that never lands: dropping the
+    /// leader tears the entry down and one waiter takes over.
+    #[tokio::test(start_paused = true)]
+    async fn an_aborted_leader_does_not_strand_waiters() {
+        let (coalesced, counting, id) = coalesced_with_counts().await;
+
+        let leader = tokio::spawn({
+            let coalesced = coalesced.clone();
+            async move { coalesced.get_user(id).await }
+        });
+        // Let the leader register its flight and park in the slow query,
+        // then line the waiters up behind it before cutting it loose.
+        tokio::task::yield_now().await;
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let coalesced = coalesced.clone();
+                tokio::spawn(async move { coalesced.get_user(id).await })
+            })
+            .collect();
+        tokio::task::yield_now().await;
+        leader.abort();
+
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap().unwrap().unwrap().name, "Hot");
+        }
+        // The leader's aborted query plus the takeover's completed one.
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+        assert!(coalesced.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,781 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use axum::http::StatusCode;
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    AuditEntry, Avatar, CreateUserRequest, ImportRowError, ImportUserRecord, UpdateUserRequest,
+    User, UserAuditEntry, UserStats,
+};
+use crate::repository::users::{CollectionVersion, Pagination, UserQuery};
+use crate::repository::UserRepository;
+
+/// In-memory [`UserRepository`] used by the test suites and for local
+/// experimentation without a database.
+pub struct MemoryUserRepository {
+    inner: Mutex<Inner>,
+    /// Ceiling on rows the unbounded scan paths may return; see
+    /// [`crate::repository::DEFAULT_MAX_ROWS_PER_QUERY`].
+    max_rows: usize,
+}
+
+impl Default for MemoryUserRepository {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::default(),
+            max_rows: crate::repository::DEFAULT_MAX_ROWS_PER_QUERY,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    users: Vec<User>,
+    next_id: i32,
+    audit: Vec<AuditEntry>,
+    next_audit_id: i32,
+    /// Row-image history, mirroring the trigger-populated `user_audit`
+    /// table. Entries survive the user's deletion.
+    history: Vec<UserAuditEntry>,
+    next_history_id: i32,
+    /// Monotonic stand-in for the WAL position, bumped on every write.
+    wal: u64,
+    /// Ids of soft-deleted users; their rows stay for merge history.
+    deleted: std::collections::HashSet<i32>,
+    /// Tag sets per user id, mirroring the `user_tags` join table.
+    tags: std::collections::HashMap<i32, std::collections::BTreeSet<String>>,
+    /// Avatar blobs keyed by content hash, mirroring `user_avatars`.
+    avatars: std::collections::HashMap<String, Avatar>,
+    /// Each user's `avatar_hash` pointer.
+    avatar_hashes: std::collections::HashMap<i32, String>,
+    /// Pending email changes keyed by hashed confirmation token, mirroring
+    /// the `email_change_tokens` table.
+    email_change_tokens: std::collections::HashMap<String, EmailChangeToken>,
+    /// Each user's `pending_email` column.
+    pending_emails: std::collections::HashMap<i32, String>,
+}
+
+/// One row of the in-memory `email_change_tokens` stand-in.
+struct EmailChangeToken {
+    user_id: i32,
+    new_email: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A v4-format UUID for new rows, standing in for `gen_random_uuid()`.
+/// Drawn from OS-seeded `RandomState` output — the crate deliberately
+/// carries no dedicated RNG dependency — which is plenty for uniqueness
+/// within a test-scoped store.
+fn generate_public_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        let keyed = std::collections::hash_map::RandomState::new().build_hasher();
+        chunk.copy_from_slice(&keyed.finish().to_be_bytes());
+    }
+    // Stamp the version (4) and variant (RFC 4122) bits.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl Inner {
+    fn push_audit(&mut self, user_id: i32, action: &str) {
+        self.wal += 1;
+        self.next_audit_id += 1;
+        self.audit.push(AuditEntry {
+            id: self.next_audit_id,
+            user_id,
+            action: action.to_string(),
+            created_at: Utc::now(),
+        });
+    }
+
+    /// Mirror the `record_user_audit` trigger: capture row images around a
+    /// change to `users`.
+    fn push_history(
+        &mut self,
+        user_id: i32,
+        action: &str,
+        old_row: Option<&User>,
+        new_row: Option<&User>,
+    ) {
+        self.wal += 1;
+        self.next_history_id += 1;
+        self.history.push(UserAuditEntry {
+            id: self.next_history_id,
+            user_id,
+            action: action.to_string(),
+            changed_at: Utc::now(),
+            old_row: old_row.map(|u| serde_json::to_value(u).expect("user serializes")),
+            new_row: new_row.map(|u| serde_json::to_value(u).expect("user serializes")),
+        });
+    }
+
+    /// Look up a merge participant, rejecting missing (404) and soft-deleted
+    /// (422) users.
+    fn merge_participant(&self, id: i32) -> Result<User> {
+        let Some(user) = self.users.iter().find(|u| u.id == id) else {
+            return Err(AppError::NotFound);
+        };
+        if self.deleted.contains(&id) {
+            return Err(AppError::http(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("user {id} is deleted and cannot take part in a merge"),
+            ));
+        }
+        Ok(user.clone())
+    }
+}
+
+impl MemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the row ceiling for the unbounded scan paths
+    /// (`MAX_ROWS_PER_QUERY`).
+    #[must_use]
+    pub fn with_row_limit(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Number of distinct avatar blobs held, for asserting deduplication.
+    pub fn avatar_blob_count(&self) -> usize {
+        self.inner
+            .lock()
+            .expect("repository lock poisoned")
+            .avatars
+            .len()
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User> {
+        let email: crate::models::EmailAddress = req.email.parse()?;
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.users.iter().any(|u| u.email == email) {
+            return Err(AppError::Conflict("email already in use".to_string()));
+        }
+
+        inner.next_id += 1;
+        let now = Utc::now();
+        let user = User {
+            id: inner.next_id,
+            name: req.name,
+            email,
+            public_id: generate_public_id(),
+            created_at: now,
+            updated_at: now,
+            created_by: Some(actor.to_string()),
+            updated_by: Some(actor.to_string()),
+        };
+        inner.users.push(user.clone());
+        inner.push_history(user.id, "insert", None, Some(&user));
+        Ok(user)
+    }
+
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        // Mirrors the SQL implementation's retry-once shape, although the
+        // repository mutex means the fetch cannot actually race a delete
+        // here.
+        for _ in 0..2 {
+            match self.create_user(req.clone(), actor).await {
+                Ok(user) => return Ok((user, true)),
+                Err(AppError::Conflict(_)) => {
+                    if let Some(existing) = self.get_user_by_email(&req.email).await? {
+                        return Ok((existing, false));
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(AppError::Conflict("email already in use".to_string()))
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .users
+            .iter()
+            .find(|u| u.id == id && !inner.deleted.contains(&id))
+            .cloned())
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .users
+            .iter()
+            .find(|u| {
+                u.email.as_str().eq_ignore_ascii_case(email) && !inner.deleted.contains(&u.id)
+            })
+            .cloned())
+    }
+
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .users
+            .iter()
+            .filter(|u| {
+                !inner.deleted.contains(&u.id)
+                    && emails
+                        .iter()
+                        .any(|email| u.email.as_str().eq_ignore_ascii_case(email))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        let matching = inner
+            .users
+            .iter()
+            .filter(|u| query.matches(u, inner.deleted.contains(&u.id), inner.tags.get(&u.id)));
+        Ok(match *query.pagination() {
+            Pagination::Offset { limit, offset } => matching
+                .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+                .take(usize::try_from(limit).unwrap_or(0))
+                .cloned()
+                .collect(),
+            Pagination::Keyset { limit, after_id } => matching
+                .filter(|u| after_id.map_or(true, |after| u.id > after))
+                .take(usize::try_from(limit).unwrap_or(0))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    async fn count_users(&self, query: &UserQuery) -> Result<i64> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .users
+            .iter()
+            .filter(|u| query.matches(u, inner.deleted.contains(&u.id), inner.tags.get(&u.id)))
+            .count() as i64)
+    }
+
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        let matching: Vec<&User> = inner
+            .users
+            .iter()
+            .filter(|u| query.matches(u, inner.deleted.contains(&u.id), inner.tags.get(&u.id)))
+            .collect();
+        Ok(CollectionVersion {
+            latest_updated_at: matching.iter().map(|u| u.updated_at).max(),
+            count: matching.len() as i64,
+        })
+    }
+
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        // Compare at millisecond precision to match the wire format
+        // produced by `models::serde_rfc3339`.
+        let mut users: Vec<User> = inner
+            .users
+            .iter()
+            .filter(|u| {
+                u.updated_at.timestamp_millis() > since.timestamp_millis()
+                    && !inner.deleted.contains(&u.id)
+            })
+            .cloned()
+            .collect();
+        users.sort_by_key(|u| u.updated_at);
+        crate::repository::check_row_limit("modified_since", users.len(), self.max_rows)?;
+        Ok(users)
+    }
+
+    async fn user_stats(&self) -> Result<UserStats> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        let surviving: Vec<&User> = inner
+            .users
+            .iter()
+            .filter(|u| !inner.deleted.contains(&u.id))
+            .collect();
+        Ok(UserStats {
+            count: surviving.len() as i64,
+            first_created_at: surviving.iter().map(|u| u.created_at).min(),
+            last_created_at: surviving.iter().map(|u| u.created_at).max(),
+        })
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&id) {
+            return Ok(None);
+        }
+        let Some(position) = inner.users.iter().position(|u| u.id == id) else {
+            return Ok(None);
+        };
+        let email: Option<crate::models::EmailAddress> =
+            req.email.map(|email| email.parse()).transpose()?;
+        // The unique index spans soft-deleted rows too, so any other row
+        // holding the email conflicts — mirroring the SQL 23505 → 409.
+        if let Some(email) = &email {
+            if inner.users.iter().any(|u| u.id != id && u.email == *email) {
+                return Err(AppError::Conflict("email already in use".to_string()));
+            }
+        }
+        let user = &mut inner.users[position];
+        let old = user.clone();
+
+        if let Some(name) = req.name {
+            user.name = name;
+        }
+        if let Some(email) = email {
+            user.email = email;
+        }
+        user.updated_at = Utc::now();
+        user.updated_by = Some(actor.to_string());
+        let updated = user.clone();
+        inner.push_history(id, "update", Some(&old), Some(&updated));
+        Ok(Some(updated))
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&id) {
+            return Ok(None);
+        }
+        // Compare at millisecond precision to match the wire format
+        // produced by `models::serde_rfc3339`.
+        let Some(position) = inner.users.iter().position(|u| {
+            u.id == id && u.updated_at.timestamp_millis() == expected_updated_at.timestamp_millis()
+        }) else {
+            return Ok(None);
+        };
+        let email: Option<crate::models::EmailAddress> =
+            req.email.map(|email| email.parse()).transpose()?;
+        // Same uniqueness contract as `update_user`: the optimistic path
+        // hits the same index, so it answers the same 409.
+        if let Some(email) = &email {
+            if inner.users.iter().any(|u| u.id != id && u.email == *email) {
+                return Err(AppError::Conflict("email already in use".to_string()));
+            }
+        }
+        let user = &mut inner.users[position];
+        let old = user.clone();
+
+        if let Some(name) = req.name {
+            user.name = name;
+        }
+        if let Some(email) = email {
+            user.email = email;
+        }
+        user.updated_at = Utc::now();
+        user.updated_by = Some(actor.to_string());
+        let updated = user.clone();
+        inner.push_history(id, "update", Some(&old), Some(&updated));
+        Ok(Some(updated))
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&id) {
+            return Ok(false);
+        }
+        let old = inner.users.iter().find(|u| u.id == id).cloned();
+        let before = inner.users.len();
+        inner.users.retain(|u| u.id != id);
+        let removed = inner.users.len() < before;
+        if removed {
+            if let Some(old) = old {
+                inner.push_history(id, "delete", Some(&old), None);
+            }
+            // Mirrors the ON DELETE CASCADE on `user_tags` and
+            // `audit_log`. The avatar pointer goes, but the blob stays:
+            // another user may still reference it.
+            inner.tags.remove(&id);
+            inner.avatar_hashes.remove(&id);
+            inner.audit.retain(|entry| entry.user_id != id);
+        }
+        Ok(removed)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        // Soft-deleted rows stay, mirroring the SQL `deleted_at IS NULL`
+        // guard.
+        let soft_deleted = inner.deleted.clone();
+        let removed_rows: Vec<User> = inner
+            .users
+            .iter()
+            .filter(|u| ids.contains(&u.id) && !soft_deleted.contains(&u.id))
+            .cloned()
+            .collect();
+        inner
+            .users
+            .retain(|u| !ids.contains(&u.id) || soft_deleted.contains(&u.id));
+        let removed = removed_rows.len();
+        for old in &removed_rows {
+            inner.push_history(old.id, "delete", Some(old), None);
+        }
+        for id in ids {
+            inner.tags.remove(id);
+            inner.avatar_hashes.remove(id);
+        }
+        let removed_ids: Vec<i32> = removed_rows.iter().map(|u| u.id).collect();
+        inner
+            .audit
+            .retain(|entry| !removed_ids.contains(&entry.user_id));
+        Ok(removed as u64)
+    }
+
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        let email: crate::models::EmailAddress = req.email.parse()?;
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if let Some(existing) = inner.users.iter().position(|u| u.email == email) {
+            let id = inner.users[existing].id;
+            if inner.deleted.contains(&id) {
+                return Err(AppError::Conflict(
+                    "email belongs to a deleted user".to_string(),
+                ));
+            }
+            let user = &mut inner.users[existing];
+            let old = user.clone();
+            user.name = req.name;
+            user.updated_at = Utc::now();
+            user.updated_by = Some(actor.to_string());
+            let updated = user.clone();
+            inner.push_history(id, "update", Some(&old), Some(&updated));
+            return Ok((updated, false));
+        }
+
+        inner.next_id += 1;
+        let now = Utc::now();
+        let user = User {
+            id: inner.next_id,
+            name: req.name,
+            email,
+            public_id: generate_public_id(),
+            created_at: now,
+            updated_at: now,
+            created_by: Some(actor.to_string()),
+            updated_by: Some(actor.to_string()),
+        };
+        inner.users.push(user.clone());
+        inner.push_history(user.id, "insert", None, Some(&user));
+        Ok((user, true))
+    }
+
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        inner.push_audit(user_id, action);
+        Ok(())
+    }
+
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .audit
+            .iter()
+            .filter(|e| e.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn wal_token(&self) -> Result<crate::repository::Lsn> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(crate::repository::Lsn(inner.wal))
+    }
+
+    async fn wal_caught_up(&self, token: crate::repository::Lsn) -> Result<bool> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(token.0 <= inner.wal)
+    }
+
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .history
+            .iter()
+            .filter(|e| e.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&user_id) || !inner.users.iter().any(|u| u.id == user_id) {
+            return Ok(None);
+        }
+        inner.wal += 1;
+        inner.avatar_hashes.insert(user_id, avatar.hash.clone());
+        inner.avatars.entry(avatar.hash.clone()).or_insert(avatar);
+        Ok(Some(()))
+    }
+
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&user_id) {
+            return Ok(None);
+        }
+        Ok(inner
+            .avatar_hashes
+            .get(&user_id)
+            .and_then(|hash| inner.avatars.get(hash))
+            .cloned())
+    }
+
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&user_id) || !inner.users.iter().any(|u| u.id == user_id) {
+            return Ok(None);
+        }
+        inner.wal += 1;
+        inner.tags.insert(user_id, tags.iter().cloned().collect());
+        Ok(Some(tags.to_vec()))
+    }
+
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&user_id) || !inner.users.iter().any(|u| u.id == user_id) {
+            return Ok(None);
+        }
+        Ok(Some(
+            inner
+                .tags
+                .get(&user_id)
+                .map(|tags| tags.iter().cloned().collect())
+                .unwrap_or_default(),
+        ))
+    }
+
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        let primary = inner.merge_participant(primary_id)?;
+        let duplicate = inner.merge_participant(duplicate_id)?;
+
+        for entry in inner.audit.iter_mut().filter(|e| e.user_id == duplicate_id) {
+            entry.user_id = primary_id;
+        }
+
+        // Precedence: the primary keeps its email; other fields come from
+        // whichever row was updated most recently.
+        let now = Utc::now();
+        let merged = {
+            let user = inner
+                .users
+                .iter_mut()
+                .find(|u| u.id == primary_id)
+                .expect("primary checked above");
+            if duplicate.updated_at > primary.updated_at {
+                user.name = duplicate.name;
+            }
+            user.updated_at = now;
+            user.updated_by = Some(actor.to_string());
+            user.clone()
+        };
+
+        if let Some(user) = inner.users.iter_mut().find(|u| u.id == duplicate_id) {
+            user.updated_at = now;
+            user.updated_by = Some(actor.to_string());
+        }
+        inner.deleted.insert(duplicate_id);
+        inner.push_audit(duplicate_id, &format!("merged_into:{primary_id}"));
+
+        Ok(merged)
+    }
+
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut seen_emails = std::collections::HashSet::new();
+        let mut errors = Vec::new();
+        for row in rows {
+            let email: crate::models::EmailAddress = row.email.parse()?;
+            if !seen_ids.insert(row.id) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "duplicate id in import batch".to_string(),
+                });
+                continue;
+            }
+            if !seen_emails.insert(email.as_str().to_string()) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "duplicate email in import batch".to_string(),
+                });
+                continue;
+            }
+            // Soft-deleted rows stay in `users`, so these scans cover them
+            // like the SQL whole-table checks do.
+            if inner.users.iter().any(|u| u.id == row.id) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "id already in use".to_string(),
+                });
+                continue;
+            }
+            if inner.users.iter().any(|u| u.email == email) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "email already in use".to_string(),
+                });
+                continue;
+            }
+
+            let now = Utc::now();
+            let user = User {
+                id: row.id,
+                name: row.name.clone(),
+                email,
+                public_id: generate_public_id(),
+                created_at: now,
+                updated_at: now,
+                created_by: Some(actor.to_string()),
+                updated_by: Some(actor.to_string()),
+            };
+            inner.users.push(user.clone());
+            inner.push_history(user.id, "insert", None, Some(&user));
+            // Mirrors the SQL `setval`: the next plain insert must land
+            // past every imported id.
+            inner.next_id = inner.next_id.max(row.id);
+        }
+        Ok(errors)
+    }
+
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&user_id) || !inner.users.iter().any(|u| u.id == user_id) {
+            return Ok(None);
+        }
+        // Soft-deleted rows keep their email, so the availability check
+        // scans every row, like the SQL whole-table check.
+        if inner
+            .users
+            .iter()
+            .any(|u| u.email.as_str().eq_ignore_ascii_case(new_email))
+        {
+            return Err(AppError::Conflict("email already in use".to_string()));
+        }
+
+        // A new request supersedes any earlier one: its tokens die here.
+        inner
+            .email_change_tokens
+            .retain(|_, token| token.user_id != user_id);
+        inner.pending_emails.insert(user_id, new_email.to_string());
+        inner.email_change_tokens.insert(
+            token_hash.to_string(),
+            EmailChangeToken {
+                user_id,
+                new_email: new_email.to_string(),
+                expires_at,
+            },
+        );
+        inner.wal += 1;
+        Ok(Some(()))
+    }
+
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        // Removing up front is what makes the token single-use: a second
+        // presentation finds nothing, whatever became of the first.
+        let Some(token) = inner.email_change_tokens.remove(token_hash) else {
+            return Err(AppError::NotFound);
+        };
+        if token.expires_at <= Utc::now() {
+            return Err(AppError::http(
+                StatusCode::GONE,
+                "confirmation token has expired",
+            ));
+        }
+        if inner.users.iter().any(|u| {
+            u.id != token.user_id && u.email.as_str().eq_ignore_ascii_case(&token.new_email)
+        }) {
+            // Mirror the SQL rollback: the token survives a conflict, so
+            // put it back for a retry once the clash resolves.
+            let hash = token_hash.to_string();
+            inner.email_change_tokens.insert(hash, token);
+            return Err(AppError::Conflict("email already in use".to_string()));
+        }
+
+        if inner.deleted.contains(&token.user_id) {
+            return Err(AppError::NotFound);
+        }
+        let user_id = token.user_id;
+        let Some(user) = inner.users.iter_mut().find(|u| u.id == user_id) else {
+            return Err(AppError::NotFound);
+        };
+        let old = user.clone();
+        user.email = token.new_email.parse()?;
+        user.updated_at = Utc::now();
+        user.updated_by = Some(actor.to_string());
+        let updated = user.clone();
+        inner.pending_emails.remove(&user_id);
+        inner.push_history(user_id, "update", Some(&old), Some(&updated));
+        Ok(updated)
+    }
+
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>> {
+        let inner = self.inner.lock().expect("repository lock poisoned");
+        Ok(inner
+            .users
+            .iter()
+            .find(|u| u.public_id.eq_ignore_ascii_case(public_id) && !inner.deleted.contains(&u.id))
+            .cloned())
+    }
+
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>> {
+        let mut inner = self.inner.lock().expect("repository lock poisoned");
+        if inner.deleted.contains(&id) {
+            return Ok(None);
+        }
+        let Some(user) = inner.users.iter_mut().find(|u| u.id == id) else {
+            return Ok(None);
+        };
+        let old = user.clone();
+        user.public_id = generate_public_id();
+        user.updated_at = Utc::now();
+        user.updated_by = Some(actor.to_string());
+        let updated = user.clone();
+        inner.push_history(id, "update", Some(&old), Some(&updated));
+        Ok(Some(updated))
+    }
+}
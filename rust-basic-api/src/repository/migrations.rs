@@ -0,0 +1,99 @@
+//! The single embedded copy of the `migrations/` directory.
+//!
+//! `sqlx::migrate!()` resolves `migrations/` relative to the crate
+//! manifest, so every expansion site is a place a moved directory or a
+//! Docker build context mismatch can break. All consumers — startup,
+//! tenant migration, the pending-migrations probe, and tests — go through
+//! [`MIGRATOR`] instead, and [`tests`] pin the embedded set's shape so a
+//! migration that fails to embed fails `cargo test`, not a deploy.
+
+use sqlx::migrate::Migrator;
+
+/// Every migration compiled into the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Advisory lock key serializing migration runs across replicas.
+pub const MIGRATION_LOCK_KEY: i64 = i64::from_be_bytes(*b"migrate!");
+
+/// Run the embedded migrations while holding a Postgres advisory lock, so
+/// replicas rolled out simultaneously take turns instead of racing
+/// concurrent DDL: one migrates while the rest block on
+/// [`MIGRATION_LOCK_KEY`] and then find the schema already up to date
+/// (their run is a no-op).
+///
+/// The lock is taken on `conn` itself and released before returning, even
+/// when the run fails — a lock left behind would wedge every other
+/// replica's startup until this session dies.
+pub async fn run_locked(conn: &mut sqlx::PgConnection) -> anyhow::Result<()> {
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+    let run = MIGRATOR.run(&mut *conn).await;
+    let unlock = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await;
+    run?;
+    unlock?;
+    Ok(())
+}
+
+/// Versions of the embedded up-migrations, in embed order.
+pub fn embedded_versions() -> Vec<i64> {
+    MIGRATOR
+        .migrations
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect()
+}
+
+/// Log the embedded migration set, so image-vs-source drift (a binary
+/// built from a stale build context) is visible in the startup log of a
+/// running replica.
+pub fn log_embedded_set() {
+    tracing::info!(
+        count = MIGRATOR.migrations.len(),
+        versions = ?embedded_versions(),
+        "embedded migrations"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs a live Postgres (`DATABASE_URL`); run from the database
+    /// harness with `cargo test -- --ignored`. Two sessions stand in for
+    /// two replicas rolling out at once: both migration runs must
+    /// succeed, with the lock turning the race into a queue.
+    #[tokio::test]
+    #[ignore = "requires a live Postgres via DATABASE_URL"]
+    async fn concurrent_migration_runs_both_succeed() {
+        use sqlx::Connection;
+
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL for the ignored DB test");
+        let mut first = sqlx::PgConnection::connect(&url).await.unwrap();
+        let mut second = sqlx::PgConnection::connect(&url).await.unwrap();
+
+        let (first, second) = tokio::join!(run_locked(&mut first), run_locked(&mut second));
+        first.expect("first replica migrates");
+        second.expect("second replica migrates");
+    }
+
+    #[test]
+    fn embedded_set_is_non_empty_and_strictly_increasing() {
+        let versions = embedded_versions();
+        assert!(
+            !versions.is_empty(),
+            "no migrations embedded; was the migrations/ directory moved?"
+        );
+        for pair in versions.windows(2) {
+            assert!(
+                pair[0] < pair[1],
+                "embedded migration versions must be strictly increasing: {versions:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! [`UserRepository`] decorator feeding the `db` phase of `Server-Timing`.
+//!
+//! Every call is timed and reported through
+//! [`crate::middleware::server_timing::record_db`]; outside a timed
+//! request scope the report is a no-op, so the decorator is wrapped
+//! unconditionally around whichever repository stack is configured
+//! (including the cache, whose hits legitimately count as fast `db` time).
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::Result;
+use crate::models::{
+    AuditEntry, Avatar, CreateUserRequest, ImportRowError, ImportUserRecord, UpdateUserRequest,
+    User, UserAuditEntry, UserStats,
+};
+use crate::repository::users::{CollectionVersion, UserQuery};
+use crate::repository::UserRepository;
+
+pub struct TimedUserRepository {
+    inner: Arc<dyn UserRepository>,
+}
+
+impl TimedUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository>) -> Self {
+        Self { inner }
+    }
+}
+
+async fn timed<T>(call: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = call.await;
+    crate::middleware::server_timing::record_db(started.elapsed());
+    result
+}
+
+#[async_trait]
+impl UserRepository for TimedUserRepository {
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User> {
+        timed(self.inner.create_user(req, actor)).await
+    }
+
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        timed(self.inner.create_user_or_existing(req, actor)).await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        timed(self.inner.get_user(id)).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        timed(self.inner.get_user_by_email(email)).await
+    }
+
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>> {
+        timed(self.inner.get_users_by_emails(emails)).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>> {
+        timed(self.inner.list_users(query)).await
+    }
+
+    async fn count_users(&self, query: &UserQuery) -> Result<i64> {
+        timed(self.inner.count_users(query)).await
+    }
+
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion> {
+        timed(self.inner.collection_version(query)).await
+    }
+
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>> {
+        timed(self.inner.modified_since(since)).await
+    }
+
+    async fn user_stats(&self) -> Result<UserStats> {
+        timed(self.inner.user_stats()).await
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        timed(self.inner.update_user(id, req, actor)).await
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        timed(
+            self.inner
+                .update_if_unchanged(id, req, expected_updated_at, actor),
+        )
+        .await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool> {
+        timed(self.inner.delete_user(id)).await
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64> {
+        timed(self.inner.delete_many(ids)).await
+    }
+
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        timed(self.inner.upsert_user_by_email(req, actor)).await
+    }
+
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()> {
+        timed(self.inner.record_audit(user_id, action)).await
+    }
+
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>> {
+        timed(self.inner.audit_entries(user_id)).await
+    }
+
+    async fn wal_token(&self) -> Result<crate::repository::Lsn> {
+        timed(self.inner.wal_token()).await
+    }
+
+    async fn wal_caught_up(&self, token: crate::repository::Lsn) -> Result<bool> {
+        timed(self.inner.wal_caught_up(token)).await
+    }
+
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>> {
+        timed(self.inner.user_history(user_id)).await
+    }
+
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>> {
+        timed(self.inner.set_user_avatar(user_id, avatar)).await
+    }
+
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>> {
+        timed(self.inner.user_avatar(user_id)).await
+    }
+
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>> {
+        timed(self.inner.set_user_tags(user_id, tags)).await
+    }
+
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>> {
+        timed(self.inner.user_tags(user_id)).await
+    }
+
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User> {
+        timed(self.inner.merge_users(primary_id, duplicate_id, actor)).await
+    }
+
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>> {
+        timed(self.inner.import_users_with_ids(rows, actor)).await
+    }
+
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>> {
+        timed(
+            self.inner
+                .request_email_change(user_id, new_email, token_hash, expires_at),
+        )
+        .await
+    }
+
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User> {
+        timed(self.inner.confirm_email_change(token_hash, actor)).await
+    }
+
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>> {
+        timed(self.inner.get_user_by_public_id(public_id)).await
+    }
+
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>> {
+        timed(self.inner.rotate_public_id(id, actor)).await
+    }
+}
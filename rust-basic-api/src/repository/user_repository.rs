@@ -0,0 +1,1202 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    AuditEntry, Avatar, CreateUserRequest, EmailAddress, ImportRowError, ImportUserRecord,
+    UpdateUserRequest, User, UserAuditEntry, UserStats,
+};
+use crate::repository::users::{CollectionVersion, UserQuery};
+use crate::repository::{acquire, CancelGuard, Lsn, PoolHandle};
+
+/// Storage operations for users.
+///
+/// The production implementation is [`SqlxUserRepository`]; tests use the
+/// in-memory implementation in [`crate::repository::memory`].
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Create the user, recording `actor` as its creator. A taken email
+    /// is a conflict.
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User>;
+    /// Create the user, or — when the email is already taken — return
+    /// the existing row instead of failing, plus whether this call
+    /// created it. Race-safe for concurrent duplicate registrations: the
+    /// losing side re-fetches by email, retrying the insert once if the
+    /// conflicting row is deleted between the failure and the fetch.
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)>;
+    async fn get_user(&self, id: i32) -> Result<Option<User>>;
+    /// Look up a user by email, case-insensitively.
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>>;
+    /// Resolve several emails in one query, case-insensitively. Returns
+    /// only the users that exist; callers pair the result back up with
+    /// their input. Order is unspecified.
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>>;
+    /// List the users matching the given query, in id order.
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>>;
+    /// Count the users matching the given query's filters.
+    async fn count_users(&self, query: &UserQuery) -> Result<i64>;
+    /// The change-detection aggregate (newest `updated_at` plus count) for
+    /// the query's filters, fetched in one cheap query for conditional GET.
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion>;
+    /// Users whose trigger-maintained `updated_at` is strictly after
+    /// `since`, oldest change first, so sync clients can pull deltas
+    /// instead of rescanning the collection.
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>>;
+    /// Collection-wide aggregates (count plus oldest and newest
+    /// `created_at`) over surviving users, fetched in one cheap query for
+    /// dashboard summaries.
+    async fn user_stats(&self) -> Result<UserStats>;
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>>;
+    /// Update only when the stored `updated_at` still matches
+    /// `expected_updated_at`, preventing lost updates. Returns `None` when
+    /// the row is missing or has been modified since the client read it.
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>>;
+    async fn delete_user(&self, id: i32) -> Result<bool>;
+    /// Delete every listed user in one statement, returning how many rows
+    /// were removed. Missing ids are skipped; an empty list is a no-op.
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64>;
+    /// Insert the user, or update the existing user carrying the same
+    /// email. Returns the resulting row and `true` when a new row was
+    /// inserted. Emails held by soft-deleted users cannot be upserted and
+    /// produce a conflict.
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)>;
+    /// Append an audit log entry for the given user.
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()>;
+    /// Audit log entries for the given user, oldest first.
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>>;
+    /// The primary's current WAL position, issued to clients as an opaque
+    /// read-after-write consistency token after a mutation.
+    async fn wal_token(&self) -> Result<Lsn>;
+    /// Whether this connection's WAL replay has reached `token`. True on a
+    /// primary, which has nothing to replay.
+    async fn wal_caught_up(&self, token: Lsn) -> Result<bool>;
+    /// Trigger-written `user_audit` rows for the given user, oldest first.
+    /// Rows survive the user's deletion, so history stays queryable for
+    /// compliance review.
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>>;
+    /// Store the avatar blob (deduplicated by content hash) and point the
+    /// user at it. Returns `None` when the user is missing or deleted.
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>>;
+    /// The user's avatar blob, or `None` when the user is missing, deleted,
+    /// or has no avatar.
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>>;
+    /// Replace the user's tag set with the given (already normalized)
+    /// tags. Returns `None` when the user is missing or deleted.
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>>;
+    /// The user's tags, sorted, or `None` when the user is missing or
+    /// deleted.
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>>;
+    /// Merge the duplicate user into the primary one, transactionally.
+    ///
+    /// Re-points the duplicate's audit log entries to the primary, keeps the
+    /// primary's email while taking other fields from whichever row was
+    /// updated most recently, soft-deletes the duplicate, and records a
+    /// `merged_into:<primary>` audit entry against it. Returns the merged
+    /// primary. Fails with 404 when either user is missing and 422 when
+    /// either is already soft-deleted.
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User>;
+    /// Insert legacy users with their original ids, transactionally, and
+    /// advance the id sequence past the largest imported id so future
+    /// plain inserts cannot collide. Rows whose id or email is already
+    /// taken (in the table or earlier in the batch) are skipped and
+    /// reported; the rest import.
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>>;
+    /// Park `new_email` as the user's pending address and store the hashed
+    /// confirmation token, replacing any earlier pending change (whose
+    /// tokens stop working). An address already in use anywhere in the
+    /// table is a conflict. Returns `None` when the user is missing or
+    /// deleted.
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>>;
+    /// Atomically apply the pending change named by `token_hash`, returning
+    /// the updated user. The token must exist and be unexpired, and the new
+    /// address must still be free — a registration that claimed it between
+    /// request and confirmation is a conflict. Tokens are single-use:
+    /// success (and expiry) consumes them.
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User>;
+    /// Look up a user by their public UUID, case-insensitively. Callers
+    /// validate the format first; this only ever sees well-formed UUIDs.
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>>;
+    /// Assign the user a fresh public UUID, retiring the old one. Returns
+    /// `None` when the user is missing or deleted.
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>>;
+}
+
+/// Postgres-backed [`UserRepository`] implementation.
+///
+/// The pool is loaded through a [`PoolHandle`] on every call so a pool
+/// recycle is picked up without reconstructing the repository.
+pub struct SqlxUserRepository {
+    pool: PoolHandle,
+    acquire_warn_threshold: Duration,
+    /// Schema the queries run against in multi-tenant mode; `None` uses
+    /// the connection's default search path.
+    schema: Option<String>,
+    /// Ceiling on rows the unbounded scan paths may return; see
+    /// [`crate::repository::DEFAULT_MAX_ROWS_PER_QUERY`].
+    max_rows: usize,
+}
+
+/// Query executor scoped to the repository's schema: a plain connection in
+/// single-tenant mode, or a transaction with `SET LOCAL search_path`
+/// applied so the scoping cannot outlive the query and never leaks back to
+/// the pool.
+enum TenantExecutor<'c> {
+    Plain(&'c mut sqlx::PgConnection),
+    Tx(sqlx::Transaction<'c, sqlx::Postgres>),
+}
+
+impl TenantExecutor<'_> {
+    /// Commit the scoping transaction, if any. Dropping an unfinished
+    /// executor rolls it back instead.
+    async fn finish(self) -> std::result::Result<(), sqlx::Error> {
+        match self {
+            TenantExecutor::Plain(_) => Ok(()),
+            TenantExecutor::Tx(tx) => tx.commit().await,
+        }
+    }
+}
+
+impl std::ops::Deref for TenantExecutor<'_> {
+    type Target = sqlx::PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            TenantExecutor::Plain(conn) => conn,
+            TenantExecutor::Tx(tx) => tx,
+        }
+    }
+}
+
+impl std::ops::DerefMut for TenantExecutor<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            TenantExecutor::Plain(conn) => conn,
+            TenantExecutor::Tx(tx) => tx,
+        }
+    }
+}
+
+impl SqlxUserRepository {
+    pub fn new(pool: PoolHandle, acquire_warn_threshold: Duration) -> Self {
+        Self {
+            pool,
+            acquire_warn_threshold,
+            schema: None,
+            max_rows: crate::repository::DEFAULT_MAX_ROWS_PER_QUERY,
+        }
+    }
+
+    /// Repository bound to a tenant schema; every query runs inside a
+    /// transaction that applies `SET LOCAL search_path` first.
+    pub fn with_schema(pool: PoolHandle, acquire_warn_threshold: Duration, schema: String) -> Self {
+        Self {
+            pool,
+            acquire_warn_threshold,
+            schema: Some(schema),
+            max_rows: crate::repository::DEFAULT_MAX_ROWS_PER_QUERY,
+        }
+    }
+
+    /// Override the row ceiling for the unbounded scan paths
+    /// (`MAX_ROWS_PER_QUERY`).
+    #[must_use]
+    pub fn with_row_limit(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Scope a connection to this repository's schema.
+    async fn scope<'c>(
+        &self,
+        conn: &'c mut sqlx::PgConnection,
+    ) -> std::result::Result<TenantExecutor<'c>, sqlx::Error> {
+        match &self.schema {
+            None => Ok(TenantExecutor::Plain(conn)),
+            Some(schema) => {
+                let mut tx = sqlx::Connection::begin(conn).await?;
+                sqlx::Executor::execute(
+                    &mut *tx,
+                    format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+                )
+                .await?;
+                Ok(TenantExecutor::Tx(tx))
+            }
+        }
+    }
+
+    /// Acquire a connection from the current pool for the named operation,
+    /// with starvation instrumentation and context logging applied.
+    async fn conn(
+        &self,
+        context: &str,
+    ) -> crate::error::Result<sqlx::pool::PoolConnection<sqlx::Postgres>> {
+        let pool = self.pool.current();
+        acquire(&pool, self.acquire_warn_threshold, context).await
+    }
+
+    /// Like [`Self::conn`], but also arms a [`CancelGuard`] so a client
+    /// disconnect mid-query cancels the backend instead of letting the
+    /// query run to completion. Used for the scan-heavy operations where an
+    /// abandoned query can hold resources for a long time.
+    async fn cancellable_conn(
+        &self,
+        context: &'static str,
+    ) -> crate::error::Result<(sqlx::pool::PoolConnection<sqlx::Postgres>, CancelGuard)> {
+        let mut conn = self.conn(context).await?;
+        let guard = CancelGuard::capture(&mut conn, self.pool.current(), context).await?;
+        Ok((conn, guard))
+    }
+}
+
+/// Row shape returned by the upsert query: the user columns plus the
+/// `xmax = 0` insert marker.
+type UpsertRow = (
+    i32,
+    String,
+    EmailAddress,
+    String,
+    DateTime<Utc>,
+    DateTime<Utc>,
+    Option<String>,
+    Option<String>,
+    bool,
+);
+
+/// Whether the error is a Postgres unique-constraint violation (23505).
+fn is_unique_violation(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(db) if db.code().as_deref() == Some("23505"))
+}
+
+#[async_trait]
+impl UserRepository for SqlxUserRepository {
+    async fn create_user(&self, req: CreateUserRequest, actor: &str) -> Result<User> {
+        let mut conn = self.conn("create_user").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"INSERT INTO users (name, email, created_by, updated_by) VALUES ($1, $2, $3, $3)
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+        )
+        .bind(&req.name)
+        .bind(&req.email)
+        .bind(actor)
+        .fetch_one(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        match user {
+            Err(error) if is_unique_violation(&error) => {
+                Err(AppError::Conflict("email already in use".to_string()))
+            }
+            user => Ok(user?),
+        }
+    }
+
+    async fn create_user_or_existing(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        // Loop once: the conflicting row can be deleted between the
+        // failed insert and the fetch, in which case the insert is worth
+        // one more try.
+        for _ in 0..2 {
+            match self.create_user(req.clone(), actor).await {
+                Ok(user) => return Ok((user, true)),
+                Err(AppError::Conflict(_)) => {
+                    if let Some(existing) = self.get_user_by_email(&req.email).await? {
+                        return Ok((existing, false));
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        // Both attempts conflicted with a row the fetch cannot see: the
+        // email belongs to a soft-deleted user.
+        Err(AppError::Conflict("email already in use".to_string()))
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        let mut conn = self.conn("get_user").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by FROM users
+              WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(user?)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        let mut conn = self.conn("get_user_by_email").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by FROM users
+              WHERE LOWER(email) = LOWER($1) AND deleted_at IS NULL",
+        )
+        .bind(email)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(user?)
+    }
+
+    async fn get_users_by_emails(&self, emails: &[String]) -> Result<Vec<User>> {
+        let lowered: Vec<String> = emails.iter().map(|email| email.to_lowercase()).collect();
+        let mut conn = self.conn("get_users_by_emails").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let users = sqlx::query_as::<_, User>(
+            r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by FROM users
+              WHERE LOWER(email) = ANY($1) AND deleted_at IS NULL",
+        )
+        .bind(&lowered)
+        .fetch_all(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(users?)
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<Vec<User>> {
+        let (mut conn, guard) = self.cancellable_conn("list_users").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let mut builder = query.build();
+        let users = builder.build_query_as::<User>().fetch_all(&mut *exec).await;
+        // Disarm on completion (including errors); only a dropped future —
+        // a client disconnect mid-query — leaves the guard to fire.
+        guard.finish();
+        exec.finish().await?;
+
+        Ok(users?)
+    }
+
+    async fn count_users(&self, query: &UserQuery) -> Result<i64> {
+        let (mut conn, guard) = self.cancellable_conn("count_users").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let mut builder = query.build_count();
+        let count: std::result::Result<(i64,), sqlx::Error> =
+            builder.build_query_as().fetch_one(&mut *exec).await;
+        guard.finish();
+        exec.finish().await?;
+
+        Ok(count?.0)
+    }
+
+    async fn collection_version(&self, query: &UserQuery) -> Result<CollectionVersion> {
+        let (mut conn, guard) = self.cancellable_conn("collection_version").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let mut builder = query.build_version();
+        let row: std::result::Result<(Option<DateTime<Utc>>, i64), sqlx::Error> =
+            builder.build_query_as().fetch_one(&mut *exec).await;
+        guard.finish();
+        exec.finish().await?;
+
+        let (latest_updated_at, count) = row?;
+        Ok(CollectionVersion {
+            latest_updated_at,
+            count,
+        })
+    }
+
+    async fn modified_since(&self, since: DateTime<Utc>) -> Result<Vec<User>> {
+        let mut conn = self.conn("modified_since").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        // Cursors arrive in the wire format's millisecond precision;
+        // compare at that granularity so a client resuming from the
+        // `updated_at` it last saw does not re-receive that entry.
+        // One row past the guard is fetched so tripping it is
+        // distinguishable from an exactly-full result.
+        let users = sqlx::query_as::<_, User>(
+            r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by FROM users
+              WHERE date_trunc('milliseconds', updated_at) > date_trunc('milliseconds', $1)
+                AND deleted_at IS NULL
+              ORDER BY updated_at ASC
+              LIMIT $2",
+        )
+        .bind(since)
+        .bind(i64::try_from(self.max_rows.saturating_add(1)).unwrap_or(i64::MAX))
+        .fetch_all(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        let users = users?;
+        crate::repository::check_row_limit("modified_since", users.len(), self.max_rows)?;
+        Ok(users)
+    }
+
+    async fn user_stats(&self) -> Result<UserStats> {
+        let (mut conn, guard) = self.cancellable_conn("user_stats").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let row: std::result::Result<
+            (i64, Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+            sqlx::Error,
+        > = sqlx::query_as(
+            r"SELECT COUNT(*), MIN(created_at), MAX(created_at) FROM users
+                  WHERE deleted_at IS NULL",
+        )
+        .fetch_one(&mut *exec)
+        .await;
+        guard.finish();
+        exec.finish().await?;
+
+        let (count, first_created_at, last_created_at) = row?;
+        Ok(UserStats {
+            count,
+            first_created_at,
+            last_created_at,
+        })
+    }
+
+    async fn update_user(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let mut conn = self.conn("update_user").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"UPDATE users
+              SET name = COALESCE($2, name),
+                  email = COALESCE($3, email),
+                  updated_at = NOW(),
+                  updated_by = $4
+              WHERE id = $1 AND deleted_at IS NULL
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+        )
+        .bind(id)
+        .bind(&req.name)
+        .bind(&req.email)
+        .bind(actor)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        match user {
+            Err(error) if is_unique_violation(&error) => {
+                Err(AppError::Conflict("email already in use".to_string()))
+            }
+            user => Ok(user?),
+        }
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: i32,
+        req: UpdateUserRequest,
+        expected_updated_at: DateTime<Utc>,
+        actor: &str,
+    ) -> Result<Option<User>> {
+        let mut conn = self.conn("update_if_unchanged").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"UPDATE users
+              SET name = COALESCE($2, name),
+                  email = COALESCE($3, email),
+                  updated_at = NOW(),
+                  updated_by = $5
+              WHERE id = $1 AND deleted_at IS NULL
+                AND date_trunc('milliseconds', updated_at) = date_trunc('milliseconds', $4)
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+        )
+        .bind(id)
+        .bind(&req.name)
+        .bind(&req.email)
+        .bind(expected_updated_at)
+        .bind(actor)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        match user {
+            Err(error) if is_unique_violation(&error) => {
+                Err(AppError::Conflict("email already in use".to_string()))
+            }
+            user => Ok(user?),
+        }
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool> {
+        let mut conn = self.conn("delete_user").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let result = sqlx::query(r"DELETE FROM users WHERE id = $1 AND deleted_at IS NULL")
+            .bind(id)
+            .execute(&mut *exec)
+            .await;
+        exec.finish().await?;
+
+        Ok(result?.rows_affected() > 0)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.conn("delete_many").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let result = sqlx::query(r"DELETE FROM users WHERE id = ANY($1) AND deleted_at IS NULL")
+            .bind(ids)
+            .execute(&mut *exec)
+            .await;
+        exec.finish().await?;
+
+        Ok(result?.rows_affected())
+    }
+
+    async fn upsert_user_by_email(
+        &self,
+        req: CreateUserRequest,
+        actor: &str,
+    ) -> Result<(User, bool)> {
+        // `xmax = 0` distinguishes a freshly inserted row from one rewritten
+        // by the conflict update. The `WHERE` keeps soft-deleted rows from
+        // being silently revived through their email.
+        let mut conn = self.conn("upsert_user_by_email").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let row: std::result::Result<Option<UpsertRow>, sqlx::Error> = sqlx::query_as(
+            r"INSERT INTO users (name, email, created_by, updated_by) VALUES ($1, $2, $3, $3)
+              ON CONFLICT (email) DO UPDATE
+              SET name = EXCLUDED.name, updated_at = NOW(), updated_by = EXCLUDED.updated_by
+              WHERE users.deleted_at IS NULL
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by,
+                        (xmax = 0) AS inserted",
+        )
+        .bind(&req.name)
+        .bind(&req.email)
+        .bind(actor)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        match row? {
+            Some(row) => Ok((
+                User {
+                    id: row.0,
+                    name: row.1,
+                    email: row.2,
+                    public_id: row.3,
+                    created_at: row.4,
+                    updated_at: row.5,
+                    created_by: row.6,
+                    updated_by: row.7,
+                },
+                row.8,
+            )),
+            None => Err(AppError::Conflict(
+                "email belongs to a deleted user".to_string(),
+            )),
+        }
+    }
+
+    async fn record_audit(&self, user_id: i32, action: &str) -> Result<()> {
+        let mut conn = self.conn("record_audit").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let result = sqlx::query(r"INSERT INTO audit_log (user_id, action) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(action)
+            .execute(&mut *exec)
+            .await;
+        exec.finish().await?;
+        result?;
+
+        Ok(())
+    }
+
+    async fn audit_entries(&self, user_id: i32) -> Result<Vec<AuditEntry>> {
+        let mut conn = self.conn("audit_entries").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            r"SELECT id, user_id, action, created_at FROM audit_log
+              WHERE user_id = $1
+              ORDER BY id",
+        )
+        .bind(user_id)
+        .fetch_all(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(entries?)
+    }
+
+    async fn wal_token(&self) -> Result<Lsn> {
+        let mut conn = self.conn("wal_token").await?;
+        // The WAL position is cluster-wide, so no tenant scoping applies.
+        let (lsn,): (String,) = sqlx::query_as("SELECT pg_current_wal_lsn()::text")
+            .fetch_one(&mut *conn)
+            .await?;
+        lsn.parse()
+    }
+
+    async fn wal_caught_up(&self, token: Lsn) -> Result<bool> {
+        let mut conn = self.conn("wal_caught_up").await?;
+        // NULL replay position means this is the primary: trivially caught
+        // up.
+        let (caught_up,): (bool,) =
+            sqlx::query_as("SELECT COALESCE(pg_last_wal_replay_lsn() >= $1::pg_lsn, true)")
+                .bind(token.to_string())
+                .fetch_one(&mut *conn)
+                .await?;
+        Ok(caught_up)
+    }
+
+    async fn user_history(&self, user_id: i32) -> Result<Vec<UserAuditEntry>> {
+        let mut conn = self.conn("user_history").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let entries = sqlx::query_as::<_, UserAuditEntry>(
+            r"SELECT id, user_id, action, changed_at, old_row, new_row FROM user_audit
+              WHERE user_id = $1
+              ORDER BY id",
+        )
+        .bind(user_id)
+        .fetch_all(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(entries?)
+    }
+
+    async fn set_user_avatar(&self, user_id: i32, avatar: Avatar) -> Result<Option<()>> {
+        // Blob insert and pointer update go together; the ON CONFLICT makes
+        // re-uploading existing bytes point at the shared row.
+        let mut conn = self.conn("set_user_avatar").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        sqlx::query(
+            r"INSERT INTO user_avatars (hash, content_type, bytes) VALUES ($1, $2, $3)
+              ON CONFLICT (hash) DO NOTHING",
+        )
+        .bind(&avatar.hash)
+        .bind(&avatar.content_type)
+        .bind(&avatar.bytes)
+        .execute(&mut *tx)
+        .await?;
+        let updated =
+            sqlx::query(r"UPDATE users SET avatar_hash = $2 WHERE id = $1 AND deleted_at IS NULL")
+                .bind(user_id)
+                .bind(&avatar.hash)
+                .execute(&mut *tx)
+                .await?;
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        tx.commit().await?;
+        Ok(Some(()))
+    }
+
+    async fn user_avatar(&self, user_id: i32) -> Result<Option<Avatar>> {
+        let mut conn = self.conn("user_avatar").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let avatar = sqlx::query_as::<_, Avatar>(
+            r"SELECT a.hash, a.content_type, a.bytes
+              FROM users u
+              JOIN user_avatars a ON a.hash = u.avatar_hash
+              WHERE u.id = $1 AND u.deleted_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(avatar?)
+    }
+
+    async fn set_user_tags(&self, user_id: i32, tags: &[String]) -> Result<Option<Vec<String>>> {
+        // Replacing the set is a delete plus inserts, so run it in its own
+        // transaction to keep concurrent readers from seeing the user
+        // momentarily untagged.
+        let mut conn = self.conn("set_user_tags").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        let exists: Option<(i32,)> =
+            sqlx::query_as(r"SELECT id FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+                .bind(user_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        sqlx::query(r"DELETE FROM user_tags WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r"INSERT INTO tags (name) SELECT unnest($1::text[])
+              ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(tags)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r"INSERT INTO user_tags (user_id, tag_id)
+              SELECT $1, id FROM tags WHERE name = ANY($2)",
+        )
+        .bind(user_id)
+        .bind(tags)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(tags.to_vec()))
+    }
+
+    async fn user_tags(&self, user_id: i32) -> Result<Option<Vec<String>>> {
+        let mut conn = self.conn("user_tags").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let exists: std::result::Result<Option<(i32,)>, sqlx::Error> =
+            sqlx::query_as(r"SELECT id FROM users WHERE id = $1 AND deleted_at IS NULL")
+                .bind(user_id)
+                .fetch_optional(&mut *exec)
+                .await;
+        let tags = match exists {
+            Ok(Some(_)) => sqlx::query_as::<_, (String,)>(
+                r"SELECT t.name FROM tags t
+                      JOIN user_tags ut ON ut.tag_id = t.id
+                      WHERE ut.user_id = $1
+                      ORDER BY t.name",
+            )
+            .bind(user_id)
+            .fetch_all(&mut *exec)
+            .await
+            .map(|rows| Some(rows.into_iter().map(|(name,)| name).collect())),
+            Ok(None) => Ok(None),
+            Err(error) => Err(error),
+        };
+        exec.finish().await?;
+
+        Ok(tags?)
+    }
+
+    async fn merge_users(&self, primary_id: i32, duplicate_id: i32, actor: &str) -> Result<User> {
+        let mut conn = self.conn("merge_users").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        // Lock both rows up front so a concurrent merge or update cannot
+        // interleave with the re-pointing below.
+        let primary = lock_user_for_merge(&mut tx, primary_id).await?;
+        let duplicate = lock_user_for_merge(&mut tx, duplicate_id).await?;
+
+        sqlx::query(r"UPDATE audit_log SET user_id = $1 WHERE user_id = $2")
+            .bind(primary_id)
+            .bind(duplicate_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Precedence: the primary keeps its email (it stays unique on the
+        // duplicate's soft-deleted row); other fields come from whichever
+        // row was updated most recently.
+        let merged = if duplicate.updated_at > primary.updated_at {
+            sqlx::query_as::<_, User>(
+                r"UPDATE users
+                  SET name = $2, updated_at = NOW(), updated_by = $3
+                  WHERE id = $1
+                  RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+            )
+            .bind(primary_id)
+            .bind(&duplicate.name)
+            .bind(actor)
+            .fetch_one(&mut *tx)
+            .await?
+        } else {
+            primary
+        };
+
+        sqlx::query(
+            r"UPDATE users SET deleted_at = NOW(), updated_at = NOW(), updated_by = $2
+              WHERE id = $1",
+        )
+        .bind(duplicate_id)
+        .bind(actor)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(r"INSERT INTO audit_log (user_id, action) VALUES ($1, $2)")
+            .bind(duplicate_id)
+            .bind(format!("merged_into:{primary_id}"))
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(merged)
+    }
+
+    async fn import_users_with_ids(
+        &self,
+        rows: &[ImportUserRecord],
+        actor: &str,
+    ) -> Result<Vec<ImportRowError>> {
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.conn("import_users_with_ids").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        // `id` is a plain SERIAL, so explicit values insert directly; the
+        // conflict pre-checks run inside the same transaction and therefore
+        // see the rows imported earlier in the batch.
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut seen_emails = std::collections::HashSet::new();
+        let mut errors = Vec::new();
+        let mut imported = 0u64;
+        for row in rows {
+            if !seen_ids.insert(row.id) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "duplicate id in import batch".to_string(),
+                });
+                continue;
+            }
+            if !seen_emails.insert(row.email.to_lowercase()) {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "duplicate email in import batch".to_string(),
+                });
+                continue;
+            }
+            // Soft-deleted rows keep their id and email, so both checks
+            // run against the whole table, not just surviving users.
+            let (id_taken, email_taken): (bool, bool) = sqlx::query_as(
+                r"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1),
+                         EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($2))",
+            )
+            .bind(row.id)
+            .bind(&row.email)
+            .fetch_one(&mut *tx)
+            .await?;
+            if id_taken {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "id already in use".to_string(),
+                });
+                continue;
+            }
+            if email_taken {
+                errors.push(ImportRowError {
+                    id: row.id,
+                    error: "email already in use".to_string(),
+                });
+                continue;
+            }
+
+            sqlx::query(
+                r"INSERT INTO users (id, name, email, created_by, updated_by)
+                  VALUES ($1, $2, $3, $4, $4)",
+            )
+            .bind(row.id)
+            .bind(&row.name)
+            .bind(&row.email)
+            .bind(actor)
+            .execute(&mut *tx)
+            .await?;
+            imported += 1;
+        }
+
+        // The critical step: leave the sequence past every imported id so
+        // the next plain INSERT cannot hit a duplicate key.
+        if imported > 0 {
+            sqlx::query(
+                r"SELECT setval(pg_get_serial_sequence('users', 'id'),
+                                (SELECT MAX(id) FROM users))",
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(errors)
+    }
+
+    async fn request_email_change(
+        &self,
+        user_id: i32,
+        new_email: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<()>> {
+        let mut conn = self.conn("request_email_change").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        // Lock the row so a concurrent request for the same user cannot
+        // interleave between the token sweep and the insert below.
+        let exists: Option<(i32,)> =
+            sqlx::query_as(r"SELECT id FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+                .bind(user_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        // Soft-deleted rows keep their email, so the availability check
+        // runs against the whole table, like the import pre-checks.
+        let (taken,): (bool,) =
+            sqlx::query_as(r"SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))")
+                .bind(new_email)
+                .fetch_one(&mut *tx)
+                .await?;
+        if taken {
+            return Err(AppError::Conflict("email already in use".to_string()));
+        }
+
+        // A new request supersedes any earlier one: its tokens die here.
+        sqlx::query(r"DELETE FROM email_change_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        // `updated_at` stays put: nothing client-visible has changed yet.
+        sqlx::query(r"UPDATE users SET pending_email = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(new_email)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r"INSERT INTO email_change_tokens (token_hash, user_id, new_email, expires_at)
+              VALUES ($1, $2, $3, $4)",
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(new_email)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(()))
+    }
+
+    async fn confirm_email_change(&self, token_hash: &str, actor: &str) -> Result<User> {
+        let mut conn = self.conn("confirm_email_change").await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        if let Some(schema) = &self.schema {
+            sqlx::Executor::execute(
+                &mut *tx,
+                format!(r#"SET LOCAL search_path TO "{schema}""#).as_str(),
+            )
+            .await?;
+        }
+
+        // Deleting up front is what makes the token single-use: a second
+        // presentation finds nothing, whatever became of the first.
+        let token: Option<(i32, String, DateTime<Utc>)> = sqlx::query_as(
+            r"DELETE FROM email_change_tokens WHERE token_hash = $1
+              RETURNING user_id, new_email, expires_at",
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((user_id, new_email, expires_at)) = token else {
+            return Err(AppError::NotFound);
+        };
+        if expires_at <= Utc::now() {
+            // Commit so the dead token is swept rather than rolled back.
+            tx.commit().await?;
+            return Err(AppError::http(
+                axum::http::StatusCode::GONE,
+                "confirmation token has expired",
+            ));
+        }
+
+        // A registration may have claimed the address since the request; a
+        // conflict rolls back, keeping the token usable until it expires.
+        let (taken,): (bool,) = sqlx::query_as(
+            r"SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1) AND id <> $2)",
+        )
+        .bind(&new_email)
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if taken {
+            return Err(AppError::Conflict("email already in use".to_string()));
+        }
+
+        let user = sqlx::query_as::<_, User>(
+            r"UPDATE users
+              SET email = $2, pending_email = NULL, updated_at = NOW(), updated_by = $3
+              WHERE id = $1 AND deleted_at IS NULL
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+        )
+        .bind(user_id)
+        .bind(&new_email)
+        .bind(actor)
+        .fetch_optional(&mut *tx)
+        .await;
+        let user = match user {
+            // The unique index is the last line of defense against a
+            // registration racing past the availability check above.
+            Err(error) if is_unique_violation(&error) => {
+                return Err(AppError::Conflict("email already in use".to_string()))
+            }
+            user => user?,
+        };
+        let Some(user) = user else {
+            // The user was soft-deleted after requesting the change.
+            return Err(AppError::NotFound);
+        };
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_user_by_public_id(&self, public_id: &str) -> Result<Option<User>> {
+        let mut conn = self.conn("get_user_by_public_id").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        // The handler has already format-checked the value, so the cast
+        // cannot throw.
+        let user = sqlx::query_as::<_, User>(
+            r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by FROM users
+              WHERE public_id = $1::uuid AND deleted_at IS NULL",
+        )
+        .bind(public_id)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(user?)
+    }
+
+    async fn rotate_public_id(&self, id: i32, actor: &str) -> Result<Option<User>> {
+        let mut conn = self.conn("rotate_public_id").await?;
+        let mut exec = self.scope(&mut conn).await?;
+        let user = sqlx::query_as::<_, User>(
+            r"UPDATE users
+              SET public_id = gen_random_uuid(), updated_at = NOW(), updated_by = $2
+              WHERE id = $1 AND deleted_at IS NULL
+              RETURNING id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by",
+        )
+        .bind(id)
+        .bind(actor)
+        .fetch_optional(&mut *exec)
+        .await;
+        exec.finish().await?;
+
+        Ok(user?)
+    }
+}
+
+/// Lock a merge participant's row, rejecting missing (404) and soft-deleted
+/// (422) users before any re-pointing happens.
+async fn lock_user_for_merge(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: i32,
+) -> Result<User> {
+    #[allow(clippy::type_complexity)]
+    let row: Option<(User, Option<DateTime<Utc>>)> = sqlx::query_as::<
+        _,
+        (
+            i32,
+            String,
+            EmailAddress,
+            String,
+            DateTime<Utc>,
+            DateTime<Utc>,
+            Option<String>,
+            Option<String>,
+            Option<DateTime<Utc>>,
+        ),
+    >(
+        r"SELECT id, name, email, public_id::text AS public_id, created_at, updated_at, created_by, updated_by, deleted_at
+          FROM users
+          WHERE id = $1
+          FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .map(
+        |(id, name, email, public_id, created_at, updated_at, created_by, updated_by, deleted_at)| {
+            (
+                User {
+                    id,
+                    name,
+                    email,
+                    public_id,
+                    created_at,
+                    updated_at,
+                    created_by,
+                    updated_by,
+                },
+                deleted_at,
+            )
+        },
+    );
+
+    match row {
+        None => Err(AppError::NotFound),
+        Some((_, Some(_))) => Err(AppError::http(
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            format!("user {id} is deleted and cannot take part in a merge"),
+        )),
+        Some((user, None)) => Ok(user),
+    }
+}
@@ -0,0 +1,939 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Admin, RequireScope};
+use crate::error::{AppError, Result};
+use crate::models::{self, User};
+use crate::repository;
+use crate::AppState;
+
+/// Response body for `POST /admin/pool/recycle`.
+#[derive(Debug, Serialize)]
+pub struct RecycleResponse {
+    /// Connections held by the pool that was closed.
+    pub closed_connections: u32,
+    /// Connections already warmed in the replacement pool.
+    pub warmed_connections: u32,
+}
+
+/// POST /admin/pool/recycle
+///
+/// Swap the live connection pool for a freshly created one, e.g. after a
+/// Postgres failover left pooled connections pointing at the old primary.
+/// In-flight queries keep their clone of the old pool and finish normally;
+/// the old pool is then closed gracefully.
+pub async fn recycle_pool(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Result<Json<RecycleResponse>> {
+    let Some(handle) = &state.db else {
+        tracing::error!("pool recycle requested but no database pool is attached");
+        return Err(AppError::Internal);
+    };
+
+    let new_pool = repository::create_pool(&state.config).await?;
+    let warmed_connections = new_pool.size();
+
+    let old_pool = handle.replace(new_pool);
+    let closed_connections = old_pool.size();
+    tracing::info!(
+        closed_connections,
+        warmed_connections,
+        "recycling connection pool"
+    );
+    old_pool.close().await;
+
+    Ok(Json(RecycleResponse {
+        closed_connections,
+        warmed_connections,
+    }))
+}
+
+/// GET /admin/routes
+///
+/// Machine-readable manifest of every registered route, for generating
+/// gateway configuration. Rendered from the same typed route table the
+/// router is built from, with the configured base path applied.
+pub async fn route_manifest(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Json<Vec<crate::routes::ManifestRoute>> {
+    Json(crate::routes::manifest(&state.config.base_path))
+}
+
+/// Query parameters accepted by `GET /admin/usage`.
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// Only count buckets at or after this RFC 3339 timestamp; defaults to
+    /// the whole retained window.
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// GET /admin/usage
+///
+/// Per-caller request counts and error rates from the in-memory rolling
+/// window, for quick per-tenant usage questions without a metrics stack.
+pub async fn usage_summary(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<UsageQuery>,
+) -> Json<Vec<crate::middleware::usage::CallerUsage>> {
+    let since = query
+        .since
+        .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+    Json(state.usage.summary(since))
+}
+
+/// GET /admin/webhooks/dead-letters
+///
+/// Webhook events abandoned after passing their delivery deadline, oldest
+/// first, so an operator can see what a receiver outage cost and replay
+/// events out-of-band if needed.
+pub async fn webhook_dead_letters(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Json<Vec<crate::webhooks::DeadLetter>> {
+    Json(state.webhooks.dead_letters())
+}
+
+/// GET /admin/slo
+///
+/// Current burn rate and remaining error budget per route class, over
+/// 5-minute and 1-hour windows, against the `SLO_AVAILABILITY` target.
+/// The `degraded` flag here is the one mirrored into `/health/ready`.
+pub async fn slo_status(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Json<crate::middleware::slo::SloReport> {
+    Json(crate::middleware::slo::report(
+        &state.slo,
+        state.config.slo_availability,
+    ))
+}
+
+/// GET /admin/rate-limits
+///
+/// The effective per-minute limit for every rate-limit class, with the
+/// source of each value (`env` or `runtime`) so an operator can tell
+/// whether an override is in effect.
+pub async fn show_rate_limits(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Json<std::collections::BTreeMap<&'static str, crate::middleware::rate_limit::ClassLimit>> {
+    Json((*state.rate_limits.current()).clone())
+}
+
+/// PATCH /admin/rate-limits
+///
+/// Apply partial limit overrides, keyed by class name. Values are
+/// validated against sane bounds and unknown classes are rejected.
+/// Overrides are process-local and ephemeral: they do not survive a
+/// restart and must be applied to each replica separately.
+pub async fn update_rate_limits(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    Json(overrides): Json<std::collections::BTreeMap<String, u32>>,
+) -> Result<Json<std::collections::BTreeMap<&'static str, crate::middleware::rate_limit::ClassLimit>>>
+{
+    state.rate_limits.apply_overrides(
+        overrides
+            .iter()
+            .map(|(class, per_minute)| (class.as_str(), *per_minute)),
+    )?;
+    tracing::info!(?overrides, "applied runtime rate limit overrides");
+    Ok(Json((*state.rate_limits.current()).clone()))
+}
+
+/// Response body for `POST /admin/cors/reload`.
+#[derive(Debug, Serialize)]
+pub struct CorsReloadResponse {
+    /// The origin set now in effect, sorted.
+    pub allowed_origins: Vec<String>,
+}
+
+/// POST /admin/cors/reload
+///
+/// Re-read `CORS_ALLOWED_ORIGINS` from the process environment and swap
+/// the allowed-origin set atomically, so a frontend added to the
+/// deployment's environment is accepted without a restart. Only origins
+/// are hot-reloadable; the methods and headers the CORS layer grants are
+/// fixed at build time.
+pub async fn reload_cors(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+) -> Json<CorsReloadResponse> {
+    let origins = crate::config::env_list(crate::middleware::cors::ORIGINS_ENV);
+    let snapshot = state.cors.replace(origins);
+    let allowed_origins: Vec<String> = snapshot.iter().cloned().collect();
+    tracing::info!(?allowed_origins, "reloaded CORS allowed origins");
+    Json(CorsReloadResponse { allowed_origins })
+}
+
+/// POST /admin/chaos (cargo feature `chaos` only)
+///
+/// Configure the fault-injection layer at runtime: artificial latency
+/// and/or a percentage of injected failures on matching routes. Posting
+/// an empty object `{}` turns injection off entirely. Like the rate-limit
+/// overrides, settings are process-local and ephemeral.
+#[cfg(feature = "chaos")]
+pub async fn configure_chaos(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    Json(settings): Json<crate::middleware::chaos::ChaosSettings>,
+) -> Result<Json<crate::middleware::chaos::ChaosSettings>> {
+    if settings.error_rate > 100 {
+        return Err(AppError::Validation(
+            "error_rate must be between 0 and 100".to_string(),
+        ));
+    }
+    if let Some(status) = settings.error_status {
+        if status != 500 && status != 503 {
+            return Err(AppError::Validation(
+                "error_status must be 500 or 503".to_string(),
+            ));
+        }
+    }
+
+    tracing::warn!(?settings, "chaos: applying injection settings");
+    let applied = state.chaos.replace(settings);
+    Ok(Json((*applied).clone()))
+}
+
+/// Request body for `POST /admin/users/delete`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteUsersRequest {
+    pub ids: Vec<i32>,
+}
+
+impl DeleteUsersRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["ids"];
+}
+
+/// Response body for `POST /admin/users/delete`.
+#[derive(Debug, Serialize)]
+pub struct DeleteUsersResponse {
+    /// Rows actually removed; ids that did not match a live user are
+    /// skipped silently.
+    pub deleted: u64,
+}
+
+/// POST /admin/users/delete
+///
+/// Batch cleanup: delete every listed user in one statement. An empty id
+/// list is a no-op returning zero.
+pub async fn delete_users(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    tenant: crate::middleware::Tenant,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<DeleteUsersResponse>> {
+    let req: DeleteUsersRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        DeleteUsersRequest::FIELDS,
+    )?;
+
+    let deleted = state
+        .repository_for(tenant.0.as_ref())
+        .delete_many(&req.ids)
+        .await?;
+    tracing::info!(requested = req.ids.len(), deleted, "batch-deleted users");
+    Ok(Json(DeleteUsersResponse { deleted }))
+}
+
+/// Request body for `POST /admin/users/merge`.
+#[derive(Debug, Deserialize)]
+pub struct MergeUsersRequest {
+    pub primary_id: i32,
+    pub duplicate_id: i32,
+}
+
+impl MergeUsersRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["primary_id", "duplicate_id"];
+}
+
+/// POST /admin/users/merge
+///
+/// Merge a duplicate registration into its canonical user: dependent audit
+/// log rows are re-pointed to the primary, the duplicate is soft-deleted
+/// with a `merged_into:<primary>` audit entry, and the merged primary is
+/// returned. Merging a user into itself, or involving an already-deleted
+/// user, is rejected with 422.
+pub async fn merge_users(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    tenant: crate::middleware::Tenant,
+    caller: crate::auth::Caller,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<User>> {
+    let req: MergeUsersRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        MergeUsersRequest::FIELDS,
+    )?;
+    if req.primary_id == req.duplicate_id {
+        return Err(AppError::http(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "cannot merge a user into itself",
+        ));
+    }
+
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .merge_users(req.primary_id, req.duplicate_id, &caller.principal)
+        .await?;
+    tracing::info!(
+        primary_id = req.primary_id,
+        duplicate_id = req.duplicate_id,
+        "merged duplicate user"
+    );
+    Ok(Json(user))
+}
+
+/// Request body for `POST /admin/users/import-with-ids`.
+#[derive(Debug, Deserialize)]
+pub struct ImportUsersRequest {
+    pub users: Vec<crate::models::ImportUserRecord>,
+}
+
+impl ImportUsersRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["users"];
+}
+
+/// Response body for `POST /admin/users/import-with-ids`.
+#[derive(Debug, Serialize)]
+pub struct ImportUsersResponse {
+    /// Rows inserted with their legacy id preserved.
+    pub imported: u64,
+    /// Rows skipped because their id or email was already taken, keyed by
+    /// the offending id.
+    pub errors: Vec<crate::models::ImportRowError>,
+}
+
+/// POST /admin/users/import-with-ids
+///
+/// Migration helper: bulk-create users while preserving their ids from the
+/// legacy system. Rows import in one transaction; conflicting ids or
+/// emails are skipped and reported per row rather than failing the batch,
+/// and the id sequence is advanced past the largest imported id so
+/// subsequent `POST /users` calls cannot hit a duplicate key.
+pub async fn import_users_with_ids(
+    _scope: RequireScope<Admin>,
+    State(state): State<AppState>,
+    tenant: crate::middleware::Tenant,
+    caller: crate::auth::Caller,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<ImportUsersResponse>> {
+    let req: ImportUsersRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        ImportUsersRequest::FIELDS,
+    )?;
+    for record in &req.users {
+        record.validate()?;
+    }
+
+    let errors = state
+        .repository_for(tenant.0.as_ref())
+        .import_users_with_ids(&req.users, &caller.principal)
+        .await?;
+    let imported = (req.users.len() - errors.len()) as u64;
+    tracing::info!(
+        requested = req.users.len(),
+        imported,
+        rejected = errors.len(),
+        "imported users with explicit ids"
+    );
+    Ok(Json(ImportUsersResponse { imported, errors }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+
+    use crate::repository::PoolHandle;
+    use crate::test_helpers::{test_app, test_state};
+
+    fn lazy_pool() -> sqlx::PgPool {
+        PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/recycle_test")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn replace_swaps_pool_and_old_one_closes() {
+        let handle = PoolHandle::new(lazy_pool());
+
+        let old = handle.replace(lazy_pool());
+        old.close().await;
+
+        assert!(old.is_closed());
+        assert!(!handle.current().is_closed());
+    }
+
+    async fn body_json(response: axum::response::Response) -> serde_json::Value {
+        use http_body_util::BodyExt;
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn manifest_matches_the_mounted_router() {
+        let app = test_app(test_state());
+
+        for route in crate::routes::manifest("") {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("OPTIONS")
+                        .uri(route.path.replace(":id", "1"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_ne!(
+                response.status(),
+                StatusCode::NOT_FOUND,
+                "manifest names an unmounted route: {} {}",
+                route.method,
+                route.path
+            );
+            let allow = response
+                .headers()
+                .get("allow")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            assert!(
+                allow.contains(route.method),
+                "{} is not served on {} (allow: {allow})",
+                route.method,
+                route.path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn route_manifest_applies_the_base_path() {
+        let mut state = test_state();
+        state.config.base_path = "/api".to_string();
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/admin/routes")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        let routes = body.as_array().unwrap();
+        assert!(!routes.is_empty());
+        for route in routes {
+            assert!(
+                route["path"].as_str().unwrap().starts_with("/api/"),
+                "manifest path missing base path: {route}"
+            );
+        }
+    }
+
+    /// The chaos endpoint must not exist in default builds: no route, no
+    /// manifest entry.
+    #[cfg(not(feature = "chaos"))]
+    #[tokio::test]
+    async fn chaos_endpoint_is_absent_without_the_feature() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/chaos")
+                    .header("content-type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!crate::routes::manifest("")
+            .iter()
+            .any(|route| route.path == "/admin/chaos"));
+    }
+
+    /// Test state that also hands back the concrete memory repository so
+    /// tests can seed users and inspect audit entries directly.
+    fn state_with_repository() -> (
+        crate::AppState,
+        std::sync::Arc<crate::repository::MemoryUserRepository>,
+    ) {
+        let repository = std::sync::Arc::new(crate::repository::MemoryUserRepository::new());
+        let mut state = test_state();
+        state.repository = repository.clone();
+        (state, repository)
+    }
+
+    async fn seed_user(
+        repository: &crate::repository::MemoryUserRepository,
+        name: &str,
+        email: &str,
+    ) -> i32 {
+        use crate::repository::UserRepository;
+        repository
+            .create_user(
+                crate::models::CreateUserRequest {
+                    name: name.to_string(),
+                    email: email.to_string(),
+                },
+                "seed",
+            )
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn merge_request(primary_id: i32, duplicate_id: i32) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/admin/users/merge")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"primary_id":{primary_id},"duplicate_id":{duplicate_id}}}"#
+            )))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merge_repoints_audit_and_soft_deletes_duplicate() {
+        use crate::repository::UserRepository;
+
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let primary = seed_user(&repository, "Primary", "primary@example.com").await;
+        let duplicate = seed_user(&repository, "Duplicate", "duplicate@example.com").await;
+        repository.record_audit(primary, "login").await.unwrap();
+        repository.record_audit(duplicate, "login").await.unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(merge_request(primary, duplicate))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The duplicate's audit entries now hang off the primary; the
+        // duplicate keeps only the merge marker and is gone from the API.
+        let primary_audit = repository.audit_entries(primary).await.unwrap();
+        assert_eq!(primary_audit.len(), 2);
+        let duplicate_audit = repository.audit_entries(duplicate).await.unwrap();
+        assert_eq!(duplicate_audit.len(), 1);
+        assert_eq!(duplicate_audit[0].action, format!("merged_into:{primary}"));
+        assert!(repository.get_user(duplicate).await.unwrap().is_none());
+
+        // The duplicate was created later, so its name wins the merge.
+        let merged = repository.get_user(primary).await.unwrap().unwrap();
+        assert_eq!(merged.name, "Duplicate");
+        assert_eq!(merged.email, "primary@example.com");
+    }
+
+    /// A merged-into primary carries the duplicate's re-pointed audit
+    /// rows; deleting it must cascade through them (migration 011) rather
+    /// than trip the foreign key and mask as a 500.
+    #[tokio::test]
+    async fn a_merged_into_user_can_still_be_deleted() {
+        use crate::repository::UserRepository;
+
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let primary = seed_user(&repository, "Primary", "primary@example.com").await;
+        let duplicate = seed_user(&repository, "Duplicate", "duplicate@example.com").await;
+        repository.record_audit(duplicate, "login").await.unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(merge_request(primary, duplicate))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert!(repository.delete_user(primary).await.unwrap());
+        assert!(repository.get_user(primary).await.unwrap().is_none());
+        assert!(repository.audit_entries(primary).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn merging_a_user_into_itself_is_rejected() {
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let id = seed_user(&repository, "Only", "only@example.com").await;
+
+        let response = app.oneshot(merge_request(id, id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn merging_into_a_deleted_user_is_rejected() {
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let primary = seed_user(&repository, "Primary", "primary@example.com").await;
+        let duplicate = seed_user(&repository, "Duplicate", "duplicate@example.com").await;
+        let other = seed_user(&repository, "Other", "other@example.com").await;
+
+        let response = app
+            .clone()
+            .oneshot(merge_request(primary, duplicate))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The duplicate is now soft-deleted and cannot absorb another user.
+        let response = app.oneshot(merge_request(duplicate, other)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn merging_a_missing_user_is_not_found() {
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let primary = seed_user(&repository, "Primary", "primary@example.com").await;
+
+        let response = app.oneshot(merge_request(primary, 999)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn delete_request(ids: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/admin/users/delete")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"ids":{ids}}}"#)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_delete_removes_a_subset_and_reports_the_count() {
+        use crate::repository::UserRepository;
+
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let a = seed_user(&repository, "A", "a@example.com").await;
+        let b = seed_user(&repository, "B", "b@example.com").await;
+        let c = seed_user(&repository, "C", "c@example.com").await;
+
+        // One missing id in the batch is skipped, not an error.
+        let response = app
+            .clone()
+            .oneshot(delete_request(&format!("[{a},{b},999]")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["deleted"], 2);
+
+        assert!(repository.get_user(a).await.unwrap().is_none());
+        assert!(repository.get_user(b).await.unwrap().is_none());
+        assert!(repository.get_user(c).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn batch_delete_of_nothing_is_a_no_op() {
+        let (state, _repository) = state_with_repository();
+        let app = test_app(state);
+
+        let response = app.oneshot(delete_request("[]")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["deleted"], 0);
+    }
+
+    fn import_request(users: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/admin/users/import-with-ids")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"users":{users}}}"#)))
+            .unwrap()
+    }
+
+    /// The import route carries its own, larger body cap: a payload that
+    /// sails through it is rejected with 413 on single-create, whose
+    /// global `MAX_BODY_BYTES` stays tight.
+    #[tokio::test]
+    async fn bulk_route_accepts_bodies_the_global_limit_rejects() {
+        let (mut state, _repository) = state_with_repository();
+        state.config.max_body_bytes = 1_024;
+        state.config.bulk_max_body_bytes = 1_048_576;
+        let app = test_app(state);
+
+        let rows: Vec<String> = (2000..=2040)
+            .map(|id| {
+                format!(r#"{{"id":{id},"name":"Legacy {id}","email":"legacy{id}@example.com"}}"#)
+            })
+            .collect();
+        let body = format!("[{}]", rows.join(","));
+        assert!(body.len() > 1_024, "payload must exceed the global cap");
+
+        let response = app.clone().oneshot(import_request(&body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let padded_name = "x".repeat(2_000);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"name":"{padded_name}","email":"large@example.com"}}"#
+                    )))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// The critical migration property: after importing explicit ids the
+    /// sequence has moved past them, so a normal create gets the next free
+    /// id instead of a duplicate-key error.
+    #[tokio::test]
+    async fn plain_create_after_an_import_continues_past_the_imported_ids() {
+        let (state, _repository) = state_with_repository();
+        let app = test_app(state);
+
+        let rows: Vec<String> = (1000..=1010)
+            .map(|id| {
+                format!(r#"{{"id":{id},"name":"Legacy {id}","email":"legacy{id}@example.com"}}"#)
+            })
+            .collect();
+        let response = app
+            .clone()
+            .oneshot(import_request(&format!("[{}]", rows.join(","))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["imported"], 11);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 0);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name": "New", "email": "new@example.com"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["id"], 1011);
+    }
+
+    #[tokio::test]
+    async fn conflicting_import_rows_are_reported_and_the_rest_import() {
+        use crate::repository::UserRepository;
+
+        let (state, repository) = state_with_repository();
+        let app = test_app(state);
+        let existing = seed_user(&repository, "Existing", "existing@example.com").await;
+
+        let response = app
+            .oneshot(import_request(&format!(
+                r#"[{{"id":{existing},"name":"Clash","email":"clash@example.com"}},
+                    {{"id":50,"name":"Email Clash","email":"EXISTING@example.com"}},
+                    {{"id":60,"name":"First","email":"first@example.com"}},
+                    {{"id":60,"name":"Second","email":"second@example.com"}},
+                    {{"id":70,"name":"Clean","email":"clean@example.com"}}]"#
+            )))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["imported"], 2);
+        let errors = body["errors"].as_array().unwrap();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0]["id"], existing);
+        assert_eq!(errors[0]["error"], "id already in use");
+        assert_eq!(errors[1]["id"], 50);
+        assert_eq!(errors[1]["error"], "email already in use");
+        assert_eq!(errors[2]["id"], 60);
+        assert_eq!(errors[2]["error"], "duplicate id in import batch");
+
+        assert!(repository.get_user(60).await.unwrap().is_some());
+        assert!(repository.get_user(70).await.unwrap().is_some());
+        assert!(repository.get_user(50).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn import_rows_are_validated_like_regular_creates() {
+        let (state, _repository) = state_with_repository();
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(import_request(
+                r#"[{"id":0,"name":"Bad Id","email":"bad@example.com"}]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn recycle_without_database_pool_is_an_error() {
+        // Test state is memory-backed and carries no pool handle.
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/pool/recycle")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    fn patch_limits(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("PATCH")
+            .uri("/admin/rate-limits")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rate_limits_adjust_at_runtime_without_rebuilding_the_router() {
+        let mut state = test_state();
+        state.config.rate_limit_public_read = 2;
+        state.rate_limits =
+            std::sync::Arc::new(crate::middleware::RateLimits::from_config(&state.config));
+        let app = test_app(state);
+        let list = || {
+            Request::builder()
+                .uri("/users")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(list()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = app.clone().oneshot(list()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // The configured value shows as env-sourced.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/rate-limits")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = body_json(response).await;
+        assert_eq!(body["public_read"]["per_minute"], 2);
+        assert_eq!(body["public_read"]["source"], "env");
+
+        // Raising the limit takes effect on the very next request.
+        let response = app
+            .clone()
+            .oneshot(patch_limits(r#"{"public_read": 100}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["public_read"]["per_minute"], 100);
+        assert_eq!(body["public_read"]["source"], "runtime");
+
+        let response = app.oneshot(list()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_overrides_are_validated() {
+        let app = test_app(test_state());
+
+        for body in [
+            r#"{"public_read": 0}"#,
+            r#"{"public_read": 2000000}"#,
+            r#"{"no_such_class": 10}"#,
+        ] {
+            let response = app.clone().oneshot(patch_limits(body)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "body: {body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn dead_lettered_webhook_events_are_listed() {
+        let mut state = test_state();
+        state.config.webhook_url = Some("http://hook.test/events".to_string());
+        let webhooks = state.webhooks.clone();
+        let app = test_app(state);
+
+        // Produce an event through the API so the trace id is captured from
+        // the request, then expire it without a delivery attempt.
+        let trace = "0af7651916cd43dd8448eb211c80319c";
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .header("traceparent", format!("00-{trace}-b7ad6b7169203331-01"))
+                    .body(Body::from(
+                        r#"{"name": "Hooked", "email": "hooked@example.com"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(webhooks.pending(), 1);
+
+        let past_deadline = chrono::Utc::now() + chrono::Duration::seconds(301);
+        webhooks
+            .deliver_due(
+                &crate::webhooks::HttpWebhookSender,
+                "http://hook.test/events",
+                std::time::Duration::from_secs(300),
+                past_deadline,
+            )
+            .await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/webhooks/dead-letters")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["event"]["kind"], "user.created");
+        assert_eq!(body[0]["event"]["trace_id"], trace);
+        assert_eq!(body[0]["reason"], "delivery deadline exceeded");
+    }
+}
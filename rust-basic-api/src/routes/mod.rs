@@ -0,0 +1,622 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{delete, get, patch, post, put, MethodRouter};
+use serde::Serialize;
+
+use crate::auth::scopes;
+use crate::middleware::rate_limit::classes;
+use crate::AppState;
+
+pub mod admin;
+pub mod user_routes;
+
+#[cfg(feature = "chaos")]
+pub use admin::configure_chaos;
+pub use admin::{
+    delete_users, import_users_with_ids, merge_users, recycle_pool, reload_cors, route_manifest,
+    show_rate_limits, slo_status, update_rate_limits, usage_summary, webhook_dead_letters,
+};
+pub use user_routes::{
+    confirm_email_change, create_user, delete_user, get_user, get_user_avatar, get_user_by_email,
+    get_user_by_public_id, get_user_history, get_user_stats, get_user_tags, list_changed_users,
+    list_users, lookup_users, request_email_change, rotate_public_id, set_user_avatar,
+    set_user_tags, update_user, upsert_user,
+};
+
+/// Typed description of one registered route.
+///
+/// The router is built from [`route_table`], and `GET /admin/routes` (plus
+/// the `--print-routes` CLI flag) is rendered from the same table, so the
+/// manifest our gateway config is generated from cannot drift from what is
+/// actually served.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteSpec {
+    pub method: &'static str,
+    pub path: &'static str,
+    /// Scope required when authorization is enabled; `None` for public
+    /// endpoints.
+    pub auth_scope: Option<&'static str>,
+    /// Rate-limit class enforced by the in-process limiter (and exported
+    /// to the gateway): `public_read`, `public_write`, or `expensive`.
+    pub rate_limit_class: &'static str,
+    /// Upstream timeout budget the gateway should allow for this route.
+    pub timeout_budget_ms: u64,
+    /// Whether the route counts against the DB-heavy concurrency ceiling
+    /// (`ROUTE_CONCURRENCY_LIMIT`); overflow is shed with a 503. Scan
+    /// endpoints opt in so they cannot monopolize the connection pool,
+    /// while light routes stay unlimited.
+    pub concurrency_limited: bool,
+    /// Whether the route accepts bodies up to `BULK_MAX_BODY_BYTES`
+    /// instead of the global `MAX_BODY_BYTES`. Bulk endpoints opt in so
+    /// large imports pass without loosening the cap everywhere else.
+    pub bulk_body_limit: bool,
+}
+
+impl RouteSpec {
+    const fn new(
+        method: &'static str,
+        path: &'static str,
+        auth_scope: Option<&'static str>,
+        rate_limit_class: &'static str,
+        timeout_budget_ms: u64,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            auth_scope,
+            rate_limit_class,
+            timeout_budget_ms,
+            concurrency_limited: false,
+            bulk_body_limit: false,
+        }
+    }
+
+    /// Mark the route as counting against the DB-heavy concurrency
+    /// ceiling.
+    const fn concurrency_limited(mut self) -> Self {
+        self.concurrency_limited = true;
+        self
+    }
+
+    /// Mark the route as accepting bodies up to `BULK_MAX_BODY_BYTES`.
+    const fn bulk_body_limit(mut self) -> Self {
+        self.bulk_body_limit = true;
+        self
+    }
+}
+
+/// The single source of truth for registered routes: each entry pairs the
+/// spec served by the manifest with the handler mounted on the router.
+/// Entries sharing a path are merged into one method router by axum.
+pub fn route_table() -> Vec<(RouteSpec, MethodRouter<AppState>)> {
+    #[cfg_attr(not(feature = "chaos"), allow(unused_mut))]
+    let mut routes = vec![
+        (
+            RouteSpec::new("GET", "/health", None, classes::PUBLIC_READ, 1_000),
+            get(health_check),
+        ),
+        (
+            RouteSpec::new("GET", "/health/ready", None, classes::PUBLIC_READ, 1_000),
+            get(readiness_check),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/health/migrations",
+                None,
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(migrations_health),
+        ),
+        (
+            RouteSpec::new("GET", "/metrics", None, classes::PUBLIC_READ, 5_000),
+            get(metrics),
+        ),
+        (
+            RouteSpec::new("GET", "/docs", None, classes::PUBLIC_READ, 5_000),
+            get(docs),
+        ),
+        (
+            RouteSpec::new("GET", "/time", None, classes::PUBLIC_READ, 1_000),
+            get(server_time),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            )
+            .concurrency_limited(),
+            get(list_users),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/users",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            post(create_user),
+        ),
+        (
+            RouteSpec::new(
+                "PUT",
+                "/users",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            put(upsert_user),
+        ),
+        (
+            // A POST, but read-only: batch resolution of several emails.
+            RouteSpec::new(
+                "POST",
+                "/users/lookup",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            post(lookup_users),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/users/email-change/confirm",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            post(confirm_email_change),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/changes",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            )
+            .concurrency_limited(),
+            get(list_changed_users),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/stats",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_stats),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/by-email/:email",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_by_email),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/by-public-id/:uuid",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_by_public_id),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/:id",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user),
+        ),
+        (
+            RouteSpec::new(
+                "PUT",
+                "/users/:id",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            put(update_user),
+        ),
+        (
+            RouteSpec::new(
+                "DELETE",
+                "/users/:id",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            delete(delete_user),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/:id/history",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_history),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/:id/avatar",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_avatar),
+        ),
+        (
+            RouteSpec::new(
+                "PUT",
+                "/users/:id/avatar",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                10_000,
+            ),
+            put(set_user_avatar),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/users/:id/tags",
+                Some(scopes::USERS_READ),
+                classes::PUBLIC_READ,
+                5_000,
+            ),
+            get(get_user_tags),
+        ),
+        (
+            RouteSpec::new(
+                "PUT",
+                "/users/:id/tags",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            put(set_user_tags),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/users/:id/email-change",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            post(request_email_change),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/users/:id/rotate-public-id",
+                Some(scopes::USERS_WRITE),
+                classes::PUBLIC_WRITE,
+                5_000,
+            ),
+            post(rotate_public_id),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/admin/pool/recycle",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                30_000,
+            ),
+            post(recycle_pool),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/admin/users/merge",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                30_000,
+            ),
+            post(merge_users),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/admin/users/delete",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                30_000,
+            ),
+            post(delete_users),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/admin/users/import-with-ids",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                30_000,
+            )
+            .bulk_body_limit(),
+            post(import_users_with_ids),
+        ),
+        (
+            RouteSpec::new(
+                "POST",
+                "/admin/cors/reload",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            post(reload_cors),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/admin/rate-limits",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            get(show_rate_limits),
+        ),
+        (
+            RouteSpec::new(
+                "PATCH",
+                "/admin/rate-limits",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            patch(update_rate_limits),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/admin/routes",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            get(route_manifest),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/admin/usage",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            get(usage_summary),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/admin/slo",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            get(slo_status),
+        ),
+        (
+            RouteSpec::new(
+                "GET",
+                "/admin/webhooks/dead-letters",
+                Some(scopes::ADMIN),
+                classes::EXPENSIVE,
+                5_000,
+            ),
+            get(webhook_dead_letters),
+        ),
+    ];
+
+    // Test-only fault injection; compiled out of default builds entirely.
+    #[cfg(feature = "chaos")]
+    routes.push((
+        RouteSpec::new(
+            "POST",
+            "/admin/chaos",
+            Some(scopes::ADMIN),
+            classes::EXPENSIVE,
+            5_000,
+        ),
+        post(configure_chaos),
+    ));
+
+    routes
+}
+
+/// One route as emitted by the manifest, with the configured base path
+/// applied so the paths match what callers actually hit.
+#[derive(Debug, Serialize)]
+pub struct ManifestRoute {
+    pub method: &'static str,
+    pub path: String,
+    pub auth_scope: Option<&'static str>,
+    pub rate_limit_class: &'static str,
+    pub timeout_budget_ms: u64,
+}
+
+/// The route manifest after base-path resolution.
+pub fn manifest(base_path: &str) -> Vec<ManifestRoute> {
+    let prefix = crate::normalized_base_path(base_path).unwrap_or_default();
+    route_table()
+        .into_iter()
+        .map(|(spec, _)| ManifestRoute {
+            method: spec.method,
+            path: format!("{prefix}{}", spec.path),
+            auth_scope: spec.auth_scope,
+            rate_limit_class: spec.rate_limit_class,
+            timeout_budget_ms: spec.timeout_budget_ms,
+        })
+        .collect()
+}
+
+/// JSON rendering of [`manifest`] for the `--print-routes` CLI flag.
+pub fn manifest_json(base_path: &str) -> String {
+    serde_json::to_string_pretty(&manifest(base_path)).unwrap_or_default()
+}
+
+/// Liveness endpoint, deliberately the cheapest handler in the table.
+///
+/// The body is a pre-serialized static — axum wraps it via
+/// `Bytes::from_static` and stamps the `text/plain` content type from a
+/// constant — and the handler takes no state, so serving it costs no
+/// heap allocation at all. That property is pinned by the
+/// `health_alloc` test binary and measured by the `health` benchmark;
+/// `ACCESS_LOG_QUIET_PATHS` keeps the log line and tracing span off the
+/// same path. Readiness keeps the richer JSON body.
+pub async fn health_check() -> &'static str {
+    "OK"
+}
+
+/// Response body for `GET /health/ready`.
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    /// `"ok"`, or `"degraded (read-only)"` while only reads are served —
+    /// still 200, so a primary failover does not read as fully down.
+    pub status: &'static str,
+    /// True when the service refuses writes — a read-only database
+    /// connection (write endpoints answer 503 `READ_ONLY`) or an
+    /// operator-set `READ_ONLY_MODE` (they answer 405).
+    pub read_only: bool,
+    /// True when some route class is burning error budget past the
+    /// fast-burn threshold (see `GET /admin/slo`). Informational: the
+    /// endpoint still answers 200 so probes do not amplify an error spike
+    /// into an outage.
+    pub degraded: bool,
+}
+
+/// Readiness endpoint consulted by load balancers; fails during the
+/// shutdown drain sequence.
+pub async fn readiness_check(
+    State(state): State<AppState>,
+) -> Result<axum::Json<Readiness>, StatusCode> {
+    if state.readiness.is_ready() {
+        let read_only = state.read_only || state.config.read_only_mode;
+        Ok(axum::Json(Readiness {
+            status: if read_only {
+                "degraded (read-only)"
+            } else {
+                "ok"
+            },
+            read_only,
+            degraded: crate::middleware::slo::fast_burn_exceeded(
+                &state.slo,
+                state.config.slo_availability,
+            ),
+        }))
+    } else {
+        Err(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+/// Response body for `GET /health/migrations`.
+#[derive(Debug, Serialize)]
+pub struct MigrationsHealth {
+    /// Embedded migrations not yet applied to this database.
+    pub pending_migrations: usize,
+    /// True when migrations are pending and this process will not apply
+    /// them itself (`RUN_MIGRATIONS_ON_STARTUP=false`): the binary is
+    /// running ahead of its schema.
+    pub degraded: bool,
+}
+
+/// GET /health/migrations
+///
+/// Compares the embedded migrator against `_sqlx_migrations`, so an
+/// operator can tell whether a deployed binary expects schema changes its
+/// database has not seen.
+pub async fn migrations_health(
+    State(state): State<AppState>,
+) -> crate::error::Result<axum::Json<MigrationsHealth>> {
+    let pending_migrations = match &state.db {
+        Some(handle) => crate::repository::applied_migration_versions(&handle.current())
+            .await
+            .map(|applied| crate::repository::pending_migrations(&applied).len())?,
+        // No database attached (in-memory repository): nothing to apply.
+        None => 0,
+    };
+
+    Ok(axum::Json(MigrationsHealth {
+        pending_migrations,
+        degraded: pending_migrations > 0 && !state.config.run_migrations_on_startup,
+    }))
+}
+
+/// GET /docs
+///
+/// Route documentation rendered from the typed route table — what this
+/// service serves in place of Swagger UI. `DOCS_ENABLED=false` makes the
+/// path 404 exactly like an unknown route so probes cannot tell the
+/// endpoint exists, and `DOCS_REQUIRE_AUTH=true` demands a valid bearer
+/// token.
+pub async fn docs(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+
+    if !state.config.docs_enabled {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if state.config.docs_require_auth {
+        if let Err(error) =
+            crate::auth::require_authenticated(&headers, state.config.auth_jwt_secret.as_deref())
+        {
+            return error.into_response();
+        }
+    }
+    axum::Json(manifest(&state.config.base_path)).into_response()
+}
+
+/// Response body for `GET /time`.
+#[derive(Debug, Serialize)]
+pub struct ServerTime {
+    /// The server's current UTC time in RFC 3339 form.
+    pub utc: String,
+    /// The same instant as a Unix timestamp in seconds.
+    pub unix: i64,
+}
+
+/// GET /time
+///
+/// The server's notion of "now", for clients reconciling timestamps and
+/// debugging clock skew.
+pub async fn server_time() -> axum::Json<ServerTime> {
+    let now = chrono::Utc::now();
+    axum::Json(ServerTime {
+        utc: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        unix: now.timestamp(),
+    })
+}
+
+/// Prometheus metrics in the text exposition format. Optionally requires
+/// a valid bearer token (`METRICS_REQUIRE_AUTH=true`): exposition output
+/// reveals route shapes and traffic patterns.
+pub async fn metrics(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<String, crate::error::AppError> {
+    if state.config.metrics_require_auth {
+        crate::auth::require_authenticated(&headers, state.config.auth_jwt_secret.as_deref())?;
+    }
+    Ok(crate::metrics::render())
+}
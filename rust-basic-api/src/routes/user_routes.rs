@@ -0,0 +1,2858 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Caller, RequireScope, UsersRead, UsersWrite};
+use crate::error::{AppError, Result};
+use crate::middleware::Tenant;
+use crate::models::{self, CreateUserRequest, UpdateUserRequest, User, UserAuditEntry, UserStats};
+use crate::repository::{Pagination, UserQuery};
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Query parameters accepted by `GET /users`.
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// When `false`, skip the `COUNT(*)` over the table and omit `total`
+    /// from the response. Defaults to `true`.
+    pub with_total: Option<bool>,
+    /// Only return users carrying this tag (normalized before matching).
+    pub tag: Option<String>,
+    /// Case-insensitive substring match over name and email.
+    pub search: Option<String>,
+    /// Only users created at or after this RFC 3339 instant.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only users created at or before this RFC 3339 instant.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Keyset cursor: return users with an id greater than this one.
+    /// Mutually exclusive with `offset`; deep pages should prefer it.
+    pub after_id: Option<i32>,
+    /// Sparse fieldset: comma-separated `User` field names to include in
+    /// each entry (`id` is always kept). Unknown names are a 400.
+    pub fields: Option<String>,
+}
+
+/// Validated sparse fieldset parsed from a `fields` query parameter.
+///
+/// Holds the canonical (sorted, deduplicated) subset of [`User::FIELDS`]
+/// to serialize; `id` is always a member so entries stay addressable.
+struct FieldSet(std::collections::BTreeSet<&'static str>);
+
+impl FieldSet {
+    fn parse(raw: &str) -> Result<Self> {
+        let mut fields = std::collections::BTreeSet::from(["id"]);
+        let mut unknown = Vec::new();
+        for name in raw.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match User::FIELDS.iter().find(|known| **known == name) {
+                Some(known) => {
+                    fields.insert(known);
+                }
+                None => unknown.push(name),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(AppError::Validation(format!(
+                "unknown fields: {}",
+                unknown.join(", ")
+            )));
+        }
+        Ok(Self(fields))
+    }
+
+    /// Stable rendering of the set, for folding into ETag computation.
+    fn canonical(&self) -> String {
+        self.0.iter().copied().collect::<Vec<_>>().join(",")
+    }
+
+    /// The user's JSON object reduced to the requested fields. Keys the
+    /// user serializes without (attribution `None`s) stay absent even
+    /// when requested.
+    fn apply(&self, user: &User) -> serde_json::Value {
+        let serde_json::Value::Object(mut map) =
+            serde_json::to_value(user).expect("user serializes to an object")
+        else {
+            unreachable!("user serializes to an object");
+        };
+        map.retain(|key, _| self.0.contains(key.as_str()));
+        serde_json::Value::Object(map)
+    }
+}
+
+/// Response body for `GET /users`. Generic over the entry shape so a
+/// sparse fieldset reuses the same envelope.
+#[derive(Debug, Serialize)]
+pub struct UserListResponse<T = User> {
+    pub users: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// GET /users
+///
+/// Offsets beyond `MAX_OFFSET` are rejected with a 400: Postgres has to
+/// scan and discard every skipped row, so deep pages should move to
+/// narrower filters rather than ever-larger offsets.
+///
+/// Responses carry a weak collection ETag and honor `If-None-Match` with
+/// 304, so pollers revalidate with one cheap aggregate query instead of
+/// refetching and reserializing an unchanged page.
+pub async fn list_users(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    headers: HeaderMap,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<axum::response::Response> {
+    let repository = state.repository_for(tenant.0.as_ref());
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    if offset > state.config.max_offset {
+        return Err(AppError::Validation(format!(
+            "offset must not exceed {}; use narrower filters for deep pages",
+            state.config.max_offset
+        )));
+    }
+    if query.after_id.is_some() && query.offset.is_some() {
+        return Err(AppError::Validation(
+            "after_id and offset are mutually exclusive".to_string(),
+        ));
+    }
+    let fields = query.fields.as_deref().map(FieldSet::parse).transpose()?;
+
+    let mut user_query = UserQuery::new().paginate(match query.after_id {
+        Some(after_id) => Pagination::Keyset {
+            limit,
+            after_id: Some(after_id),
+        },
+        None => Pagination::Offset { limit, offset },
+    });
+    if let Some(tag) = query.tag.as_deref() {
+        user_query = user_query.tag(&models::tag::normalize_tag(tag)?);
+    }
+    if let Some(search) = query.search.as_deref().filter(|s| !s.trim().is_empty()) {
+        user_query = user_query.search(search.trim());
+    }
+    if let Some(after) = query.created_after {
+        user_query = user_query.created_after(after);
+    }
+    if let Some(before) = query.created_before {
+        user_query = user_query.created_before(before);
+    }
+
+    let version = repository.collection_version(&user_query).await?;
+    let etag = collection_etag(
+        &user_query,
+        &version,
+        &caller,
+        tenant.0.as_ref().map(|t| t.id.as_str()),
+        fields.as_ref(),
+    );
+    let etag_header = [(axum::http::header::ETAG, etag.clone())];
+    if headers
+        .get(axum::http::header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, etag_header).into_response());
+    }
+
+    let mut users = repository.list_users(&user_query).await?;
+    if !caller.is_admin {
+        users = users.into_iter().map(User::without_attribution).collect();
+    }
+    // The version aggregate already counted the filtered set; no separate
+    // COUNT(*) round trip.
+    let total = query.with_total.unwrap_or(true).then_some(version.count);
+
+    Ok(match fields {
+        Some(fields) => (
+            etag_header,
+            Json(UserListResponse {
+                users: users.iter().map(|user| fields.apply(user)).collect(),
+                total,
+                limit,
+                offset,
+            }),
+        )
+            .into_response(),
+        None => (
+            etag_header,
+            Json(UserListResponse {
+                users,
+                total,
+                limit,
+                offset,
+            }),
+        )
+            .into_response(),
+    })
+}
+
+/// Weak validator for a filtered listing, hashed over the rendered query
+/// (which encodes every filter, sort, and pagination parameter, so
+/// different views cannot collide), the change-detection aggregate, and
+/// the response-shaping inputs (tenant, caller privileges).
+///
+/// Weak (`W/`) because `MAX(updated_at)` has finite resolution and the
+/// match promises equivalent content, not identical bytes. A delete
+/// without any accompanying update would slip past `MAX(updated_at)`
+/// alone — which is why the row count is part of the validator.
+fn collection_etag(
+    query: &crate::repository::UserQuery,
+    version: &crate::repository::CollectionVersion,
+    caller: &Caller,
+    tenant: Option<&str>,
+    fields: Option<&FieldSet>,
+) -> String {
+    use sha2::{Digest, Sha256};
+
+    let (sql, binds) = query.render();
+    let mut hasher = Sha256::new();
+    hasher.update(sql.as_bytes());
+    hasher.update(format!("|{binds:?}"));
+    hasher.update(format!(
+        "|{}|{}|{}|{}",
+        version
+            .latest_updated_at
+            .map_or(0, |at| at.timestamp_micros()),
+        version.count,
+        caller.is_admin,
+        tenant.unwrap_or_default(),
+    ));
+    // The fieldset shapes the entries, so two views of the same rows must
+    // not share a validator.
+    hasher.update(format!(
+        "|{}",
+        fields.map(FieldSet::canonical).unwrap_or_default()
+    ));
+    let digest = hasher.finalize();
+    let opaque: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("W/\"{opaque}\"")
+}
+
+/// Query parameters accepted by `GET /users/:id`.
+#[derive(Debug, Deserialize)]
+pub struct GetUserQuery {
+    /// Sparse fieldset, as on the listing (`id` is always kept).
+    pub fields: Option<String>,
+}
+
+/// GET /users/:id
+pub async fn get_user(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Path(id): Path<i32>,
+    Query(query): Query<GetUserQuery>,
+) -> Result<axum::response::Response> {
+    let fields = query.fields.as_deref().map(FieldSet::parse).transpose()?;
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .get_user(id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let user = shaped(user, &caller);
+    Ok(match fields {
+        Some(fields) => Json(fields.apply(&user)).into_response(),
+        None => Json(user).into_response(),
+    })
+}
+
+/// Apply scope-based response shaping: attribution fields are for
+/// admin-scoped callers only.
+fn shaped(user: User, caller: &Caller) -> User {
+    if caller.is_admin {
+        user
+    } else {
+        user.without_attribution()
+    }
+}
+
+/// GET /users/by-email/:email
+///
+/// Id lookup's companion for admin tooling. The path segment is
+/// percent-decoded by the extractor, so encoded `@` (`%40`) and literal
+/// `+` in plus-addressed emails both arrive intact, and the lookup is
+/// case-insensitive.
+pub async fn get_user_by_email(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Path(email): Path<String>,
+) -> Result<Json<User>> {
+    require_authenticated_for_enumeration(&state, &caller)?;
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .get_user_by_email(&email)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(shaped(user, &caller)))
+}
+
+/// 401 for the email-existence oracles (`GET /users/by-email`,
+/// `POST /users/lookup`) when `ENUMERATION_PROTECTION` is on and the
+/// caller holds no valid bearer token.
+fn require_authenticated_for_enumeration(state: &AppState, caller: &Caller) -> Result<()> {
+    if state.config.enumeration_protection && !caller.is_authenticated() {
+        return Err(AppError::http(
+            StatusCode::UNAUTHORIZED,
+            "authentication required to look up users by email",
+        ));
+    }
+    Ok(())
+}
+
+/// Structural check for a UUID path segment (8-4-4-4-12 hex groups), so
+/// malformed input is a 400 here instead of a cast error inside Postgres.
+fn is_uuid(raw: &str) -> bool {
+    let bytes = raw.as_bytes();
+    bytes.len() == 36
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// GET /users/by-public-id/:uuid
+///
+/// Lookup by the non-sequential public identifier, for clients that never
+/// see (or store) the serial id.
+pub async fn get_user_by_public_id(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Path(public_id): Path<String>,
+) -> Result<Json<User>> {
+    if !is_uuid(&public_id) {
+        return Err(AppError::Validation("public id must be a UUID".to_string()));
+    }
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .get_user_by_public_id(&public_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(shaped(user, &caller)))
+}
+
+/// POST /users/:id/rotate-public-id
+///
+/// Assign the user a fresh public UUID. The old identifier stops
+/// resolving immediately, which is the point: rotation is the remedy for
+/// a leaked identifier.
+pub async fn rotate_public_id(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<Json<User>> {
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .rotate_public_id(id, &caller.principal)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    tracing::info!(id, "rotated public id");
+    state.publish_event("user.updated", serde_json::json!({"id": id}), &headers);
+    Ok(Json(shaped(user, &caller)))
+}
+
+/// Request body for `POST /users/lookup`.
+#[derive(Debug, Deserialize)]
+pub struct LookupUsersRequest {
+    pub emails: Vec<String>,
+}
+
+impl LookupUsersRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["emails"];
+}
+
+/// POST /users/lookup
+///
+/// Resolve several emails in one round trip for batch integrations: the
+/// response maps each requested email (as sent) to its user, or `null`
+/// for a miss. One `WHERE email = ANY($1)` query serves the whole batch;
+/// the batch size is capped by `LOOKUP_MAX_EMAILS`.
+pub async fn lookup_users(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<std::collections::BTreeMap<String, Option<User>>>> {
+    require_authenticated_for_enumeration(&state, &caller)?;
+    let req: LookupUsersRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        LookupUsersRequest::FIELDS,
+    )?;
+    if req.emails.len() > state.config.lookup_max_emails {
+        return Err(AppError::Validation(format!(
+            "at most {} emails per lookup",
+            state.config.lookup_max_emails
+        )));
+    }
+
+    let users = state
+        .repository_for(tenant.0.as_ref())
+        .get_users_by_emails(&req.emails)
+        .await?;
+    let resolved = req
+        .emails
+        .into_iter()
+        .map(|email| {
+            let user = users
+                .iter()
+                .find(|u| u.email.as_str().eq_ignore_ascii_case(&email))
+                .cloned()
+                .map(|user| shaped(user, &caller));
+            (email, user)
+        })
+        .collect();
+    Ok(Json(resolved))
+}
+
+/// POST /users
+pub async fn create_user(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<axum::response::Response> {
+    let mut req: CreateUserRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        CreateUserRequest::FIELDS,
+    )?;
+    if state.config.normalize_emails {
+        req.email = models::email::normalize(&req.email);
+    }
+    req.validate()?;
+
+    // Enumeration protection: an unauthenticated caller gets the same
+    // empty 202 whether the email is new or already registered, padded to
+    // a constant minimum duration so timing tells them apart no better
+    // than the body. The true outcome only travels the outbox path.
+    if state.config.enumeration_protection && !caller.is_authenticated() {
+        let started = tokio::time::Instant::now();
+        let floor = std::time::Duration::from_millis(state.config.enumeration_min_response_ms);
+        let (user, created) = state
+            .repository_for(tenant.0.as_ref())
+            .create_user_or_existing(req, &caller.principal)
+            .await?;
+        if created {
+            tracing::info!(id = user.id, email = %user.email, "created user");
+            state.publish_event("user.created", serde_json::json!({"id": user.id}), &headers);
+        }
+        tokio::time::sleep_until(started + floor).await;
+        return Ok(StatusCode::ACCEPTED.into_response());
+    }
+
+    // `Prefer: return=existing`: a duplicate registration (including the
+    // loser of a concurrent pair) gets the surviving row back with 200
+    // instead of a 409, with `X-Created` telling the outcomes apart.
+    if prefers_existing(&headers) {
+        let (user, created) = state
+            .repository_for(tenant.0.as_ref())
+            .create_user_or_existing(req, &caller.principal)
+            .await?;
+        if created {
+            tracing::info!(id = user.id, email = %user.email, "created user");
+            state.publish_event("user.created", serde_json::json!({"id": user.id}), &headers);
+        }
+        let status = if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        };
+        return Ok((
+            status,
+            [("x-created", if created { "true" } else { "false" })],
+            Json(shaped(user, &caller)),
+        )
+            .into_response());
+    }
+
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .create_user(req, &caller.principal)
+        .await?;
+    // `email` is redacted by the logging layer unless LOG_REDACTION=false.
+    tracing::info!(id = user.id, email = %user.email, "created user");
+    state.publish_event("user.created", serde_json::json!({"id": user.id}), &headers);
+    Ok((StatusCode::CREATED, Json(shaped(user, &caller))).into_response())
+}
+
+/// Whether the request carries a `Prefer: return=existing` preference
+/// ([RFC 7240] list syntax; unrecognized preferences are ignored).
+///
+/// [RFC 7240]: https://www.rfc-editor.org/rfc/rfc7240
+fn prefers_existing(headers: &HeaderMap) -> bool {
+    headers.get_all("prefer").iter().any(|value| {
+        value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .any(|pref| pref.trim().eq_ignore_ascii_case("return=existing"))
+        })
+    })
+}
+
+/// PUT /users
+///
+/// Upsert by email: creates the user when the email is unseen (201) or
+/// updates the existing user carrying it (200), so clients can retry the
+/// call idempotently and still tell the two outcomes apart.
+pub async fn upsert_user(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Json(body): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<User>)> {
+    let mut req: CreateUserRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        CreateUserRequest::FIELDS,
+    )?;
+    if state.config.normalize_emails {
+        req.email = models::email::normalize(&req.email);
+    }
+    req.validate()?;
+
+    let (user, inserted) = state
+        .repository_for(tenant.0.as_ref())
+        .upsert_user_by_email(req, &caller.principal)
+        .await?;
+    let status = if inserted {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(shaped(user, &caller))))
+}
+
+/// PUT /users/:id
+///
+/// When an `If-Unmodified-Since` header (RFC 3339) is supplied, the update
+/// only applies if the stored `updated_at` still matches; a stale version
+/// gets `409 Conflict`.
+pub async fn update_user(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<User>> {
+    let repository = state.repository_for(tenant.0.as_ref());
+    let mut req: UpdateUserRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        UpdateUserRequest::FIELDS,
+    )?;
+    if state.config.normalize_emails {
+        if let Some(email) = &req.email {
+            req.email = Some(models::email::normalize(email));
+        }
+    }
+    req.validate()?;
+
+    let Some(expected) = parse_if_unmodified_since(&headers)? else {
+        let user = repository
+            .update_user(id, req, &caller.principal)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        state.publish_event("user.updated", serde_json::json!({"id": user.id}), &headers);
+        return Ok(Json(shaped(user, &caller)));
+    };
+
+    match repository
+        .update_if_unchanged(id, req, expected, &caller.principal)
+        .await?
+    {
+        Some(user) => {
+            state.publish_event("user.updated", serde_json::json!({"id": user.id}), &headers);
+            Ok(Json(shaped(user, &caller)))
+        }
+        None => {
+            // Distinguish a missing row from a concurrent modification.
+            if repository.get_user(id).await?.is_some() {
+                Err(AppError::Conflict(
+                    "user was modified since the supplied version".to_string(),
+                ))
+            } else {
+                Err(AppError::NotFound)
+            }
+        }
+    }
+}
+
+fn parse_if_unmodified_since(headers: &HeaderMap) -> Result<Option<DateTime<Utc>>> {
+    let Some(value) = headers.get("if-unmodified-since") else {
+        return Ok(None);
+    };
+    let raw = value
+        .to_str()
+        .map_err(|_| AppError::Validation("If-Unmodified-Since must be valid UTF-8".to_string()))?;
+    let parsed = DateTime::parse_from_rfc3339(raw).map_err(|_| {
+        AppError::Validation("If-Unmodified-Since must be an RFC 3339 timestamp".to_string())
+    })?;
+    Ok(Some(parsed.with_timezone(&Utc)))
+}
+
+/// Query parameters accepted by `GET /users/changes`.
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// Return users modified strictly after this RFC 3339 instant.
+    pub since: DateTime<Utc>,
+}
+
+/// GET /users/changes
+///
+/// Users whose `updated_at` is strictly after `since`, oldest change
+/// first, so sync clients can pull deltas instead of re-listing the
+/// whole collection. Clients resume from the `updated_at` of the last
+/// entry they processed.
+pub async fn list_changed_users(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(query): Query<ChangesQuery>,
+) -> Result<Json<Vec<User>>> {
+    let users = state
+        .repository_for(tenant.0.as_ref())
+        .modified_since(query.since)
+        .await?;
+    Ok(Json(users))
+}
+
+/// GET /users/stats
+///
+/// Collection-wide aggregates for a dashboard summary card, computed in
+/// a single query. The timestamps are `null` while the table is empty.
+pub async fn get_user_stats(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<UserStats>> {
+    let stats = state.repository_for(tenant.0.as_ref()).user_stats().await?;
+    Ok(Json(stats))
+}
+
+/// GET /users/:id/history
+///
+/// Trigger-written row images for every change to the user, oldest
+/// first. History survives the user's deletion, so a missing user with
+/// recorded entries still returns them (ending in a `delete` entry)
+/// rather than a 404.
+pub async fn get_user_history(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<UserAuditEntry>>> {
+    let history = state
+        .repository_for(tenant.0.as_ref())
+        .user_history(id)
+        .await?;
+    Ok(Json(history))
+}
+
+/// GET /users/:id/tags
+pub async fn get_user_tags(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<String>>> {
+    let tags = state
+        .repository_for(tenant.0.as_ref())
+        .user_tags(id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(tags))
+}
+
+/// PUT /users/:id/tags
+///
+/// Replace the user's tag set with the submitted one; an empty list
+/// detaches every tag. Tag names are normalized (trimmed, lowercased)
+/// before storage, so `" Beta "` and `"beta"` are the same tag.
+pub async fn set_user_tags(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<Vec<String>>> {
+    let req: models::SetUserTagsRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        models::SetUserTagsRequest::FIELDS,
+    )?;
+    let tags = req.normalized()?;
+
+    let tags = state
+        .repository_for(tenant.0.as_ref())
+        .set_user_tags(id, &tags)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(tags))
+}
+
+/// PUT /users/:id/avatar
+///
+/// Accepts a PNG or JPEG body, validated by magic bytes rather than the
+/// Content-Type header alone. Blobs are stored content-addressed by
+/// SHA-256, so identical uploads across users share one row; the hash is
+/// returned in the `ETag` header.
+pub async fn set_user_avatar(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<axum::response::Response> {
+    if body.len() > state.config.avatar_max_bytes {
+        return Err(AppError::http(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "avatar exceeds the {} byte limit",
+                state.config.avatar_max_bytes
+            ),
+        ));
+    }
+    let declared = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let content_type = models::avatar::validate_image(declared, &body)?;
+    let hash = models::avatar::content_hash(&body);
+
+    state
+        .repository_for(tenant.0.as_ref())
+        .set_user_avatar(
+            id,
+            models::Avatar {
+                hash: hash.clone(),
+                content_type: content_type.to_string(),
+                bytes: body.to_vec(),
+            },
+        )
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(axum::http::header::ETAG, format!("\"{hash}\""))],
+    )
+        .into_response())
+}
+
+/// GET /users/:id/avatar
+///
+/// Streams the avatar back with its content type, the blob hash as a
+/// strong ETag, and immutable cache headers — the content address never
+/// serves different bytes, so clients may cache it forever.
+pub async fn get_user_avatar(
+    _scope: RequireScope<UsersRead>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response> {
+    let avatar = state
+        .repository_for(tenant.0.as_ref())
+        .user_avatar(id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let etag = format!("\"{}\"", avatar.hash);
+
+    let cache_headers = [
+        (axum::http::header::ETAG, etag.clone()),
+        (
+            axum::http::header::CACHE_CONTROL,
+            "public, max-age=31536000, immutable".to_string(),
+        ),
+    ];
+    if headers
+        .get(axum::http::header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    Ok((
+        StatusCode::OK,
+        [(
+            axum::http::header::CONTENT_TYPE,
+            avatar.content_type.clone(),
+        )],
+        cache_headers,
+        avatar.bytes,
+    )
+        .into_response())
+}
+
+/// DELETE /users/:id
+pub async fn delete_user(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    if state
+        .repository_for(tenant.0.as_ref())
+        .delete_user(id)
+        .await?
+    {
+        state.publish_event("user.deleted", serde_json::json!({"id": id}), &headers);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+/// Request body for `POST /users/:id/email-change`.
+#[derive(Debug, Deserialize)]
+pub struct EmailChangeRequest {
+    pub email: String,
+}
+
+impl EmailChangeRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["email"];
+}
+
+/// Request body for `POST /users/email-change/confirm`.
+#[derive(Debug, Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
+
+impl ConfirmEmailChangeRequest {
+    /// Field names accepted in strict unknown-fields mode.
+    pub const FIELDS: &'static [&'static str] = &["token"];
+}
+
+/// A fresh email-change confirmation token: hex SHA-256 over per-thread
+/// OS-seeded `RandomState` output plus the current time. Unguessable
+/// without the process's hasher keys, which is the quality this needs;
+/// the crate deliberately carries no dedicated RNG dependency (compare
+/// the access-log sampler's seed).
+fn confirmation_token() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for _ in 0..4 {
+        let keyed = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.update(keyed.finish().to_be_bytes());
+    }
+    hasher.update(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_be_bytes(),
+    );
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Hex SHA-256 of a confirmation token. Only hashes reach storage, so a
+/// leaked `email_change_tokens` table cannot be replayed as confirmations.
+fn confirmation_token_hash(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// POST /users/:id/email-change
+///
+/// Starts a two-phase email change: the new address is validated, checked
+/// for availability (409 when taken), and parked as `pending_email`, and a
+/// single-use confirmation token — stored hashed, valid for
+/// `EMAIL_CHANGE_TOKEN_TTL_SECS` — goes out through the webhook outbox for
+/// out-of-band delivery to the new address. Nothing client-visible changes
+/// until the token comes back through `POST /users/email-change/confirm`,
+/// so a typo cannot lock the owner out.
+pub async fn request_email_change(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<axum::response::Response> {
+    let mut req: EmailChangeRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        EmailChangeRequest::FIELDS,
+    )?;
+    if state.config.normalize_emails {
+        req.email = models::email::normalize(&req.email);
+    }
+    let email: models::EmailAddress = req.email.parse()?;
+
+    let token = confirmation_token();
+    let ttl = i64::try_from(state.config.email_change_token_ttl_secs).unwrap_or(i64::MAX);
+    let expires_at = Utc::now() + chrono::Duration::seconds(ttl);
+    state
+        .repository_for(tenant.0.as_ref())
+        .request_email_change(
+            id,
+            email.as_str(),
+            &confirmation_token_hash(&token),
+            expires_at,
+        )
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    tracing::info!(id, "email change requested");
+    // The token travels only through the outbox (to be mailed to the new
+    // address); it is never part of the response.
+    state.publish_event(
+        "user.email_change_requested",
+        serde_json::json!({"id": id, "new_email": email.as_str(), "token": token}),
+        &headers,
+    );
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "expires_at": expires_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        })),
+    )
+        .into_response())
+}
+
+/// POST /users/email-change/confirm
+///
+/// Completes a pending email change. The change applies atomically when
+/// the token is known and unexpired and the address is still free; a
+/// registration that claimed it since the request is a 409, an expired
+/// token a 410, and an unknown (or already used) token a 404. Success
+/// consumes the token.
+pub async fn confirm_email_change(
+    _scope: RequireScope<UsersWrite>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    caller: Caller,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<User>> {
+    let req: ConfirmEmailChangeRequest = models::from_json_value(
+        body,
+        state.config.strict_json_fields,
+        ConfirmEmailChangeRequest::FIELDS,
+    )?;
+
+    let user = state
+        .repository_for(tenant.0.as_ref())
+        .confirm_email_change(&confirmation_token_hash(&req.token), &caller.principal)
+        .await?;
+    tracing::info!(id = user.id, "email change confirmed");
+    state.publish_event("user.updated", serde_json::json!({"id": user.id}), &headers);
+    Ok(Json(shaped(user, &caller)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    async fn body_json(response: axum::response::Response) -> serde_json::Value {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn create_request(name: &str, email: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/users")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"name":"{name}","email":"{email}"}}"#
+            )))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn validation_errors_localize_by_accept_language() {
+        let app = test_app(test_state());
+        let invalid_email = |lang: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri("/users")
+                .header("content-type", "application/json");
+            if let Some(lang) = lang {
+                builder = builder.header("accept-language", lang);
+            }
+            builder
+                .body(Body::from(r#"{"name":"Valid","email":"not-an-email"}"#))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(invalid_email(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let english = body_json(response).await;
+        assert_eq!(english["message"], "email must be a valid email address");
+
+        let response = app
+            .clone()
+            .oneshot(invalid_email(Some("de")))
+            .await
+            .unwrap();
+        let german = body_json(response).await;
+        assert_eq!(german["message"], "die E-Mail-Adresse ist ungültig");
+
+        // French is preferred but unsupported; the q=0.8 German wins.
+        let response = app
+            .clone()
+            .oneshot(invalid_email(Some("fr, de;q=0.8")))
+            .await
+            .unwrap();
+        assert_eq!(
+            body_json(response).await["message"],
+            "die E-Mail-Adresse ist ungültig"
+        );
+
+        // A wholly unsupported locale falls back to English.
+        let response = app
+            .clone()
+            .oneshot(invalid_email(Some("fr")))
+            .await
+            .unwrap();
+        assert_eq!(
+            body_json(response).await["message"],
+            "email must be a valid email address"
+        );
+
+        // The machine-readable key is identical across locales.
+        assert_eq!(english["code"], german["code"]);
+        assert_eq!(english["code"], "validation.email_invalid");
+        assert_eq!(german["field"], "email");
+    }
+
+    #[tokio::test]
+    async fn create_and_fetch_user() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Test User", "test@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+        assert_eq!(created["name"], "Test User");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{}", created["id"]))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let fetched = body_json(response).await;
+        assert_eq!(fetched["email"], "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn get_missing_user_returns_404() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn create_user_rejects_invalid_email() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(create_request("Test User", "not-an-email"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn lenient_mode_ignores_unknown_fields() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"name":"Test","email":"t@example.com","naem":"typo"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn strict_mode_rejects_unknown_fields() {
+        let mut state = test_state();
+        state.config.strict_json_fields = true;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"name":"Test","email":"t@example.com","naem":"typo"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        assert!(
+            body["message"].as_str().unwrap().contains("naem"),
+            "message should name the unknown field: {body}"
+        );
+    }
+
+    fn upsert_request(name: &str, email: &str) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri("/users")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"name":"{name}","email":"{email}"}}"#
+            )))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn upsert_returns_201_on_insert_and_200_on_update() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(upsert_request("First", "upsert@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+
+        let response = app
+            .oneshot(upsert_request("Second", "upsert@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated = body_json(response).await;
+        assert_eq!(updated["id"], created["id"]);
+        assert_eq!(updated["name"], "Second");
+    }
+
+    /// Changing a user's email to one another row already holds is a
+    /// client mistake: 409, the same answer the create path gives, not a
+    /// masked 500.
+    #[tokio::test]
+    async fn updating_to_a_taken_email_conflicts() {
+        let app = test_app(test_state());
+
+        app.clone()
+            .oneshot(create_request("First", "first@example.com"))
+            .await
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(create_request("Second", "second@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{}", created["id"]))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"email":"first@example.com"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn conditional_update_applies_with_matching_version() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Test User", "test@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{}", created["id"]))
+                    .header("content-type", "application/json")
+                    .header(
+                        "if-unmodified-since",
+                        created["updated_at"].as_str().unwrap(),
+                    )
+                    .body(Body::from(r#"{"name":"Renamed"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated = body_json(response).await;
+        assert_eq!(updated["name"], "Renamed");
+    }
+
+    /// The optimistic-concurrency path hits the same unique index as a
+    /// plain update: a duplicate email with a matching version is still a
+    /// 409, not a masked 500.
+    #[tokio::test]
+    async fn conditional_update_to_a_taken_email_conflicts() {
+        let app = test_app(test_state());
+
+        app.clone()
+            .oneshot(create_request("First", "first@example.com"))
+            .await
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(create_request("Second", "second@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{}", created["id"]))
+                    .header("content-type", "application/json")
+                    .header(
+                        "if-unmodified-since",
+                        created["updated_at"].as_str().unwrap(),
+                    )
+                    .body(Body::from(r#"{"email":"first@example.com"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn conditional_update_rejects_stale_version() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Test User", "test@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{}", created["id"]))
+                    .header("content-type", "application/json")
+                    .header("if-unmodified-since", "2000-01-01T00:00:00Z")
+                    .body(Body::from(r#"{"name":"Renamed"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn list_includes_total_by_default() {
+        let app = test_app(test_state());
+
+        app.clone()
+            .oneshot(create_request("A", "a@example.com"))
+            .await
+            .unwrap();
+        app.clone()
+            .oneshot(create_request("B", "b@example.com"))
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["users"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn list_accepts_offset_at_the_limit_and_rejects_beyond_it() {
+        let mut state = test_state();
+        state.config.max_offset = 100;
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users?offset=100")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?offset=101")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    const SECRET: &str = "attribution-secret";
+
+    fn mint(sub: &str, scope: &str) -> String {
+        use jsonwebtoken::{EncodingKey, Header};
+        jsonwebtoken::encode(
+            &Header::default(),
+            &serde_json::json!({ "sub": sub, "scope": scope }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn authed_request(mut request: Request<Body>, token: &str) -> Request<Body> {
+        request
+            .headers_mut()
+            .insert("authorization", format!("Bearer {token}").parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn mutations_record_the_acting_principal() {
+        let mut state = test_state();
+        state.config.auth_jwt_secret = Some(SECRET.to_string());
+        let app = test_app(state);
+        let alice = mint("alice", "admin");
+        let bob = mint("bob", "admin");
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                create_request("Attributed", "attributed@example.com"),
+                &alice,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+        assert_eq!(created["created_by"], "alice");
+        assert_eq!(created["updated_by"], "alice");
+
+        let response = app
+            .oneshot(authed_request(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{}", created["id"]))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name":"Renamed"}"#))
+                    .unwrap(),
+                &bob,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated = body_json(response).await;
+        assert_eq!(updated["created_by"], "alice");
+        assert_eq!(updated["updated_by"], "bob");
+    }
+
+    /// Under `ENUMERATION_PROTECTION` a caller without a token cannot
+    /// tell a duplicate registration from a fresh one: both answer an
+    /// empty 202 after the same minimum duration (the paused clock makes
+    /// elapsed time count only the handler's own sleep).
+    #[tokio::test(start_paused = true)]
+    async fn protected_creations_are_indistinguishable_for_duplicates() {
+        let mut state = test_state();
+        state.config.enumeration_protection = true;
+        state.config.enumeration_min_response_ms = 250;
+        let app = test_app(state);
+        let floor = std::time::Duration::from_millis(250);
+
+        let started = tokio::time::Instant::now();
+        let response = app
+            .clone()
+            .oneshot(create_request("First", "enum@example.com"))
+            .await
+            .unwrap();
+        let fresh_elapsed = started.elapsed();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(bytes.is_empty(), "protected create must not leak a body");
+
+        let started = tokio::time::Instant::now();
+        let response = app
+            .clone()
+            .oneshot(create_request("Second", "enum@example.com"))
+            .await
+            .unwrap();
+        let duplicate_elapsed = started.elapsed();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(bytes.is_empty(), "duplicate must look exactly like success");
+
+        assert!(fresh_elapsed >= floor, "fresh: {fresh_elapsed:?}");
+        assert!(
+            duplicate_elapsed >= floor,
+            "duplicate: {duplicate_elapsed:?}"
+        );
+        let difference = fresh_elapsed
+            .checked_sub(duplicate_elapsed)
+            .unwrap_or_else(|| duplicate_elapsed - fresh_elapsed);
+        assert!(
+            difference < std::time::Duration::from_millis(5),
+            "timing oracle between outcomes: {difference:?}"
+        );
+
+        // The first request really created the user; the duplicate did
+        // not overwrite it.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["name"], "First");
+    }
+
+    /// The existence oracles close for anonymous callers under the mode.
+    #[tokio::test]
+    async fn protected_email_lookups_require_authentication() {
+        let mut state = test_state();
+        state.config.enumeration_protection = true;
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users/by-email/absent@example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/users/lookup")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"emails":["absent@example.com"]}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    /// A valid token keeps the precise contract: 201 with a body, 409 on
+    /// a duplicate, and working existence lookups.
+    #[tokio::test]
+    async fn token_holders_keep_precise_responses_under_enumeration_protection() {
+        let mut state = test_state();
+        state.config.auth_jwt_secret = Some(SECRET.to_string());
+        state.config.enumeration_protection = true;
+        let app = test_app(state);
+        let token = mint("ops", "users:read users:write");
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                create_request("Precise", "precise@example.com"),
+                &token,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["name"], "Precise");
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                create_request("Precise Again", "precise@example.com"),
+                &token,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app
+            .oneshot(authed_request(
+                Request::builder()
+                    .uri("/users/by-email/precise@example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+                &token,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn attribution_is_omitted_for_non_admin_callers() {
+        let mut state = test_state();
+        state.config.auth_jwt_secret = Some(SECRET.to_string());
+        let app = test_app(state);
+        let writer = mint("alice", "users:read users:write");
+
+        let response = app
+            .clone()
+            .oneshot(authed_request(
+                create_request("Attributed", "attributed@example.com"),
+                &writer,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+        assert!(created.get("created_by").is_none(), "body: {created}");
+        assert!(created.get("updated_by").is_none());
+
+        let response = app
+            .oneshot(authed_request(
+                Request::builder()
+                    .uri(format!("/users/{}", created["id"]))
+                    .body(Body::empty())
+                    .unwrap(),
+                &writer,
+            ))
+            .await
+            .unwrap();
+        let fetched = body_json(response).await;
+        assert!(fetched.get("created_by").is_none(), "body: {fetched}");
+    }
+
+    #[tokio::test]
+    async fn lookup_by_email_decodes_and_ignores_case() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Plus", "First.Last+tag@Example.com").await;
+
+        // Percent-encoded `@` and a literal `+` in the path both resolve,
+        // and the stored casing does not have to match the query's.
+        for uri in [
+            "/users/by-email/first.last+tag%40example.com",
+            "/users/by-email/First.Last%2Btag%40Example.com",
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "uri: {uri}");
+            assert_eq!(body_json(response).await["id"], id);
+        }
+    }
+
+    #[tokio::test]
+    async fn lookup_by_unknown_email_returns_404() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/by-email/nobody%40example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn tags_request(id: i64, tags: &str) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri(format!("/users/{id}/tags"))
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"tags":{tags}}}"#)))
+            .unwrap()
+    }
+
+    async fn created_id(app: &axum::Router, name: &str, email: &str) -> i64 {
+        let response = app
+            .clone()
+            .oneshot(create_request(name, email))
+            .await
+            .unwrap();
+        body_json(response).await["id"].as_i64().unwrap()
+    }
+
+    #[tokio::test]
+    async fn tags_are_attached_replaced_and_detached() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Tagged", "tagged@example.com").await;
+
+        // Attach: normalized, deduplicated, and sorted.
+        let response = app
+            .clone()
+            .oneshot(tags_request(id, r#"[" Beta ","VIP","beta"]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!(["beta", "vip"])
+        );
+
+        // Replace the whole set.
+        let response = app
+            .clone()
+            .oneshot(tags_request(id, r#"["early-adopter"]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}/tags"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!(["early-adopter"])
+        );
+
+        // An empty list detaches everything.
+        let response = app.clone().oneshot(tags_request(id, "[]")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_tag() {
+        let app = test_app(test_state());
+        let beta = created_id(&app, "Beta", "beta@example.com").await;
+        created_id(&app, "Plain", "plain@example.com").await;
+        app.clone()
+            .oneshot(tags_request(beta, r#"["beta"]"#))
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?tag=Beta")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["users"][0]["id"], beta);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_search_and_supports_keyset_pagination() {
+        let app = test_app(test_state());
+        let first = created_id(&app, "Ada Smith", "ada@example.com").await;
+        let second = created_id(&app, "Grace Smith", "grace@example.com").await;
+        created_id(&app, "Other Person", "other@example.com").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users?search=smith")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = body_json(response).await;
+        assert_eq!(body["total"], 2);
+
+        // Keyset continuation from the first match.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users?search=smith&after_id={first}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = body_json(response).await;
+        assert_eq!(body["users"].as_array().unwrap().len(), 1);
+        assert_eq!(body["users"][0]["id"], second);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?after_id=1&offset=5")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn list_with_etag(
+        app: &axum::Router,
+        uri: &str,
+        if_none_match: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(etag) = if_none_match {
+            builder = builder.header("if-none-match", etag);
+        }
+        let response = app
+            .clone()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        (status, etag)
+    }
+
+    #[tokio::test]
+    async fn list_etag_revalidates_with_304_until_the_collection_changes() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Poll Target", "poll@example.com").await;
+        let other = created_id(&app, "Bystander", "bystander@example.com").await;
+
+        let (status, etag) = list_with_etag(&app, "/users?limit=50", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(etag.starts_with("W/\""), "weak validator expected: {etag}");
+
+        // An unchanged collection revalidates without a body.
+        let (status, unchanged) = list_with_etag(&app, "/users?limit=50", Some(&etag)).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(unchanged, etag);
+
+        // An update moves `max(updated_at)` and invalidates the tag.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{id}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name":"Renamed"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, after_update) = list_with_etag(&app, "/users?limit=50", Some(&etag)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(after_update, etag);
+
+        // A bare delete changes no surviving row, but the count moves.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/users/{other}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let (status, after_delete) =
+            list_with_etag(&app, "/users?limit=50", Some(&after_update)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(after_delete, after_update);
+    }
+
+    #[tokio::test]
+    async fn list_etags_differ_per_filter_and_page() {
+        let app = test_app(test_state());
+        created_id(&app, "Ada Smith", "ada@example.com").await;
+        created_id(&app, "Other Person", "other@example.com").await;
+
+        let (_, plain) = list_with_etag(&app, "/users", None).await;
+        let (_, filtered) = list_with_etag(&app, "/users?search=smith", None).await;
+        let (_, paged) = list_with_etag(&app, "/users?limit=1&offset=1", None).await;
+
+        assert_ne!(plain, filtered);
+        assert_ne!(plain, paged);
+        assert_ne!(filtered, paged);
+    }
+
+    fn lookup_request(emails: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/users/lookup")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"emails":{emails}}}"#)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lookup_resolves_a_mixed_batch_in_one_request() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Known", "known@example.com").await;
+
+        let response = app
+            .oneshot(lookup_request(
+                r#"["Known@Example.com", "missing@example.com"]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        // Keys are the emails exactly as requested; matching is
+        // case-insensitive and misses come back as null.
+        assert_eq!(body["Known@Example.com"]["id"], id);
+        assert!(body["missing@example.com"].is_null());
+    }
+
+    #[tokio::test]
+    async fn oversized_lookup_batches_are_rejected() {
+        let mut state = test_state();
+        state.config.lookup_max_emails = 2;
+        let app = test_app(state);
+
+        let response = app
+            .oneshot(lookup_request(
+                r#"["a@example.com", "b@example.com", "c@example.com"]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_user_cascades_to_its_tags() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Doomed", "doomed@example.com").await;
+        app.clone()
+            .oneshot(tags_request(id, r#"["beta"]"#))
+            .await
+            .unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?tag=beta")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["total"], 0);
+    }
+
+    #[tokio::test]
+    async fn invalid_tag_names_are_rejected() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Tagged", "tagged@example.com").await;
+
+        for tags in [
+            r#"["   "]"#.to_string(),
+            format!(r#"["{}"]"#, "x".repeat(51)),
+        ] {
+            let response = app.clone().oneshot(tags_request(id, &tags)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "tags: {tags}");
+        }
+    }
+
+    #[tokio::test]
+    async fn tags_of_a_missing_user_are_not_found() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(tags_request(42, r#"["beta"]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/42/tags")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n-test-image-data";
+
+    fn avatar_upload(id: i64, content_type: &str, bytes: &[u8]) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri(format!("/users/{id}/avatar"))
+            .header("content-type", content_type)
+            .body(Body::from(bytes.to_vec()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn avatar_round_trips_with_etag_and_304() {
+        let app = test_app(test_state());
+        let id = created_id(&app, "Pictured", "pictured@example.com").await;
+
+        let response = app
+            .clone()
+            .oneshot(avatar_upload(id, "image/png", PNG))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}/avatar"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+        assert!(response.headers()["cache-control"]
+            .to_str()
+            .unwrap()
+            .contains("immutable"));
+        use http_body_util::BodyExt;
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&bytes[..], PNG);
+
+        // A cached copy revalidates to 304 via If-None-Match.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}/avatar"))
+                    .header("if-none-match", &etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn identical_avatars_share_one_blob() {
+        let repository = std::sync::Arc::new(crate::repository::MemoryUserRepository::new());
+        let mut state = test_state();
+        state.repository = repository.clone();
+        let app = test_app(state);
+        let first = created_id(&app, "First", "first@example.com").await;
+        let second = created_id(&app, "Second", "second@example.com").await;
+
+        for id in [first, second] {
+            let response = app
+                .clone()
+                .oneshot(avatar_upload(id, "image/png", PNG))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        }
+        assert_eq!(repository.avatar_blob_count(), 1);
+
+        // Deleting one referent must not take the shared blob with it.
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/users/{first}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{second}/avatar"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn oversized_and_mislabeled_avatars_are_rejected() {
+        let mut state = test_state();
+        state.config.avatar_max_bytes = 64;
+        let app = test_app(state);
+        let id = created_id(&app, "Pictured", "pictured@example.com").await;
+
+        let mut oversized = PNG.to_vec();
+        oversized.resize(65, 0);
+        let response = app
+            .clone()
+            .oneshot(avatar_upload(id, "image/png", &oversized))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // PNG bytes labelled as JPEG fail the magic check.
+        let response = app
+            .oneshot(avatar_upload(id, "image/jpeg", PNG))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_omits_total_when_opted_out() {
+        let app = test_app(test_state());
+
+        app.clone()
+            .oneshot(create_request("A", "a@example.com"))
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?with_total=false")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert!(body.get("total").is_none());
+        assert_eq!(body["users"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn emails_normalize_on_insert_and_cased_duplicates_collapse() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Mixed", "  MiXeD@Example.COM "))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["email"], "mixed@example.com");
+
+        // A differently-cased duplicate resolves to the same address and
+        // trips the uniqueness check.
+        let response = app
+            .oneshot(create_request("Mixed Again", "mixed@EXAMPLE.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn concurrent_duplicate_creates_yield_one_winner() {
+        let app = test_app(test_state());
+
+        let (first, second) = tokio::join!(
+            app.clone()
+                .oneshot(create_request("Racer A", "racer@example.com")),
+            app.clone()
+                .oneshot(create_request("Racer B", "racer@example.com")),
+        );
+        let mut statuses = [first.unwrap().status(), second.unwrap().status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+    }
+
+    #[tokio::test]
+    async fn prefer_return_existing_replays_the_surviving_row() {
+        let app = test_app(test_state());
+        let with_prefer = |name: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/users")
+                .header("content-type", "application/json")
+                .header("prefer", "return=existing")
+                .body(Body::from(format!(
+                    r#"{{"name":"{name}","email":"prefer@example.com"}}"#
+                )))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(with_prefer("First")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-created"], "true");
+        let created = body_json(response).await;
+
+        // The duplicate gets the survivor back instead of a 409.
+        let response = app.clone().oneshot(with_prefer("Second")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-created"], "false");
+        let existing = body_json(response).await;
+        assert_eq!(existing["id"], created["id"]);
+        assert_eq!(existing["name"], "First");
+
+        // Without the preference the conflict remains visible.
+        let response = app
+            .oneshot(create_request("Third", "prefer@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn normalization_can_be_disabled() {
+        let mut state = test_state();
+        state.config.normalize_emails = false;
+        let app = test_app(state);
+
+        // Padding survives to storage when normalization is off; case does
+        // not, because `EmailAddress` lowercases at construction.
+        let response = app
+            .oneshot(create_request("Verbatim", " Padded@Example.com "))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["email"], " padded@example.com ");
+    }
+
+    #[tokio::test]
+    async fn history_records_insert_and_update_row_images() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Audited", "audited@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+        let id = created["id"].as_i64().unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{id}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name":"Renamed"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}/history"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let history = body_json(response).await;
+        let entries = history.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0]["action"], "insert");
+        assert!(entries[0]["old_row"].is_null());
+        assert_eq!(entries[0]["new_row"]["name"], "Audited");
+
+        assert_eq!(entries[1]["action"], "update");
+        assert_eq!(entries[1]["old_row"]["name"], "Audited");
+        assert_eq!(entries[1]["new_row"]["name"], "Renamed");
+    }
+
+    #[tokio::test]
+    async fn changes_feed_returns_only_users_modified_after_the_cursor() {
+        let app = test_app(test_state());
+        app.clone()
+            .oneshot(create_request("Stale", "stale@example.com"))
+            .await
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(create_request("Fresh", "fresh@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+        // A client that has synced both creations resumes from the
+        // newest `updated_at` it saw.
+        let since = created["updated_at"].as_str().unwrap().to_string();
+        let fresh_id = created["id"].as_i64().unwrap();
+
+        // The cursor has millisecond precision; make sure the update lands
+        // in a later millisecond than the creations.
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/users/{fresh_id}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name":"Fresher"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/changes?since={since}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let changes = body_json(response).await;
+        let entries = changes.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["id"].as_i64().unwrap(), fresh_id);
+        assert_eq!(entries[0]["name"], "Fresher");
+
+        // Resuming from the update's own timestamp drains the feed.
+        let since = entries[0]["updated_at"].as_str().unwrap();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/changes?since={since}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await.as_array().unwrap().len(), 0);
+
+        // `since` is mandatory; there is no "everything ever" delta.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/changes")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn an_over_limit_scan_fails_as_a_masked_500() {
+        // A deliberately tiny ceiling makes the changes feed (the one
+        // unbounded scan) return more rows than the guard allows.
+        let mut state = test_state();
+        state.repository =
+            std::sync::Arc::new(crate::repository::MemoryUserRepository::new().with_row_limit(2));
+        let app = test_app(state);
+        for (name, email) in [
+            ("One", "one@example.com"),
+            ("Two", "two@example.com"),
+            ("Three", "three@example.com"),
+        ] {
+            app.clone()
+                .oneshot(create_request(name, email))
+                .await
+                .unwrap();
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/changes?since=1970-01-01T00:00:00Z")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        // The bug is internal; the body must not hint at the guard.
+        let body = body_json(response).await;
+        assert_eq!(body["error"], "INTERNAL_ERROR");
+    }
+
+    #[tokio::test]
+    async fn stats_aggregate_over_the_populated_table() {
+        let app = test_app(test_state());
+        app.clone()
+            .oneshot(create_request("First", "first@example.com"))
+            .await
+            .unwrap();
+        app.clone()
+            .oneshot(create_request("Second", "second@example.com"))
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/stats")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats = body_json(response).await;
+        assert_eq!(stats["count"], 2);
+        // RFC 3339 timestamps order lexicographically.
+        let first = stats["first_created_at"].as_str().unwrap();
+        let last = stats["last_created_at"].as_str().unwrap();
+        assert!(first <= last);
+    }
+
+    #[tokio::test]
+    async fn stats_report_an_empty_table_as_nulls() {
+        let app = test_app(test_state());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/stats")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats = body_json(response).await;
+        assert_eq!(stats["count"], 0);
+        assert!(stats["first_created_at"].is_null());
+        assert!(stats["last_created_at"].is_null());
+    }
+
+    #[tokio::test]
+    async fn fields_parameter_prunes_listing_entries_but_not_the_envelope() {
+        let app = test_app(test_state());
+        app.clone()
+            .oneshot(create_request("Sparse", "sparse@example.com"))
+            .await
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?fields=name")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+
+        // The envelope's meta is untouched by entry shaping.
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["limit"], 50);
+        assert_eq!(body["offset"], 0);
+
+        let entry = &body["users"][0];
+        // `id` rides along even when not requested; everything else is gone.
+        assert_eq!(entry["name"], "Sparse");
+        assert!(entry["id"].is_number());
+        assert_eq!(entry.as_object().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fields_parameter_changes_the_collection_etag() {
+        let app = test_app(test_state());
+        app.clone()
+            .oneshot(create_request("Sparse", "sparse@example.com"))
+            .await
+            .unwrap();
+
+        let etag_of = |uri: &str| {
+            let app = app.clone();
+            let uri = uri.to_string();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response.headers()["etag"].to_str().unwrap().to_string()
+            }
+        };
+        assert_ne!(etag_of("/users").await, etag_of("/users?fields=name").await);
+    }
+
+    #[tokio::test]
+    async fn unknown_fields_are_rejected_and_listed() {
+        let app = test_app(test_state());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users?fields=name,shoe_size,favourites")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        let message = body["message"].as_str().unwrap();
+        assert!(message.contains("shoe_size") && message.contains("favourites"));
+    }
+
+    #[tokio::test]
+    async fn fields_parameter_applies_to_the_single_user_get() {
+        let app = test_app(test_state());
+        let response = app
+            .clone()
+            .oneshot(create_request("Sparse", "sparse@example.com"))
+            .await
+            .unwrap();
+        let id = body_json(response).await["id"].as_i64().unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}?fields=email"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["email"], "sparse@example.com");
+        assert_eq!(body["id"], id);
+        assert_eq!(body.as_object().unwrap().len(), 2);
+    }
+
+    /// A [`crate::webhooks::WebhookSender`] that records delivered bodies,
+    /// so tests can fish the confirmation token out of the outbox the way a
+    /// real receiver would.
+    #[derive(Default)]
+    struct CaptureSender {
+        bodies: std::sync::Mutex<Vec<serde_json::Value>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::webhooks::WebhookSender for CaptureSender {
+        async fn send(
+            &self,
+            _url: &str,
+            _headers: &[(&'static str, String)],
+            body: &[u8],
+        ) -> std::result::Result<(), String> {
+            self.bodies
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(body).unwrap());
+            Ok(())
+        }
+    }
+
+    /// A test state with the outbox armed, so `publish_event` enqueues.
+    fn state_with_outbox() -> crate::AppState {
+        let mut state = test_state();
+        state.config.webhook_url = Some("http://hook.test/events".to_string());
+        state
+    }
+
+    /// Drain the outbox and return the token carried by the latest
+    /// `user.email_change_requested` event.
+    async fn issued_token(webhooks: &crate::webhooks::WebhookOutbox) -> String {
+        let sender = CaptureSender::default();
+        webhooks
+            .deliver_due(
+                &sender,
+                "http://hook.test/events",
+                std::time::Duration::from_secs(300),
+                chrono::Utc::now(),
+            )
+            .await;
+        let bodies = sender.bodies.lock().unwrap();
+        bodies
+            .iter()
+            .rev()
+            .find(|body| body["kind"] == "user.email_change_requested")
+            .expect("an email-change event was enqueued")["payload"]["token"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn email_change_request(id: i64, email: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/users/{id}/email-change"))
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"email":"{email}"}}"#)))
+            .unwrap()
+    }
+
+    fn confirm_request(token: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/users/email-change/confirm")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"token":"{token}"}}"#)))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn email_change_applies_only_once_the_token_is_confirmed() {
+        let state = state_with_outbox();
+        let webhooks = state.webhooks.clone();
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Mover", "old@example.com"))
+            .await
+            .unwrap();
+        let id = body_json(response).await["id"].as_i64().unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(email_change_request(id, "new@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // The old address still serves until the token comes back.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["email"], "old@example.com");
+
+        let token = issued_token(&webhooks).await;
+        let response = app.clone().oneshot(confirm_request(&token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let confirmed = body_json(response).await;
+        assert_eq!(confirmed["id"], id);
+        assert_eq!(confirmed["email"], "new@example.com");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["email"], "new@example.com");
+    }
+
+    #[tokio::test]
+    async fn expired_email_change_tokens_are_rejected() {
+        let mut state = state_with_outbox();
+        state.config.email_change_token_ttl_secs = 0;
+        let webhooks = state.webhooks.clone();
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Late", "late@example.com"))
+            .await
+            .unwrap();
+        let id = body_json(response).await["id"].as_i64().unwrap();
+        let response = app
+            .clone()
+            .oneshot(email_change_request(id, "fresh@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let token = issued_token(&webhooks).await;
+        let response = app.clone().oneshot(confirm_request(&token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+
+        // The address never changed.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["email"], "late@example.com");
+    }
+
+    #[tokio::test]
+    async fn email_change_tokens_are_single_use() {
+        let state = state_with_outbox();
+        let webhooks = state.webhooks.clone();
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Once", "once@example.com"))
+            .await
+            .unwrap();
+        let id = body_json(response).await["id"].as_i64().unwrap();
+        app.clone()
+            .oneshot(email_change_request(id, "twice@example.com"))
+            .await
+            .unwrap();
+
+        let token = issued_token(&webhooks).await;
+        let response = app.clone().oneshot(confirm_request(&token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A replay finds the token gone, exactly like a made-up one.
+        let response = app.clone().oneshot(confirm_request(&token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.oneshot(confirm_request("no-such-token")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn a_registration_landing_between_request_and_confirm_conflicts() {
+        let state = state_with_outbox();
+        let webhooks = state.webhooks.clone();
+        let app = test_app(state);
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Racer", "racer@example.com"))
+            .await
+            .unwrap();
+        let id = body_json(response).await["id"].as_i64().unwrap();
+        let response = app
+            .clone()
+            .oneshot(email_change_request(id, "contested@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // Someone registers the requested address before the confirmation
+        // arrives.
+        let response = app
+            .clone()
+            .oneshot(create_request("Squatter", "contested@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let token = issued_token(&webhooks).await;
+        let response = app.clone().oneshot(confirm_request(&token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // The requester keeps their original address.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["email"], "racer@example.com");
+    }
+
+    #[tokio::test]
+    async fn public_id_is_a_uuid_and_changes_on_rotation() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(create_request("Opaque", "opaque@example.com"))
+            .await
+            .unwrap();
+        let created = body_json(response).await;
+        let id = created["id"].as_i64().unwrap();
+        let public_id = created["public_id"].as_str().unwrap().to_string();
+        assert!(super::is_uuid(&public_id), "public_id: {public_id}");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/users/{id}/rotate-public-id"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let rotated = body_json(response).await;
+        let fresh = rotated["public_id"].as_str().unwrap().to_string();
+        assert_eq!(rotated["id"], id);
+        assert!(super::is_uuid(&fresh), "public_id: {fresh}");
+        assert_ne!(fresh, public_id);
+
+        // The retired identifier stops resolving; the fresh one works.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/by-public-id/{public_id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/users/by-public-id/{fresh}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["id"], id);
+    }
+
+    #[tokio::test]
+    async fn public_id_lookup_validates_the_path_segment() {
+        let app = test_app(test_state());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/users/by-public-id/not-a-uuid")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Well-formed but unknown is a plain miss.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/users/by-public-id/9e107d9d-3720-4f2a-8a3c-871d7f0e4b55")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,205 @@
+//! Tokio runtime health gauges on `/metrics` (`runtime-metrics` feature).
+//!
+//! During an incident it is otherwise hard to tell database latency from
+//! a starving runtime. A background sampler refreshes these gauges every
+//! [`SAMPLE_INTERVAL`] from [`tokio::runtime::RuntimeMetrics`] — a few
+//! atomic loads per tick, cheap enough to leave on in production — and
+//! stops with the server.
+//!
+//! The stable subset (worker count, alive tasks, global queue depth) is
+//! always exported. Per-worker busy ratios, local queue depths, and steal
+//! counts sit behind tokio's unstable metrics API, which a cargo feature
+//! alone cannot switch on: those gauges additionally require building
+//! with `RUSTFLAGS="--cfg tokio_unstable"`.
+
+use once_cell::sync::Lazy;
+use prometheus::{register_int_gauge, IntGauge};
+use std::time::Duration;
+
+/// How often the background sampler refreshes the gauges.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Worker threads in the runtime; constant after startup, exported so
+/// the per-worker series have a denominator.
+static TOKIO_WORKERS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("tokio_runtime_workers", "Tokio runtime worker threads")
+        .expect("metric registration")
+});
+
+/// Tasks alive (spawned and not yet completed) at the last sample.
+static TOKIO_ALIVE_TASKS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "tokio_runtime_alive_tasks",
+        "Tasks alive in the tokio runtime"
+    )
+    .expect("metric registration")
+});
+
+/// Tasks waiting in the runtime's global (injection) queue; a depth that
+/// stays high means the workers cannot keep up.
+static TOKIO_GLOBAL_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "tokio_runtime_global_queue_depth",
+        "Tasks waiting in the tokio runtime's global queue"
+    )
+    .expect("metric registration")
+});
+
+/// Fraction of the last sample interval each worker spent polling tasks.
+#[cfg(tokio_unstable)]
+static TOKIO_WORKER_BUSY_RATIO: Lazy<prometheus::GaugeVec> = Lazy::new(|| {
+    prometheus::register_gauge_vec!(
+        "tokio_runtime_worker_busy_ratio",
+        "Fraction of the last sample interval the worker spent polling tasks",
+        &["worker"]
+    )
+    .expect("metric registration")
+});
+
+/// Tasks waiting in each worker's local queue at the last sample.
+#[cfg(tokio_unstable)]
+static TOKIO_WORKER_LOCAL_QUEUE_DEPTH: Lazy<prometheus::IntGaugeVec> = Lazy::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "tokio_runtime_worker_local_queue_depth",
+        "Tasks waiting in the worker's local queue",
+        &["worker"]
+    )
+    .expect("metric registration")
+});
+
+/// Tasks each worker has stolen from siblings since startup.
+#[cfg(tokio_unstable)]
+static TOKIO_WORKER_STEALS: Lazy<prometheus::IntGaugeVec> = Lazy::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "tokio_runtime_worker_steals_total",
+        "Tasks the worker has stolen from sibling workers since startup",
+        &["worker"]
+    )
+    .expect("metric registration")
+});
+
+/// One snapshot-to-snapshot sampler over a runtime's metrics handle.
+///
+/// Split out from the loop in [`sampler`] so tests can drive samples
+/// deterministically instead of waiting out the interval.
+pub struct RuntimeSampler {
+    metrics: tokio::runtime::RuntimeMetrics,
+    #[cfg(tokio_unstable)]
+    last_busy: Vec<Duration>,
+    #[cfg(tokio_unstable)]
+    last_sampled: std::time::Instant,
+}
+
+impl RuntimeSampler {
+    #[must_use]
+    pub fn new(handle: &tokio::runtime::Handle) -> Self {
+        let metrics = handle.metrics();
+        #[cfg(tokio_unstable)]
+        let workers = metrics.num_workers();
+        Self {
+            metrics,
+            #[cfg(tokio_unstable)]
+            last_busy: vec![Duration::ZERO; workers],
+            #[cfg(tokio_unstable)]
+            last_sampled: std::time::Instant::now(),
+        }
+    }
+
+    /// Refresh every gauge from the current runtime counters.
+    pub fn sample(&mut self) {
+        TOKIO_WORKERS.set(as_gauge(self.metrics.num_workers()));
+        TOKIO_ALIVE_TASKS.set(as_gauge(self.metrics.num_alive_tasks()));
+        TOKIO_GLOBAL_QUEUE_DEPTH.set(as_gauge(self.metrics.global_queue_depth()));
+        #[cfg(tokio_unstable)]
+        self.sample_workers();
+    }
+
+    #[cfg(tokio_unstable)]
+    fn sample_workers(&mut self) {
+        let elapsed = self.last_sampled.elapsed();
+        self.last_sampled = std::time::Instant::now();
+        for worker in 0..self.metrics.num_workers() {
+            let label = worker.to_string();
+            let busy = self.metrics.worker_total_busy_duration(worker);
+            let delta = busy.saturating_sub(self.last_busy[worker]);
+            self.last_busy[worker] = busy;
+            let ratio = if elapsed.is_zero() {
+                0.0
+            } else {
+                delta.as_secs_f64() / elapsed.as_secs_f64()
+            };
+            TOKIO_WORKER_BUSY_RATIO
+                .with_label_values(&[&label])
+                .set(ratio);
+            TOKIO_WORKER_LOCAL_QUEUE_DEPTH
+                .with_label_values(&[&label])
+                .set(as_gauge(self.metrics.worker_local_queue_depth(worker)));
+            TOKIO_WORKER_STEALS
+                .with_label_values(&[&label])
+                .set(i64::try_from(self.metrics.worker_steal_count(worker)).unwrap_or(i64::MAX));
+        }
+    }
+}
+
+fn as_gauge(value: usize) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+/// Refresh the runtime gauges every [`SAMPLE_INTERVAL`] until shutdown.
+pub async fn sampler(
+    handle: tokio::runtime::Handle,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    let mut sampler = RuntimeSampler::new(&handle);
+    tracing::info!("runtime metrics sampler started");
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            () = &mut shutdown => break,
+            () = tokio::time::sleep(SAMPLE_INTERVAL) => sampler.sample(),
+        }
+    }
+    tracing::info!("runtime metrics sampler stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of a gauge in the rendered exposition text.
+    fn gauge_value(rendered: &str, name: &str) -> f64 {
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{name} ")))
+            .unwrap_or_else(|| panic!("{name} missing from /metrics output"))
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn runtime_gauges_are_exported_after_load() {
+        let mut sampler = RuntimeSampler::new(&tokio::runtime::Handle::current());
+        let tasks: Vec<_> = (0..64)
+            .map(|_| tokio::spawn(tokio::task::yield_now()))
+            .collect();
+        sampler.sample();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        sampler.sample();
+
+        let rendered = crate::metrics::render();
+        for name in [
+            "tokio_runtime_workers",
+            "tokio_runtime_alive_tasks",
+            "tokio_runtime_global_queue_depth",
+        ] {
+            assert!(
+                gauge_value(&rendered, name) >= 0.0,
+                "{name} should be non-negative"
+            );
+        }
+        assert!(gauge_value(&rendered, "tokio_runtime_workers") >= 1.0);
+    }
+}
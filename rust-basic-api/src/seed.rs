@@ -0,0 +1,185 @@
+//! Deterministic development data, behind the `--seed [count]` CLI mode.
+//!
+//! Users are generated from a fixed RNG seed, so the same seed always
+//! produces the same names and emails; re-running skips emails that
+//! already exist instead of failing, making the command idempotent. The
+//! generator is exposed separately from the CLI entry point so test
+//! fixtures can reuse it.
+
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::models::CreateUserRequest;
+use crate::repository::{self, SqlxUserRepository, UserRepository};
+
+/// Default number of users inserted when `--seed` is given no count.
+pub const DEFAULT_COUNT: usize = 100;
+
+/// RNG seed used by the CLI mode. Fixed so every developer's database
+/// gets the same data.
+pub const DEFAULT_SEED: u64 = 0x5eed_0001;
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada",
+    "Alan",
+    "Barbara",
+    "Claude",
+    "Donald",
+    "Edsger",
+    "Frances",
+    "Grace",
+    "John",
+    "Katherine",
+    "Leslie",
+    "Margaret",
+    "Niklaus",
+    "Radia",
+    "Tim",
+    "Vint",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Allen", "Backus", "Cerf", "Dijkstra", "Hamilton", "Hopper", "Johnson", "Kay", "Lamport",
+    "Liskov", "Lovelace", "McCarthy", "Perlman", "Shannon", "Turing", "Wirth",
+];
+
+/// SplitMix64: small, dependency-free, and stable across platforms —
+/// exactly what reproducible seed data needs. Not for anything
+/// security-sensitive.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+        options[(self.next() % options.len() as u64) as usize]
+    }
+}
+
+/// Generate `count` deterministic users for the given seed. The index is
+/// baked into each email so the set is collision-free regardless of how
+/// often name pairs repeat.
+pub fn generate(count: usize, seed: u64) -> Vec<CreateUserRequest> {
+    let mut rng = SplitMix64(seed);
+    (0..count)
+        .map(|i| {
+            let first = rng.pick(FIRST_NAMES);
+            let last = rng.pick(LAST_NAMES);
+            CreateUserRequest {
+                name: format!("{first} {last}"),
+                email: format!(
+                    "{}.{}.{i}@seed.example.com",
+                    first.to_lowercase(),
+                    last.to_lowercase()
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Outcome of one seeding run, for the CLI summary and test assertions.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SeedSummary {
+    pub inserted: usize,
+    pub skipped: usize,
+}
+
+/// Insert the generated users, skipping any email that already exists so
+/// repeated runs are idempotent.
+pub async fn seed_users(
+    repository: &dyn UserRepository,
+    count: usize,
+    seed: u64,
+) -> Result<SeedSummary> {
+    let mut summary = SeedSummary {
+        inserted: 0,
+        skipped: 0,
+    };
+    for req in generate(count, seed) {
+        if repository.get_user_by_email(&req.email).await?.is_some() {
+            summary.skipped += 1;
+            continue;
+        }
+        repository.create_user(req, "seed").await?;
+        summary.inserted += 1;
+    }
+    Ok(summary)
+}
+
+/// CLI entry point for `--seed`: run migrations, insert the data, print a
+/// summary, and exit. Refuses to touch a production database unless
+/// `--force` is also given.
+pub async fn run(config: Config, count: usize, force: bool) -> anyhow::Result<()> {
+    if config.environment == crate::config::Environment::Production && !force {
+        anyhow::bail!("refusing to seed with ENVIRONMENT=production (pass --force to override)");
+    }
+
+    let pool = repository::create_pool(&config).await?;
+    repository::migrations::MIGRATOR.run(&pool).await?;
+
+    let db = repository::PoolHandle::new(pool);
+    let repository: Arc<dyn UserRepository> = Arc::new(SqlxUserRepository::new(
+        db,
+        std::time::Duration::from_millis(config.db_acquire_warn_threshold_ms),
+    ));
+    let summary = seed_users(repository.as_ref(), count, DEFAULT_SEED).await?;
+    println!(
+        "seeded {} users ({} inserted, {} already present)",
+        count, summary.inserted, summary.skipped
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryUserRepository;
+
+    #[tokio::test]
+    async fn seeding_twice_is_idempotent() {
+        let repository = MemoryUserRepository::new();
+
+        let first = seed_users(&repository, 10, DEFAULT_SEED).await.unwrap();
+        assert_eq!(
+            first,
+            SeedSummary {
+                inserted: 10,
+                skipped: 0
+            }
+        );
+
+        let second = seed_users(&repository, 10, DEFAULT_SEED).await.unwrap();
+        assert_eq!(
+            second,
+            SeedSummary {
+                inserted: 0,
+                skipped: 10
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn known_seed_produces_expected_emails() {
+        let repository = MemoryUserRepository::new();
+        seed_users(&repository, 3, 42).await.unwrap();
+
+        let generated = generate(3, 42);
+        for (i, req) in generated.iter().enumerate() {
+            // The generated emails are stable for a fixed seed, and each
+            // one resolves to a stored user.
+            assert!(req.email.ends_with(&format!(".{i}@seed.example.com")));
+            let user = repository.get_user_by_email(&req.email).await.unwrap();
+            assert_eq!(user.unwrap().name, req.name);
+        }
+        let emails: Vec<_> = generated.iter().map(|r| r.email.clone()).collect();
+        let again: Vec<_> = generate(3, 42).into_iter().map(|r| r.email).collect();
+        assert_eq!(again, emails);
+    }
+}
@@ -0,0 +1,843 @@
+//! Listener acquisition for [`run_application`](crate::run_application).
+//!
+//! Besides plain binding, two zero-downtime takeover mechanisms are
+//! supported on Unix:
+//!
+//! * systemd socket activation (`LISTEN_FDS`/`LISTEN_PID`), where a
+//!   pre-bound listener is inherited as fd 3;
+//! * `SO_REUSEPORT`, letting a new process bind the port while the old one
+//!   is still serving.
+//!
+//! On other platforms both silently fall back to a normal bind.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+
+use crate::config::Config;
+
+/// Shared readiness flag driving `GET /health/ready`.
+///
+/// The gate starts ready and is flipped to not-ready at the beginning of the
+/// shutdown drain sequence so load balancers stop routing new traffic here.
+#[derive(Clone, Default)]
+pub struct ReadinessGate(Arc<AtomicBool>);
+
+impl ReadinessGate {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.0.store(ready, Ordering::SeqCst);
+    }
+}
+
+/// Broadcast flag long-lived (streaming) handlers observe so they can end
+/// their responses cleanly once shutdown begins, instead of holding the
+/// drain open until the client disconnects.
+///
+/// A handler streaming a response should `select!` on
+/// [`cancelled`](Self::cancelled) and wrap up — final event, truncation
+/// trailer, whatever its format calls for — within the
+/// `STREAMING_DRAIN_BUDGET_SECS` budget, which is deliberately shorter
+/// than the drain delay so the wrap-up finishes before the listener
+/// closes. No handler in this service streams yet; the plumbing is here
+/// so the first one that does is shutdown-aware from the start.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    rx: tokio::sync::watch::Receiver<bool>,
+    tx: Arc<tokio::sync::watch::Sender<bool>>,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        Self {
+            rx,
+            tx: Arc::new(tx),
+        }
+    }
+
+    /// Mark shutdown as begun, waking every waiter.
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves once shutdown has been triggered (immediately if it
+    /// already was).
+    pub async fn cancelled(&self) {
+        let mut rx = self.rx.clone();
+        // An error means the sender is gone, which only happens past
+        // shutdown anyway.
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shutdown future for `axum::serve`: wait for the signal, fail readiness so
+/// the load balancer drains us, then let the delay elapse before the
+/// listener stops accepting. In-flight requests still finish after this
+/// future resolves.
+pub async fn drain_on_shutdown(
+    gate: ReadinessGate,
+    streaming: ShutdownSignal,
+    delay: Duration,
+    signal: impl std::future::Future<Output = ()>,
+) {
+    signal.await;
+    tracing::info!("shutdown signal received; marking not ready");
+    gate.set_ready(false);
+    // Streaming responses get the whole drain window to wrap up cleanly.
+    streaming.trigger();
+    tracing::info!("waiting {delay:?} for load balancer to observe readiness");
+    tokio::time::sleep(delay).await;
+    tracing::info!("drain delay elapsed; closing listener");
+}
+
+/// Warm lazily initialized state so the first routed request does not pay
+/// the cold-start cost: one in-process request through the full middleware
+/// stack, one JSON serialization of the user list shapes, and one pooled
+/// connection established and released. Runs before the readiness gate
+/// opens; skippable with `SKIP_WARMUP=true` for fast local iteration.
+/// Returns whether the warmup actually ran.
+pub async fn warmup(config: &Config, app: &axum::Router, pool: Option<&sqlx::PgPool>) -> bool {
+    if config.skip_warmup {
+        tracing::info!("warmup skipped (SKIP_WARMUP)");
+        return false;
+    }
+    let started = std::time::Instant::now();
+
+    let request = axum::http::Request::builder()
+        .uri("/health/ready")
+        .body(axum::body::Body::empty())
+        .expect("static warmup request");
+    let _ = tower::ServiceExt::oneshot(app.clone(), request).await;
+
+    let now = chrono::Utc::now();
+    let user = crate::models::User {
+        id: 0,
+        name: "warmup".to_string(),
+        email: "warmup@warmup.invalid"
+            .parse()
+            .expect("static warmup email"),
+        public_id: "00000000-0000-4000-8000-000000000000".to_string(),
+        created_at: now,
+        updated_at: now,
+        created_by: None,
+        updated_by: None,
+    };
+    let list = crate::routes::user_routes::UserListResponse {
+        users: vec![user],
+        total: Some(1),
+        limit: 1,
+        offset: 0,
+    };
+    let _ = serde_json::to_string(&list);
+
+    if let Some(pool) = pool {
+        match pool.acquire().await {
+            Ok(conn) => drop(conn),
+            Err(error) => {
+                tracing::warn!(%error, "warmup could not establish a database connection");
+            }
+        }
+    }
+
+    tracing::info!(
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "warmup complete"
+    );
+    true
+}
+
+/// Elapsed milliseconds spent in each measured startup phase, recorded by
+/// `run_application` and reported by [`warn_if_slow_startup`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StartupTimings {
+    pub pool_ms: u64,
+    pub migrations_ms: u64,
+    pub warmup_ms: u64,
+}
+
+/// Log a warning when startup exceeded its budget (`STARTUP_WARN_SECS`),
+/// with the per-phase breakdown in the log fields so the log already says
+/// whether the pool, migrations, or warmup ate the time. Returns whether
+/// the warning fired, so tests can assert on it without capturing log
+/// output.
+pub fn warn_if_slow_startup(total: Duration, budget: Duration, timings: &StartupTimings) -> bool {
+    if total <= budget {
+        return false;
+    }
+    tracing::warn!(
+        total_ms = total.as_millis() as u64,
+        budget_ms = budget.as_millis() as u64,
+        pool_ms = timings.pool_ms,
+        migrations_ms = timings.migrations_ms,
+        warmup_ms = timings.warmup_ms,
+        "startup exceeded STARTUP_WARN_SECS"
+    );
+    true
+}
+
+/// Resolves on SIGTERM or ctrl-c.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install ctrl-c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Build the rustls server configuration for mTLS termination, or `None`
+/// when `MTLS_CA_CERT` is not configured.
+///
+/// Client certificates are requested but not required at the handshake:
+/// only `/admin/*` routes demand one (enforced by
+/// [`crate::middleware::mtls::require_mtls_on_admin`]), so ordinary API
+/// clients connect with server-side TLS alone. A certificate that is
+/// presented must chain to the configured CA or the handshake fails.
+pub fn mtls_server_config(
+    config: &Config,
+) -> anyhow::Result<Option<Arc<tokio_rustls::rustls::ServerConfig>>> {
+    use tokio_rustls::rustls;
+
+    let Some(ca_path) = config.mtls_ca_cert.as_deref() else {
+        return Ok(None);
+    };
+    let (Some(cert_path), Some(key_path)) = (
+        config.mtls_server_cert.as_deref(),
+        config.mtls_server_key.as_deref(),
+    ) else {
+        anyhow::bail!("MTLS_CA_CERT requires MTLS_SERVER_CERT and MTLS_SERVER_KEY");
+    };
+
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in read_pem_certs(ca_path)? {
+        roots.add(cert)?;
+    }
+    let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots))
+        .allow_unauthenticated()
+        .build()?;
+
+    let key =
+        rustls_pemfile::private_key(&mut std::io::BufReader::new(std::fs::File::open(key_path)?))?
+            .ok_or_else(|| anyhow::anyhow!("no private key found in {key_path}"))?;
+    // `TLS_MIN_VERSION` sets the protocol floor; values below 1.2 were
+    // already rejected when the config was loaded.
+    let versions: &[&rustls::SupportedProtocolVersion] = match config.tls_min_version {
+        crate::config::TlsMinVersion::V1_2 => &[&rustls::version::TLS12, &rustls::version::TLS13],
+        crate::config::TlsMinVersion::V1_3 => &[&rustls::version::TLS13],
+    };
+    let mut tls = rustls::ServerConfig::builder_with_protocol_versions(versions)
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(read_pem_certs(cert_path)?, key)?;
+    // ALPN mirrors the `ENABLE_HTTP2` policy enforced by
+    // `middleware::reject_unsupported_versions`: only advertise `h2`
+    // when HTTP/2 is actually served.
+    tls.alpn_protocols = if config.enable_http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(Some(Arc::new(tls)))
+}
+
+fn read_pem_certs(
+    path: &str,
+) -> anyhow::Result<Vec<tokio_rustls::rustls::pki_types::CertificateDer<'static>>> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    Ok(rustls_pemfile::certs(&mut reader).collect::<Result<_, _>>()?)
+}
+
+/// Serve the router over plain HTTP until `shutdown` resolves.
+///
+/// The one `axum::serve` call in the crate: bootstrap and tests go
+/// through here (or [`serve_mtls`]) so a change in axum's server API
+/// touches exactly one module.
+pub async fn serve(
+    listener: TcpListener,
+    app: axum::Router,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+/// Serve the router over TLS, tagging every connection's requests with
+/// whether the peer presented a (CA-verified) client certificate.
+///
+/// `axum::serve` owns the plain-HTTP path; this loop exists because it
+/// cannot thread per-connection TLS state into the request extensions.
+pub async fn serve_mtls(
+    listener: TcpListener,
+    app: axum::Router,
+    tls: Arc<tokio_rustls::rustls::ServerConfig>,
+    shutdown: impl std::future::Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let acceptor = tokio_rustls::TlsAcceptor::from(tls);
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            () = &mut shutdown => return Ok(()),
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(error) => {
+                    tracing::debug!(%peer, %error, "TLS handshake failed");
+                    return;
+                }
+            };
+            let presented = stream.get_ref().1.peer_certificates().is_some();
+            let service = hyper_util::service::TowerToHyperService::new(app.layer(
+                axum::Extension(crate::middleware::mtls::ClientCertificate { presented }),
+            ));
+            let result =
+                hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new())
+                    .serve_connection_with_upgrades(hyper_util::rt::TokioIo::new(stream), service)
+                    .await;
+            if let Err(error) = result {
+                tracing::debug!(%peer, %error, "TLS connection ended with an error");
+            }
+        });
+    }
+}
+
+/// First file descriptor passed by systemd socket activation.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Acquire the server listener according to the configuration, preferring an
+/// inherited socket-activation fd, then a `SO_REUSEPORT` bind, then a plain
+/// bind.
+pub async fn acquire_listener(config: &Config) -> anyhow::Result<TcpListener> {
+    if let Some(listener) = inherited_listener()? {
+        tracing::info!("Using socket-activated listener inherited from parent");
+        return Ok(listener);
+    }
+
+    // A socket-activated fd was bound by the parent; only our own bind
+    // needs the privilege pre-check.
+    #[cfg(unix)]
+    check_bind_privilege(config.server_port)?;
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.server_port));
+    if config.so_reuseport {
+        if let Some(listener) = bind_reuseport(addr)? {
+            tracing::info!("Bound {addr} with SO_REUSEPORT");
+            return Ok(listener);
+        }
+    }
+
+    Ok(TcpListener::bind(addr).await?)
+}
+
+/// Bit index of `CAP_NET_BIND_SERVICE` in procfs capability masks.
+#[cfg(unix)]
+const CAP_NET_BIND_SERVICE: u32 = 10;
+
+/// Fail before binding when `SERVER_PORT` is a privileged port this
+/// process cannot bind, turning the kernel's opaque `EACCES` into an
+/// explanation of the options. The inputs come from `/proc/self/status`
+/// best-effort; anything unreadable passes, so a privileged setup can
+/// never be falsely rejected.
+#[cfg(unix)]
+fn check_bind_privilege(port: u16) -> anyhow::Result<()> {
+    let status = std::fs::read_to_string("/proc/self/status").ok();
+    if let Some(message) = privileged_port_error(
+        port,
+        status.as_deref().and_then(effective_uid),
+        status.as_deref().and_then(has_net_bind_service),
+    ) {
+        anyhow::bail!(message);
+    }
+    Ok(())
+}
+
+/// The decision behind [`check_bind_privilege`], pure so tests can feed
+/// it every uid/port/capability combination. `None` inputs mean "could
+/// not determine" and always pass.
+#[cfg(unix)]
+fn privileged_port_error(
+    port: u16,
+    effective_uid: Option<u32>,
+    can_bind_service: Option<bool>,
+) -> Option<String> {
+    if port >= 1024 || effective_uid? == 0 || can_bind_service? {
+        return None;
+    }
+    Some(format!(
+        "SERVER_PORT={port} is a privileged port and this process runs unprivileged \
+         without CAP_NET_BIND_SERVICE; use a port >= 1024, grant the capability \
+         (e.g. `setcap cap_net_bind_service=+ep` on the binary), or front the \
+         service with a proxy that owns the low port"
+    ))
+}
+
+/// Effective UID from a `/proc/self/status` dump: the second field of
+/// the `Uid:` line (real, effective, saved, filesystem).
+#[cfg(unix)]
+fn effective_uid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Whether the `CapEff` mask includes `CAP_NET_BIND_SERVICE`.
+#[cfg(unix)]
+fn has_net_bind_service(status: &str) -> Option<bool> {
+    let mask = u64::from_str_radix(
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))?
+            .trim(),
+        16,
+    )
+    .ok()?;
+    Some(mask & (1 << CAP_NET_BIND_SERVICE) != 0)
+}
+
+/// Return the listener passed via the systemd socket-activation convention,
+/// if any.
+#[cfg(unix)]
+fn inherited_listener() -> anyhow::Result<Option<TcpListener>> {
+    use std::os::unix::io::FromRawFd;
+
+    let Ok(listen_pid) = std::env::var("LISTEN_PID") else {
+        return Ok(None);
+    };
+    let Ok(listen_fds) = std::env::var("LISTEN_FDS") else {
+        return Ok(None);
+    };
+
+    if listen_pid.parse() != Ok(std::process::id()) {
+        return Ok(None);
+    }
+    if listen_fds.parse::<i32>().map_or(true, |n| n < 1) {
+        return Ok(None);
+    }
+
+    // SAFETY: under the socket-activation convention fd 3 is a listening
+    // socket owned by this process and nothing else has claimed it.
+    let std_listener = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    std_listener.set_nonblocking(true)?;
+    Ok(Some(TcpListener::from_std(std_listener)?))
+}
+
+#[cfg(not(unix))]
+fn inherited_listener() -> anyhow::Result<Option<TcpListener>> {
+    Ok(None)
+}
+
+/// Bind `addr` with `SO_REUSEPORT` set, so another process can share the
+/// port during a rolling restart.
+#[cfg(unix)]
+pub(crate) fn bind_reuseport(addr: SocketAddr) -> anyhow::Result<Option<TcpListener>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(Some(TcpListener::from_std(socket.into())?))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bind_reuseport(_addr: SocketAddr) -> anyhow::Result<Option<TcpListener>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use crate::test_helpers::{test_app, test_state};
+
+    #[tokio::test]
+    async fn shutdown_signal_wakes_every_clone() {
+        let signal = super::ShutdownSignal::new();
+        let observer = signal.clone();
+        assert!(!observer.is_triggered());
+
+        let waiter = tokio::spawn(async move { observer.cancelled().await });
+        signal.trigger();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("cancelled() resolves promptly after trigger")
+            .unwrap();
+        // Late subscribers see the triggered state immediately.
+        signal.cancelled().await;
+    }
+
+    #[tokio::test]
+    async fn warmup_runs_once_and_respects_the_skip_flag() {
+        let app = test_app(test_state());
+        let mut config = crate::config::Config::for_tests();
+
+        // No pool attached: the request and serializer phases still run.
+        assert!(super::warmup(&config, &app, None).await);
+
+        config.skip_warmup = true;
+        assert!(!super::warmup(&config, &app, None).await);
+    }
+
+    #[tokio::test]
+    async fn a_delayed_migration_step_trips_the_startup_warning() {
+        let started = std::time::Instant::now();
+        let mut timings = super::StartupTimings::default();
+
+        // Stand-in for a migration that takes longer than the whole budget.
+        let phase = std::time::Instant::now();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        timings.migrations_ms = phase.elapsed().as_millis() as u64;
+
+        assert!(super::warn_if_slow_startup(
+            started.elapsed(),
+            Duration::from_millis(10),
+            &timings,
+        ));
+        // A startup comfortably inside the budget stays quiet.
+        assert!(!super::warn_if_slow_startup(
+            Duration::from_millis(5),
+            Duration::from_secs(10),
+            &timings,
+        ));
+    }
+
+    #[tokio::test]
+    async fn admin_routes_require_a_client_certificate_over_mtls() {
+        use tokio_rustls::rustls;
+
+        // A throwaway CA, a server certificate for localhost, and a client
+        // certificate — all chained to the CA.
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = rcgen::KeyPair::generate().unwrap();
+        let server_cert = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca_cert, &ca_key)
+            .unwrap();
+        let client_key = rcgen::KeyPair::generate().unwrap();
+        let client_cert = rcgen::CertificateParams::new(vec!["client".to_string()])
+            .unwrap()
+            .signed_by(&client_key, &ca_cert, &ca_key)
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("rba-mtls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str, pem: String| {
+            let path = dir.join(name);
+            std::fs::write(&path, pem).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let mut state = test_state();
+        state.config.mtls_ca_cert = Some(path("ca.pem", ca_cert.pem()));
+        state.config.mtls_server_cert = Some(path("server.pem", server_cert.pem()));
+        state.config.mtls_server_key = Some(path("server.key", server_key.serialize_pem()));
+
+        // Both allowed protocol floors produce a working config.
+        let mut v13_config = state.config.clone();
+        v13_config.tls_min_version = crate::config::TlsMinVersion::V1_3;
+        assert!(super::mtls_server_config(&v13_config).unwrap().is_some());
+
+        let tls = super::mtls_server_config(&state.config)
+            .unwrap()
+            .expect("mTLS configured");
+        // ALPN mirrors the `ENABLE_HTTP2` toggle: HTTP/1.1-only by
+        // default, `h2` offered once enabled.
+        assert_eq!(tls.alpn_protocols, vec![b"http/1.1".to_vec()]);
+        let mut h2_config = state.config.clone();
+        h2_config.enable_http2 = true;
+        let h2_tls = super::mtls_server_config(&h2_config).unwrap().unwrap();
+        assert_eq!(
+            h2_tls.alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(super::serve_mtls(
+            listener,
+            crate::build_router(state),
+            tls,
+            std::future::pending(),
+        ));
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(ca_cert.der().clone()).unwrap();
+        let with_cert = std::sync::Arc::new(
+            rustls::ClientConfig::builder()
+                .with_root_certificates(roots.clone())
+                .with_client_auth_cert(
+                    vec![client_cert.der().clone()],
+                    rustls::pki_types::PrivateKeyDer::Pkcs8(client_key.serialize_der().into()),
+                )
+                .unwrap(),
+        );
+        let without_cert = std::sync::Arc::new(
+            rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        );
+
+        let response = tls_probe(addr, "/admin/routes", with_cert).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "got: {response}");
+
+        let response = tls_probe(addr, "/admin/routes", without_cert.clone())
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "got: {response}");
+
+        // Only the admin surface demands a certificate.
+        let response = tls_probe(addr, "/health", without_cert).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "got: {response}");
+
+        server.abort();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    async fn tls_probe(
+        addr: SocketAddr,
+        path: &str,
+        config: std::sync::Arc<tokio_rustls::rustls::ClientConfig>,
+    ) -> std::io::Result<String> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from("localhost")
+            .expect("valid server name");
+        let mut stream = tokio_rustls::TlsConnector::from(config)
+            .connect(server_name, stream)
+            .await?;
+        stream
+            .write_all(
+                format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.ok();
+        Ok(response)
+    }
+
+    async fn serve_on(listener: tokio::net::TcpListener) -> tokio::task::JoinHandle<()> {
+        let app = test_app(test_state());
+        tokio::spawn(async move {
+            super::serve(listener, app, std::future::pending())
+                .await
+                .unwrap();
+        })
+    }
+
+    async fn probe(addr: SocketAddr, path: &str) -> std::io::Result<String> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        stream
+            .write_all(
+                format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    async fn health_probe(addr: SocketAddr) -> std::io::Result<String> {
+        probe(addr, "/health").await
+    }
+
+    /// The kernel spreads connections across every listener bound to the
+    /// port, so a probe racing a dying listener can see a reset; retry a few
+    /// times before declaring the port dead.
+    async fn assert_health_ok(addr: SocketAddr) {
+        let mut last = String::new();
+        for _ in 0..10 {
+            if let Ok(response) = health_probe(addr).await {
+                if response.starts_with("HTTP/1.1 200") {
+                    return;
+                }
+                last = response;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("no healthy response from {addr}, last: {last}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reuseport_allows_overlapping_binds() {
+        let first = super::bind_reuseport(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .unwrap()
+            .unwrap();
+        let addr = first.local_addr().unwrap();
+
+        let first_task = serve_on(first).await;
+        assert_health_ok(addr).await;
+
+        // A second process (simulated here by a second listener) can bind the
+        // same port while the first is still serving.
+        let second = super::bind_reuseport(addr).unwrap().unwrap();
+        let second_task = serve_on(second).await;
+
+        assert_health_ok(addr).await;
+
+        // Traffic keeps flowing after the old listener goes away.
+        first_task.abort();
+        assert_health_ok(addr).await;
+        second_task.abort();
+    }
+
+    #[tokio::test]
+    async fn drain_fails_readiness_before_listener_closes() {
+        let state = test_state();
+        let gate = state.readiness.clone();
+        let app = test_app(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let streaming = super::ShutdownSignal::new();
+        let drain =
+            super::drain_on_shutdown(gate, streaming.clone(), Duration::from_millis(300), async {
+                shutdown_rx.await.ok();
+            });
+        let server = tokio::spawn(async move {
+            super::serve(listener, app, drain).await.unwrap();
+        });
+
+        // Fully ready before the signal.
+        assert_health_ok(addr).await;
+        let ready = probe(addr, "/health/ready").await.unwrap();
+        assert!(ready.starts_with("HTTP/1.1 200"), "got: {ready}");
+
+        shutdown_tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Streaming handlers are told to wrap up as soon as the drain
+        // starts, well before the listener closes.
+        assert!(streaming.is_triggered());
+
+        // During the drain window the listener still accepts, but readiness
+        // reports 503 so the load balancer steers traffic away.
+        let ready = probe(addr, "/health/ready").await.unwrap();
+        assert!(ready.starts_with("HTTP/1.1 503"), "got: {ready}");
+        let health = probe(addr, "/health").await.unwrap();
+        assert!(health.starts_with("HTTP/1.1 200"), "got: {health}");
+
+        // Once the drain delay elapses the listener closes.
+        server.await.unwrap();
+        assert!(probe(addr, "/health").await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn privileged_port_decision_matrix() {
+        use super::privileged_port_error;
+
+        // High ports never trip, whoever asks.
+        assert!(privileged_port_error(1024, Some(1000), Some(false)).is_none());
+        assert!(privileged_port_error(3000, None, None).is_none());
+
+        // Root and capability holders may bind low ports.
+        assert!(privileged_port_error(80, Some(0), Some(false)).is_none());
+        assert!(privileged_port_error(80, Some(1000), Some(true)).is_none());
+
+        // Unknown uid or capability must pass: the check is best-effort
+        // and a privileged setup must never be falsely rejected.
+        assert!(privileged_port_error(80, None, Some(false)).is_none());
+        assert!(privileged_port_error(80, Some(1000), None).is_none());
+
+        // The one failing combination: unprivileged, uncapable, low port.
+        let message = privileged_port_error(80, Some(1000), Some(false)).unwrap();
+        assert!(message.contains("port >= 1024"), "got: {message}");
+        assert!(message.contains("CAP_NET_BIND_SERVICE"), "got: {message}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn procfs_status_fields_are_parsed() {
+        let status = "Name:\tserver\nUid:\t1000\t1001\t1000\t1000\nCapEff:\t0000000000000400\n";
+        assert_eq!(super::effective_uid(status), Some(1001));
+        // Bit 10 set: CAP_NET_BIND_SERVICE granted.
+        assert_eq!(super::has_net_bind_service(status), Some(true));
+
+        let status = "Uid:\t1000\t1000\t1000\t1000\nCapEff:\t0000000000000000\n";
+        assert_eq!(super::has_net_bind_service(status), Some(false));
+        assert_eq!(super::effective_uid("garbage"), None);
+        assert_eq!(super::has_net_bind_service("garbage"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn binding_a_low_port_unprivileged_fails_with_guidance() {
+        // Root (or a capability grant) can bind 80 legitimately; there is
+        // nothing to assert in that environment.
+        let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        if super::effective_uid(&status) == Some(0)
+            || super::has_net_bind_service(&status) == Some(true)
+        {
+            return;
+        }
+
+        let mut config = crate::config::Config::for_tests();
+        config.server_port = 80;
+        let error = super::acquire_listener(&config)
+            .await
+            .expect_err("unprivileged bind of port 80 must fail the pre-check");
+        assert!(
+            error.to_string().contains("privileged port"),
+            "got: {error}"
+        );
+    }
+}
@@ -0,0 +1,523 @@
+//! Outbound webhook delivery.
+//!
+//! User mutations enqueue events into an in-process outbox; when
+//! `WEBHOOK_URL` is configured a worker drains it, POSTing each event with
+//! the originating request's trace context (`traceparent` rebuilt from the
+//! trace id captured at enqueue time) plus `X-Event-Id` and
+//! `X-Delivery-Attempt` headers. Failed deliveries are retried on the next
+//! drain pass until the event passes its delivery deadline
+//! (`created_at + WEBHOOK_MAX_AGE_SECS`), at which point it is dead-lettered
+//! instead of retried forever; dead letters are queryable through
+//! `GET /admin/webhooks/dead-letters`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// How often the delivery worker drains the outbox.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One event awaiting delivery.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEvent {
+    pub id: u64,
+    /// Event name, e.g. `user.created`.
+    pub kind: &'static str,
+    pub payload: serde_json::Value,
+    /// Trace id of the request that produced the event, captured from its
+    /// `traceparent` header; `None` for events produced outside a traced
+    /// request.
+    pub trace_id: Option<String>,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub created_at: DateTime<Utc>,
+    /// Delivery attempts made so far.
+    pub attempts: u32,
+}
+
+/// An event abandoned after passing its delivery deadline.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub event: WebhookEvent,
+    pub reason: String,
+    #[serde(with = "crate::models::serde_rfc3339")]
+    pub dead_lettered_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct OutboxInner {
+    next_id: u64,
+    queue: VecDeque<WebhookEvent>,
+    dead: Vec<DeadLetter>,
+}
+
+/// In-process event outbox shared between the request handlers (producers)
+/// and the delivery worker.
+#[derive(Default)]
+pub struct WebhookOutbox {
+    inner: Mutex<OutboxInner>,
+}
+
+impl WebhookOutbox {
+    /// Queue an event for delivery, stamping it with the current time.
+    pub fn enqueue(
+        &self,
+        kind: &'static str,
+        payload: serde_json::Value,
+        trace_id: Option<String>,
+    ) {
+        let mut inner = self.inner.lock().expect("outbox lock poisoned");
+        inner.next_id += 1;
+        let event = WebhookEvent {
+            id: inner.next_id,
+            kind,
+            payload,
+            trace_id,
+            created_at: Utc::now(),
+            attempts: 0,
+        };
+        inner.queue.push_back(event);
+    }
+
+    /// Dead letters accumulated so far, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.inner
+            .lock()
+            .expect("outbox lock poisoned")
+            .dead
+            .clone()
+    }
+
+    /// Events still awaiting delivery.
+    pub fn pending(&self) -> usize {
+        self.inner.lock().expect("outbox lock poisoned").queue.len()
+    }
+
+    /// Drain the queue once: events past their deadline
+    /// (`created_at + max_age` relative to `now`) are dead-lettered without
+    /// a delivery attempt; the rest are sent and, on failure, requeued for
+    /// the next pass. `now` is injected so tests can drive the deadline.
+    pub async fn deliver_due(
+        &self,
+        sender: &dyn WebhookSender,
+        url: &str,
+        max_age: Duration,
+        now: DateTime<Utc>,
+    ) {
+        let batch: Vec<WebhookEvent> = {
+            let mut inner = self.inner.lock().expect("outbox lock poisoned");
+            inner.queue.drain(..).collect()
+        };
+
+        for mut event in batch {
+            let deadline = event.created_at
+                + chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+            if now > deadline {
+                tracing::warn!(
+                    event_id = event.id,
+                    kind = event.kind,
+                    attempts = event.attempts,
+                    "webhook event passed its delivery deadline; dead-lettering"
+                );
+                let mut inner = self.inner.lock().expect("outbox lock poisoned");
+                inner.dead.push(DeadLetter {
+                    event,
+                    reason: "delivery deadline exceeded".to_string(),
+                    dead_lettered_at: now,
+                });
+                continue;
+            }
+
+            event.attempts += 1;
+            let headers = delivery_headers(&event);
+            let body = serde_json::json!({
+                "id": event.id,
+                "kind": event.kind,
+                "payload": event.payload,
+                "created_at": event.created_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            });
+            let body = serde_json::to_vec(&body).expect("webhook body serializes");
+            match sender.send(url, &headers, &body).await {
+                Ok(()) => {
+                    tracing::debug!(event_id = event.id, kind = event.kind, "webhook delivered");
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        event_id = event.id,
+                        kind = event.kind,
+                        attempt = event.attempts,
+                        %error,
+                        "webhook delivery failed; will retry until deadline"
+                    );
+                    let mut inner = self.inner.lock().expect("outbox lock poisoned");
+                    inner.queue.push_back(event);
+                }
+            }
+        }
+    }
+}
+
+/// Headers attached to one delivery attempt. `traceparent` is rebuilt from
+/// the stored trace id with a fresh span id per attempt so the receiver's
+/// traces join the originating request without claiming its span.
+fn delivery_headers(event: &WebhookEvent) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("x-event-id", event.id.to_string()),
+        ("x-delivery-attempt", event.attempts.to_string()),
+    ];
+    if let Some(trace_id) = &event.trace_id {
+        let span_id = (event.id << 8) | u64::from(event.attempts & 0xff);
+        headers.push(("traceparent", format!("00-{trace_id}-{span_id:016x}-01")));
+    }
+    headers
+}
+
+/// The trace id from an incoming `traceparent` header, for stamping onto
+/// events produced by the request. Malformed headers yield `None` rather
+/// than an error: trace context is best-effort.
+pub fn trace_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get("traceparent")?.to_str().ok()?;
+    let trace_id = value.split('-').nth(1)?;
+    if trace_id.len() == 32 && trace_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(trace_id.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// Transport a delivery attempt travels over, separated from the outbox so
+/// tests can capture or fail deliveries without a network.
+#[async_trait]
+pub trait WebhookSender: Send + Sync {
+    /// POST `body` as JSON to `url` with the given headers. `Ok` means the
+    /// receiver acknowledged with a 2xx status.
+    async fn send(
+        &self,
+        url: &str,
+        headers: &[(&'static str, String)],
+        body: &[u8],
+    ) -> std::result::Result<(), String>;
+}
+
+/// Minimal HTTP/1.1 transport over a plain TCP connection.
+///
+/// Deliberately `http`-only: webhook egress is expected to go through the
+/// cluster's egress proxy, which terminates TLS, so the crate does not need
+/// a TLS client dependency for this one call site.
+pub struct HttpWebhookSender;
+
+#[async_trait]
+impl WebhookSender for HttpWebhookSender {
+    async fn send(
+        &self,
+        url: &str,
+        headers: &[(&'static str, String)],
+        body: &[u8],
+    ) -> std::result::Result<(), String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("unsupported webhook url (http:// only): {url}"))?;
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, format!("/{path}")),
+            None => (rest, "/".to_string()),
+        };
+        let address = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{authority}:80")
+        };
+
+        let mut stream = tokio::net::TcpStream::connect(&address)
+            .await
+            .map_err(|e| format!("connect {address}: {e}"))?;
+        let mut request = format!(
+            "POST {path} HTTP/1.1\r\nhost: {authority}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        request.push_str("\r\n");
+
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("write: {e}"))?;
+        stream
+            .write_all(body)
+            .await
+            .map_err(|e| format!("write body: {e}"))?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .map_err(|e| format!("read response: {e}"))?;
+        let status_line = response
+            .split(|&b| b == b'\r')
+            .next()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .unwrap_or_default();
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| format!("malformed response: {status_line:?}"))?;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("receiver answered {status}"))
+        }
+    }
+}
+
+/// Run the delivery worker until `shutdown` resolves, draining the outbox
+/// every [`POLL_INTERVAL`].
+pub async fn delivery_worker(
+    outbox: std::sync::Arc<WebhookOutbox>,
+    url: String,
+    max_age: Duration,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    tracing::info!(%url, max_age_secs = max_age.as_secs(), "webhook delivery worker started");
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            () = &mut shutdown => break,
+            () = tokio::time::sleep(POLL_INTERVAL) => {
+                outbox
+                    .deliver_due(&HttpWebhookSender, &url, max_age, Utc::now())
+                    .await;
+            }
+        }
+    }
+    tracing::info!("webhook delivery worker stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A sender that records every delivery's headers and fails while the
+    /// scripted failure stack is non-empty.
+    #[derive(Default)]
+    struct RecordingSender {
+        deliveries: Mutex<Vec<Vec<(&'static str, String)>>>,
+        failures: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl WebhookSender for RecordingSender {
+        async fn send(
+            &self,
+            _url: &str,
+            headers: &[(&'static str, String)],
+            _body: &[u8],
+        ) -> std::result::Result<(), String> {
+            self.deliveries.lock().unwrap().push(headers.to_vec());
+            match self.failures.lock().unwrap().pop() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn header<'a>(headers: &'a [(&'static str, String)], name: &str) -> &'a str {
+        headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_else(|| panic!("missing header {name}"))
+    }
+
+    #[tokio::test]
+    async fn deliveries_carry_trace_and_attempt_headers() {
+        let outbox = WebhookOutbox::default();
+        let trace = "0af7651916cd43dd8448eb211c80319c";
+        outbox.enqueue(
+            "user.created",
+            serde_json::json!({"id": 1}),
+            Some(trace.to_string()),
+        );
+
+        let sender = RecordingSender::default();
+        outbox
+            .deliver_due(
+                &sender,
+                "http://hook.test/events",
+                Duration::from_secs(300),
+                Utc::now(),
+            )
+            .await;
+
+        let deliveries = sender.deliveries.lock().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        let headers = &deliveries[0];
+        assert_eq!(header(headers, "x-event-id"), "1");
+        assert_eq!(header(headers, "x-delivery-attempt"), "1");
+        let traceparent = header(headers, "traceparent");
+        assert!(
+            traceparent.starts_with(&format!("00-{trace}-")),
+            "traceparent: {traceparent}"
+        );
+        assert_eq!(outbox.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_retry_with_incremented_attempt() {
+        let outbox = WebhookOutbox::default();
+        outbox.enqueue("user.created", serde_json::json!({"id": 2}), None);
+
+        let sender = RecordingSender::default();
+        sender
+            .failures
+            .lock()
+            .unwrap()
+            .push("connection refused".to_string());
+        outbox
+            .deliver_due(
+                &sender,
+                "http://hook.test/events",
+                Duration::from_secs(300),
+                Utc::now(),
+            )
+            .await;
+        assert_eq!(outbox.pending(), 1);
+
+        outbox
+            .deliver_due(
+                &sender,
+                "http://hook.test/events",
+                Duration::from_secs(300),
+                Utc::now(),
+            )
+            .await;
+        let deliveries = sender.deliveries.lock().unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(header(&deliveries[0], "x-delivery-attempt"), "1");
+        assert_eq!(header(&deliveries[1], "x-delivery-attempt"), "2");
+        assert_eq!(outbox.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn events_past_deadline_are_dead_lettered_without_an_attempt() {
+        let outbox = WebhookOutbox::default();
+        outbox.enqueue("user.deleted", serde_json::json!({"id": 3}), None);
+        let created_at = outbox.inner.lock().unwrap().queue[0].created_at;
+
+        let sender = RecordingSender::default();
+        let past_deadline = created_at + chrono::Duration::seconds(301);
+        outbox
+            .deliver_due(
+                &sender,
+                "http://hook.test/events",
+                Duration::from_secs(300),
+                past_deadline,
+            )
+            .await;
+
+        assert!(sender.deliveries.lock().unwrap().is_empty());
+        assert_eq!(outbox.pending(), 0);
+        let dead = outbox.dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].event.kind, "user.deleted");
+        assert_eq!(dead[0].reason, "delivery deadline exceeded");
+    }
+
+    #[tokio::test]
+    async fn http_sender_posts_headers_to_a_capture_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let capture = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request[..n]).into_owned()
+        });
+
+        let outbox = WebhookOutbox::default();
+        let trace = "4bf92f3577b34da6a3ce929d0e0e4736";
+        outbox.enqueue(
+            "user.created",
+            serde_json::json!({"id": 9}),
+            Some(trace.to_string()),
+        );
+        outbox
+            .deliver_due(
+                &HttpWebhookSender,
+                &format!("http://{address}/events"),
+                Duration::from_secs(300),
+                Utc::now(),
+            )
+            .await;
+        assert_eq!(outbox.pending(), 0);
+
+        let request = capture.await.unwrap();
+        assert!(
+            request.starts_with("POST /events HTTP/1.1\r\n"),
+            "request: {request}"
+        );
+        assert!(
+            request.contains(&format!("traceparent: 00-{trace}-")),
+            "request: {request}"
+        );
+        assert!(request.contains("x-event-id: 1\r\n"), "request: {request}");
+        assert!(
+            request.contains("x-delivery-attempt: 1\r\n"),
+            "request: {request}"
+        );
+        assert!(
+            request.contains(r#""kind":"user.created""#),
+            "request: {request}"
+        );
+    }
+
+    #[test]
+    fn trace_id_parses_from_well_formed_traceparent_only() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            trace_id_from_headers(&headers).as_deref(),
+            Some("0af7651916cd43dd8448eb211c80319c")
+        );
+
+        for bad in ["garbage", "00-short-span-01", ""] {
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", bad.parse().unwrap());
+            assert_eq!(trace_id_from_headers(&headers), None, "value: {bad}");
+        }
+        assert_eq!(trace_id_from_headers(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn worker_drains_until_shutdown() {
+        let outbox = Arc::new(WebhookOutbox::default());
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let worker = tokio::spawn(delivery_worker(
+            outbox,
+            "http://127.0.0.1:9/events".to_string(),
+            Duration::from_secs(300),
+            async {
+                rx.await.ok();
+            },
+        ));
+        tx.send(()).unwrap();
+        worker.await.unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! Pins the liveness handler's zero-allocation guarantee.
+//!
+//! A counting global allocator is process-wide, so this lives in its own
+//! integration-test binary: inside the unit-test suite, allocations from
+//! concurrently running tests would make the counter meaningless. The
+//! test is deliberately runtime-free — a plain `#[test]` polling the
+//! handler future by hand — so the count covers the handler and nothing
+//! else.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The system allocator with an allocation counter bolted on. The
+/// default `realloc`/`alloc_zeroed` both route through `alloc`, so
+/// counting there catches every growth path.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Resolve a future that never yields with a single no-op-waker poll.
+fn poll_ready<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    let mut context = Context::from_waker(&waker);
+    match pin!(future).poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("the liveness handler never awaits"),
+    }
+}
+
+#[test]
+fn the_liveness_handler_allocates_nothing() {
+    // One warm-up call in case anything initializes lazily on first use.
+    poll_ready(rust_basic_api::routes::health_check());
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let body = poll_ready(rust_basic_api::routes::health_check());
+    let after = ALLOCATIONS.load(Ordering::SeqCst);
+
+    assert_eq!(body, "OK");
+    assert_eq!(
+        after - before,
+        0,
+        "the liveness handler hit the heap {} time(s)",
+        after - before
+    );
+}